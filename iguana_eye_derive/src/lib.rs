@@ -0,0 +1,556 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{quote, format_ident};
+use syn::{parse_macro_input, DeriveInput, Data, Fields, Field, Ident, Type, Expr, Path, LitStr, GenericArgument, PathArguments};
+
+// Generates the `Componentable` impl and column accessors for a component
+// stored as parallel `Vec` columns:
+//
+//     #[derive(Serialize, Deserialize, Componentable)]
+//     #[component(on_detach = release)]
+//     pub struct HealthComponent {
+//         pub component: Component,
+//         #[entity] entity: Vec<Entity>,
+//         #[column(default = 100)] health: Vec<i64>,
+//     }
+//
+// Errors are `crate::error::Error`, components are named by `get_hash()`.
+// Every column is also listed by `Reflect`, readonly and skip_row columns can't be set through it.
+//
+// Container options:
+//     id = "name", stable id saved files refer to the component by, defaults to the struct name.
+//         Renaming the struct keeps old saves loading as long as the id stays the same.
+//
+// Hooks, each naming a method on the component:
+//     on_attach = fn(&mut self, index: usize), called after the row is pushed
+//     on_detach = fn(&mut self, index: usize) -> Result<(), Error>, called before the row is removed
+//     before_load = fn(&mut self, index: usize) -> Result<(), Error>, called once `load_row` parsed every column,
+//         before it writes them
+//     on_load = fn(&mut self, index: usize), called after `load_row` wrote the columns
+//     update = fn(&mut self, dt: f32, game: &Game, commands: &mut Commands)
+//     render = fn(&mut self, dt: f32, game: &Game, viewport: &Viewport)
+//
+// Column options:
+//     default = expr, value pushed on attach instead of `Default::default()`
+//     readonly, only getters are generated and `Reflect` can't set it, `load_row` still writes it
+//     manual, no accessors are generated
+//     skip_row, left out of `save_row`/`load_row`, for derived data or entity ids
+//     map_entities, holds entity ids that `merge` renames, the type must implement `MapEntities`
+#[proc_macro_derive(Componentable, attributes(component, entity, column))]
+pub fn derive_componentable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(val) => val.into(),
+        Err(e) => e.to_compile_error().into()
+    }
+}
+
+#[derive(Default)]
+struct Hooks {
+    id: Option<LitStr>,
+    on_attach: Option<Path>,
+    on_detach: Option<Path>,
+    before_load: Option<Path>,
+    on_load: Option<Path>,
+    update: Option<Path>,
+    render: Option<Path>,
+}
+
+struct Column {
+    ident: Ident,
+    ty: Type,
+    default: Option<Expr>,
+    readonly: bool,
+    manual: bool,
+    skip_row: bool,
+    map_entities: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let hooks = parse_hooks(&input)?;
+
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(f) => &f.named,
+            _ => return Err(syn::Error::new_spanned(name, "Componentable needs named fields"))
+        },
+        _ => return Err(syn::Error::new_spanned(name, "Componentable can only be derived for structs"))
+    };
+
+    if !fields.iter().any(|f| f.ident.as_ref().is_some_and(|i| i == "component")) {
+        return Err(syn::Error::new_spanned(name, "Componentable needs a `component: Component` field"))
+    }
+
+    let mut entity: Option<Ident> = None;
+    let mut columns = Vec::new();
+    for f in fields.iter() {
+        if f.attrs.iter().any(|a| a.path().is_ident("entity")) {
+            if entity.is_some() {
+                return Err(syn::Error::new_spanned(f, "only one field can be marked #[entity]"))
+            }
+            entity = f.ident.clone();
+        }
+
+        if let Some(c) = parse_column(f)? {
+            columns.push(c);
+        }
+    }
+
+    let entity = match entity {
+        Some(val) => val,
+        None => return Err(syn::Error::new_spanned(name, "Componentable needs an #[entity] entity: Vec<Entity> field"))
+    };
+
+    let col_idents: Vec<&Ident> = columns.iter().map(|c| &c.ident).collect();
+    let col_defaults: Vec<proc_macro2::TokenStream> = columns.iter()
+        .map(|c| match &c.default {
+            Some(expr) => quote!(#expr),
+            None => quote!(::std::default::Default::default())
+        })
+        .collect();
+
+    let on_attach = hooks.on_attach.map(|p| quote!(self.#p(index);));
+    let on_detach = hooks.on_detach.map(|p| quote!(self.#p(to_remove)?;));
+    let before_load = hooks.before_load.map(|p| quote!(self.#p(index)?;));
+    let on_load = hooks.on_load.map(|p| quote!(self.#p(index);));
+
+    let row_idents: Vec<&Ident> = columns.iter()
+        .filter(|c| !c.skip_row)
+        .map(|c| &c.ident)
+        .collect();
+    let row_keys: Vec<String> = row_idents.iter().map(|i| i.to_string()).collect();
+    let row_tys: Vec<&Type> = columns.iter()
+        .filter(|c| !c.skip_row)
+        .map(|c| &c.ty)
+        .collect();
+    let row_temps: Vec<Ident> = row_idents.iter().map(|i| format_ident!("loaded_{}", i)).collect();
+    let all_idents: Vec<&Ident> = columns.iter().map(|c| &c.ident).collect();
+    let all_keys: Vec<String> = all_idents.iter().map(|i| i.to_string()).collect();
+    let all_tys: Vec<&Type> = columns.iter().map(|c| &c.ty).collect();
+    let all_readonly: Vec<bool> = columns.iter().map(|c| c.readonly || c.skip_row).collect();
+    let readonly_keys: Vec<String> = columns.iter()
+        .filter(|c| c.readonly || c.skip_row)
+        .map(|c| c.ident.to_string())
+        .collect();
+    let mapped_idents: Vec<&Ident> = columns.iter()
+        .filter(|c| c.map_entities)
+        .map(|c| &c.ident)
+        .collect();
+    let id = hooks.id.unwrap_or_else(|| LitStr::new(&name.to_string(), name.span()));
+    let update = hooks.update.map(|p| quote!(self.#p(dt, game, commands);));
+    let render = hooks.render.map(|p| quote!(self.#p(dt, game, viewport);));
+
+    let mut getters = Vec::new();
+    let mut setters = Vec::new();
+    let mut row = Vec::new();
+    let mut row_mut = Vec::new();
+    for c in columns.iter().filter(|c| !c.manual) {
+        let ident = &c.ident;
+        let ty = &c.ty;
+        let get = format_ident!("get_{}", ident);
+        let set = format_ident!("set_{}", ident);
+
+        getters.push(quote! {
+            pub fn #get(&self, index: usize) -> Option<&#ty> {
+                if !self.component.bounds_check(index) {
+                    return None
+                }
+
+                self.#ident.get(index)
+            }
+        });
+
+        row.push(quote! {
+            pub fn #ident(&self) -> &'a #ty {
+                &self.get().#ident[self.index]
+            }
+        });
+
+        row_mut.push(quote! {
+            pub fn #ident(&self) -> &#ty {
+                &self.get().#ident[self.index]
+            }
+        });
+
+        if c.readonly {
+            continue;
+        }
+
+        setters.push(quote! {
+            pub fn #set(&mut self, index: usize, value: #ty) -> bool {
+                if !self.component.bounds_check(index) {
+                    return false
+                }
+
+                self.#ident[index] = value;
+                let e = self.#entity[index];
+                self.component.mark_changed(e);
+
+                true
+            }
+        });
+
+        row_mut.push(quote! {
+            pub fn #set(&mut self, value: #ty) -> bool {
+                let index = self.index;
+                self.get_mut().#set(index, value)
+            }
+        });
+    }
+
+    let ecs = quote!(crate::system::ecs);
+    let error = quote!(crate::error::Error);
+
+    Ok(quote! {
+        #[::typetag::serde(name = #id)]
+        impl #ecs::component_manager::component::Componentable for #name {
+            fn attach(&mut self, entity: #ecs::entity::Entity) -> ::std::result::Result<usize, #error> {
+                if self.component.does_exist(&entity) {
+                    return Err(#error::ComponentAlreadyAttached { entity, component: self.get_hash() })
+                }
+
+                let index = self.#entity.len();
+                self.component.entities.insert(entity, index);
+
+                self.#entity.push(entity);
+                #(self.#col_idents.push(#col_defaults);)*
+
+                #on_attach
+
+                Ok(index)
+            }
+
+            fn detach(&mut self, entity: #ecs::entity::Entity) -> ::std::result::Result<(), #error> {
+                let to_remove = match self.component.find_index(&entity) {
+                    Some(val) => val,
+                    None => return Err(#error::ComponentNotAttached { entity, component: self.get_hash() })
+                };
+
+                #on_detach
+
+                self.#entity.swap_remove(to_remove);
+                #(self.#col_idents.swap_remove(to_remove);)*
+
+                if to_remove < self.#entity.len() {
+                    let swapped = self.#entity[to_remove];
+                    self.component.entities.insert(swapped, to_remove);
+                }
+                self.component.entities.remove(&entity);
+
+                Ok(())
+            }
+
+            #[allow(unused_variables)]
+            fn handle_update(&mut self, dt: f32, game: &crate::game::Game, commands: &mut #ecs::commands::Commands) {
+                #update
+            }
+
+            #[allow(unused_variables)]
+            fn handle_render(&mut self, dt: f32, game: &crate::game::Game, viewport: &crate::app::Viewport) {
+                #render
+            }
+
+            fn is_empty(&self) -> bool {
+                self.component.entities.is_empty()
+            }
+
+            fn id() -> &'static str where Self: Sized {
+                #id
+            }
+
+            fn get_id(&self) -> &'static str {
+                #id
+            }
+
+            fn get_hash(&self) -> u64 {
+                #ecs::component_manager::ComponentManager::type_hash::<Self>()
+            }
+
+            fn entity_at(&self, index: usize) -> ::std::option::Option<#ecs::entity::Entity> {
+                self.#entity.get(index).copied()
+            }
+
+            #[allow(unused_mut)]
+            fn save_row(&self, index: usize) -> ::std::option::Option<::serde_json::Value> {
+                if !self.component.bounds_check(index) {
+                    return None
+                }
+
+                let mut row = ::serde_json::Map::new();
+                #(row.insert(::std::string::String::from(#row_keys), ::serde_json::to_value(&self.#row_idents[index]).ok()?);)*
+
+                Some(::serde_json::Value::Object(row))
+            }
+
+            fn load_row(&mut self, index: usize, value: &::serde_json::Value) -> ::std::result::Result<(), #error> {
+                if !self.component.bounds_check(index) {
+                    return Err(#error::OutOfBounds { index, len: self.#entity.len() })
+                }
+
+                let row = match value.as_object() {
+                    Some(val) => val,
+                    None => {
+                        return Err(#error::InvalidRow {
+                            component: self.get_hash(),
+                            msg: ::std::string::String::from("row isn't an object"),
+                        })
+                    }
+                };
+
+                // Every column is parsed before anything is written, a bad one leaves the row untouched
+                #(let mut #row_temps: ::std::option::Option<#row_tys> = None;)*
+                for (key, value) in row.iter() {
+                    let result: ::std::result::Result<(), ::serde_json::Error> = match key.as_str() {
+                        #(#row_keys => ::serde_json::from_value(value.clone()).map(|v| #row_temps = Some(v)),)*
+                        _ => return Err(#error::UnknownColumn { component: self.get_hash(), column: key.clone() })
+                    };
+
+                    if let Err(e) = result {
+                        return Err(#error::InvalidRow { component: self.get_hash(), msg: format!("{}: {}", key, e) })
+                    }
+                }
+
+                #before_load
+
+                // Columns missing from `value` keep their current value
+                #(if let Some(v) = #row_temps {
+                    self.#row_idents[index] = v;
+                })*
+
+                #on_load
+
+                let e = self.#entity[index];
+                self.component.mark_changed(e);
+
+                Ok(())
+            }
+
+            fn merge(
+                &mut self,
+                other: &mut dyn #ecs::component_manager::component::Componentable,
+                map: &::std::collections::HashMap<#ecs::entity::Entity, #ecs::entity::Entity>
+            ) -> ::std::result::Result<(), #error> {
+
+                let other = match other.as_any_mut().downcast_mut::<Self>() {
+                    Some(val) => val,
+                    None => return Err(#error::ComponentMismatch(self.get_hash()))
+                };
+
+                for e in other.#entity.iter() {
+                    match map.get(e) {
+                        Some(mapped) if self.component.does_exist(mapped) => {
+                            return Err(#error::ComponentAlreadyAttached { entity: *mapped, component: self.get_hash() })
+                        },
+                        Some(_) => {},
+                        None => return Err(#error::EntityNotMapped(*e))
+                    }
+                }
+
+                let start = self.#entity.len();
+                for e in other.#entity.drain(..) {
+                    let mapped = map[&e];
+                    self.component.entities.insert(mapped, self.#entity.len());
+                    self.#entity.push(mapped);
+                }
+                #(self.#col_idents.append(&mut other.#col_idents);)*
+                other.component.entities.clear();
+
+                for index in start..self.#entity.len() {
+                    #(#ecs::entity::MapEntities::map_entities(&mut self.#mapped_idents[index], map);)*
+                    #on_load
+
+                    let e = self.#entity[index];
+                    self.component.mark_added(e);
+                }
+
+                Ok(())
+            }
+
+            fn component(&self) -> &#ecs::component_manager::component::Component {
+                &self.component
+            }
+
+            fn component_mut(&mut self) -> &mut #ecs::component_manager::component::Component {
+                &mut self.component
+            }
+
+            fn as_any(&self) -> &dyn ::std::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn ::std::any::Any {
+                self
+            }
+        }
+
+        impl #ecs::component_manager::component::reflect::Reflect for #name {
+            fn field_infos(&self) -> ::std::vec::Vec<#ecs::component_manager::component::reflect::FieldInfo> {
+                vec![#(#ecs::component_manager::component::reflect::FieldInfo {
+                    name: #all_keys,
+                    ty: ::std::any::type_name::<#all_tys>(),
+                    readonly: #all_readonly,
+                },)*]
+            }
+
+            fn get_field(&self, entity: #ecs::entity::Entity, name: &str) -> ::std::result::Result<::serde_json::Value, #error> {
+                use #ecs::component_manager::component::Componentable;
+
+                let index = match self.component.find_index(&entity) {
+                    Some(val) => val,
+                    None => return Err(#error::ComponentNotAttached { entity, component: self.get_hash() })
+                };
+
+                match name {
+                    #(#all_keys => ::serde_json::to_value(&self.#all_idents[index]).map_err(|e| #error::Serialize(e.to_string())),)*
+                    _ => Err(#error::UnknownColumn { component: self.get_hash(), column: ::std::string::String::from(name) })
+                }
+            }
+
+            fn set_field(
+                &mut self,
+                entity: #ecs::entity::Entity,
+                name: &str,
+                value: ::serde_json::Value
+            ) -> ::std::result::Result<(), #error> {
+                use #ecs::component_manager::component::Componentable;
+
+                let index = match self.component.find_index(&entity) {
+                    Some(val) => val,
+                    None => return Err(#error::ComponentNotAttached { entity, component: self.get_hash() })
+                };
+
+                let row_keys: &[&str] = &[#(#row_keys),*];
+                let readonly_keys: &[&str] = &[#(#readonly_keys),*];
+                if readonly_keys.contains(&name) {
+                    return Err(#error::ReadOnlyColumn { component: self.get_hash(), column: ::std::string::String::from(name) })
+                }
+                if !row_keys.contains(&name) {
+                    return Err(#error::UnknownColumn { component: self.get_hash(), column: ::std::string::String::from(name) })
+                }
+
+                let mut row = ::serde_json::Map::new();
+                row.insert(::std::string::String::from(name), value);
+                self.load_row(index, &::serde_json::Value::Object(row))
+            }
+        }
+
+        impl #name {
+            pub fn get_entity(&self, index: usize) -> Option<#ecs::entity::Entity> {
+                if !self.component.bounds_check(index) {
+                    return None
+                }
+
+                Some(self.#entity[index])
+            }
+
+            #(#getters)*
+            #(#setters)*
+        }
+
+        impl<'a> #ecs::query::Row<'a, #name> {
+            #(#row)*
+        }
+
+        impl<'a> #ecs::query::RowMut<'a, #name> {
+            #(#row_mut)*
+        }
+    })
+}
+
+fn parse_hooks(input: &DeriveInput) -> syn::Result<Hooks> {
+    let mut hooks = Hooks::default();
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("component")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                hooks.id = Some(meta.value()?.parse::<LitStr>()?);
+                return Ok(())
+            }
+
+            let slot = if meta.path.is_ident("on_attach") {
+                &mut hooks.on_attach
+            } else if meta.path.is_ident("on_detach") {
+                &mut hooks.on_detach
+            } else if meta.path.is_ident("before_load") {
+                &mut hooks.before_load
+            } else if meta.path.is_ident("on_load") {
+                &mut hooks.on_load
+            } else if meta.path.is_ident("update") {
+                &mut hooks.update
+            } else if meta.path.is_ident("render") {
+                &mut hooks.render
+            } else {
+                return Err(meta.error("expected id, on_attach, on_detach, before_load, on_load, update or render"))
+            };
+
+            *slot = Some(meta.value()?.parse::<Path>()?);
+            Ok(())
+        })?;
+    }
+
+    Ok(hooks)
+}
+
+fn parse_column(field: &Field) -> syn::Result<Option<Column>> {
+    let attr = match field.attrs.iter().find(|a| a.path().is_ident("column")) {
+        Some(val) => val,
+        None => return Ok(None)
+    };
+
+    let ident = match &field.ident {
+        Some(val) => val.clone(),
+        None => return Err(syn::Error::new(Span::call_site(), "columns need a name"))
+    };
+
+    let mut column = Column {
+        ident,
+        ty: vec_element(&field.ty)?,
+        default: None,
+        readonly: false,
+        manual: false,
+        skip_row: false,
+        map_entities: false,
+    };
+
+    // A bare #[column] has no arguments to parse
+    if matches!(attr.meta, syn::Meta::List(_)) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("default") {
+                column.default = Some(meta.value()?.parse::<Expr>()?);
+            } else if meta.path.is_ident("readonly") {
+                column.readonly = true;
+            } else if meta.path.is_ident("manual") {
+                column.manual = true;
+            } else if meta.path.is_ident("skip_row") {
+                column.skip_row = true;
+            } else if meta.path.is_ident("map_entities") {
+                column.map_entities = true;
+            } else {
+                return Err(meta.error("expected default, readonly, manual, skip_row or map_entities"))
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(Some(column))
+}
+
+// `T` out of a `Vec<T>` column
+fn vec_element(ty: &Type) -> syn::Result<Type> {
+    if let Type::Path(p) = ty {
+        if let Some(last) = p.path.segments.last() {
+            if last.ident == "Vec" {
+                if let PathArguments::AngleBracketed(args) = &last.arguments {
+                    if let Some(GenericArgument::Type(t)) = args.args.first() {
+                        return Ok(t.clone())
+                    }
+                }
+            }
+        }
+    }
+
+    Err(syn::Error::new_spanned(ty, "columns must be a Vec<T>"))
+}
@@ -12,6 +12,7 @@ use crate::game::Game;
 use crate::editor::UI;
 use crate::util::file;
 use crate::util::serialize;
+use crate::graphics::render_graph::{RenderGraph, AttachmentPool};
 
 pub struct Application {
     pub width: u32,
@@ -20,13 +21,14 @@ pub struct Application {
     pub viewport: Viewport,
     pub game: Game,
     pub ui: UI,
+    pub attachments: AttachmentPool,
 }
 
 impl Application {
     pub async fn new(config_path: &str, icon_path: &str, event_loop: &EventLoop<()>) -> Self {
         
         // Load app config file
-        let config: Config = match serialize::read(config_path) {
+        let config: Config = match serialize::read_versioned(config_path) {
             Ok(val) => val,
             Err(e) => {
                 eprintln!("{e} - [app::application::new()][config]");
@@ -81,6 +83,7 @@ impl Application {
             viewport,
             game,
             ui,
+            attachments: AttachmentPool::new(),
         }
     }
 
@@ -130,12 +133,23 @@ impl Application {
     fn handle_render(&mut self, dt: f32) {
         let window = &self.window;
         let viewport = &self.viewport;
-        let mut frame = Frame::begin(&self.viewport);
-        {
-            self.game.handle_render(window, viewport, &mut frame, dt);
-            self.ui.handle_render(window, viewport, &self.game, &mut frame, dt);
+        let frame = match Frame::begin(&self.viewport) {
+            Ok(val) => val,
+            Err(e) => {
+                eprintln!("{e} - [app::application::handle_render()]");
+                return
+            }
+        };
+        let mut graph = RenderGraph::new();
+        let target = graph.import_view("frame", frame.view());
+        let depth = graph.import_view("depth", frame.depth_view());
+        self.game.handle_render(window, viewport, &mut graph, target, depth, dt);
+        self.ui.handle_render(window, viewport, &self.game, &mut graph, target, dt);
+
+        if let Err(e) = graph.execute(&viewport.device, &viewport.queue, &mut self.attachments) {
+            eprintln!("{e} - [app::application::handle_render()]");
         }
-        frame.end(&self.viewport);
+        frame.end();
     }
 
     fn handle_resize(&mut self, size: PhysicalSize<u32>) {
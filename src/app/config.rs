@@ -1,4 +1,8 @@
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::util::migration::{self, Migrations, Versioned};
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
@@ -15,4 +19,18 @@ impl Config {
             fullscreen: false,
         }
     }
-}
\ No newline at end of file
+}
+
+impl Versioned for Config {
+    fn migrations() -> Migrations {
+        Migrations::new("Config")
+            .add(Self::add_missing_fields)
+    }
+}
+
+impl Config {
+    // Files from before the version only have the fields they were written with
+    fn add_missing_fields(value: &mut Value) -> Result<(), Error> {
+        migration::fill_defaults(value, Self::default())
+    }
+}
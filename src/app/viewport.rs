@@ -1,18 +1,26 @@
-use wgpu::{SurfaceTexture, TextureView, CommandEncoder, RenderPass};
+use wgpu::{SurfaceTexture, TextureView};
 use winit::{window::Window, dpi::PhysicalSize};
 
+use crate::error::Error;
+use crate::graphics::depth::DEPTH_FORMAT;
+
+// Format of headless viewports, the same sRGB kind windows usually get
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
 pub struct Viewport {
     pub size: winit::dpi::PhysicalSize<u32>,
-    pub surface: wgpu::Surface,
+    pub surface: Option<wgpu::Surface>, // None when headless
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
+    offscreen: Option<wgpu::Texture>, // render target without a window
+    depth: wgpu::Texture, // same size as the surface, recreated on resize
 }
 
 pub struct Frame {
-    frame: SurfaceTexture,
+    frame: Option<SurfaceTexture>, // None when headless
     view: TextureView,
-    encoder: CommandEncoder,
+    depth: TextureView,
 }
 
 impl Viewport {
@@ -71,72 +79,237 @@ impl Viewport {
         };
 
         surface.configure(&device, &config);
+        let depth = Self::create_depth(&device, &config);
 
         Self {
             size,
-            surface, 
+            surface: Some(surface), 
             device, 
             queue,
             config,
+            offscreen: None,
+            depth,
+        }
+    }
+
+    // A viewport without a window that renders into an offscreen texture.
+    // Prefers the fallback (software) adapter so it runs on machines without a GPU.
+    pub async fn headless(width: u32, height: u32) -> Result<Self, Error> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: Default::default(),
+        });
+
+        let mut adapter = None;
+        for force_fallback_adapter in [true, false] {
+            adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter,
+            }).await;
+
+            if adapter.is_some() {
+                break;
+            }
         }
+
+        let adapter = match adapter {
+            Some(val) => val,
+            None => return Err(Error::NoAdapter)
+        };
+
+        let (device, queue) = match adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            },
+            None
+        ).await {
+            Ok(val) => val,
+            Err(e) => return Err(Error::Gpu(e.to_string()))
+        };
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: OFFSCREEN_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+
+        let offscreen = Self::create_offscreen(&device, &config);
+        let depth = Self::create_depth(&device, &config);
+
+        Ok(Self {
+            size: PhysicalSize::new(width, height),
+            surface: None,
+            device,
+            queue,
+            config,
+            offscreen: Some(offscreen),
+            depth,
+        })
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_none()
     }
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
         self.size = size;
         self.config.width = size.width;
         self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
+
+        match &self.surface {
+            Some(surface) => surface.configure(&self.device, &self.config),
+            None => self.offscreen = Some(Self::create_offscreen(&self.device, &self.config))
+        }
+
+        self.depth = Self::create_depth(&self.device, &self.config);
     }
-}
 
-impl Frame {
-    pub fn begin(viewport: &Viewport) -> Self {
-        let frame = viewport.surface.get_current_texture()
-            .expect("ERROR::app::viewport::frame::begin()::cannot get current texture");
+    // Copies the offscreen texture of a headless viewport to memory,
+    // as tightly packed rows of `config.format` pixels
+    pub fn read_pixels(&self) -> Result<Vec<u8>, Error> {
+        let texture = match &self.offscreen {
+            Some(val) => val,
+            None => return Err(Error::NotInitialized("offscreen target"))
+        };
 
-        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        
-        let encoder = viewport.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: None,
+        let (width, height) = (self.config.width, self.config.height);
+        let unpadded = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded = unpadded.div_ceil(align) * align;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: (padded * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
         });
 
-        Self { frame, view, encoder }
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("readback"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        match receiver.recv() {
+            Ok(Ok(())) => {},
+            Ok(Err(e)) => return Err(Error::Gpu(e.to_string())),
+            Err(e) => return Err(Error::Gpu(e.to_string()))
+        }
+
+        let mut pixels = Vec::with_capacity((unpadded * height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded as usize) {
+                pixels.extend_from_slice(&row[..unpadded as usize]);
+            }
+        }
+        buffer.unmap();
+
+        return Ok(pixels)
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), Error> {
+        let pixels = self.read_pixels()?;
+        let image = match image::RgbaImage::from_raw(self.config.width, self.config.height, pixels) {
+            Some(val) => val,
+            None => return Err(Error::Image(String::from("pixel count doesn't match the viewport size")))
+        };
+
+        match image.save_with_format(path, image::ImageFormat::Png) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Error::Image(e.to_string()))
+        }
     }
 
-    pub fn render_pass_game(&mut self) -> RenderPass {
-        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: None,
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.2, g: 0.2, b: 0.2, a: 1.0,
-                    }),
-                    store: true,
-                }
-            })],
-            depth_stencil_attachment: None,
+    fn create_offscreen(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen"),
+            size: wgpu::Extent3d { width: config.width, height: config.height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+            view_formats: &[],
         })
     }
 
-    pub fn render_pass_ui(&mut self) -> RenderPass {
-        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: None,
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
-                    store: true,
-                }
-            })],
-            depth_stencil_attachment: None,
+    fn create_depth(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth"),
+            size: wgpu::Extent3d { width: config.width, height: config.height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
         })
     }
+}
 
-    pub fn end(self, viewport: &Viewport) {
-        viewport.queue.submit(std::iter::once(self.encoder.finish()));
-        self.frame.present();
+impl Frame {
+    pub fn begin(viewport: &Viewport) -> Result<Self, Error> {
+        let depth = viewport.depth.create_view(&wgpu::TextureViewDescriptor::default());
+
+        if let Some(texture) = &viewport.offscreen {
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            return Ok(Self { frame: None, view, depth })
+        }
+
+        let surface = match &viewport.surface {
+            Some(val) => val,
+            None => return Err(Error::NotInitialized("surface"))
+        };
+
+        let frame = match surface.get_current_texture() {
+            Ok(val) => val,
+            Err(e) => return Err(Error::Gpu(e.to_string()))
+        };
+
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Ok(Self { frame: Some(frame), view, depth })
+    }
+
+    // The surface texture, import it into the frame's `RenderGraph`
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    // The viewport's depth and stencil texture, cleared by the first pass using it
+    pub fn depth_view(&self) -> &TextureView {
+        &self.depth
     }
-}
\ No newline at end of file
+
+    pub fn end(self) {
+        if let Some(frame) = self.frame {
+            frame.present();
+        }
+    }
+}
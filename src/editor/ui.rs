@@ -6,7 +6,8 @@ use imgui_wgpu::{Renderer, RendererConfig};
 use imgui_winit_support::WinitPlatform;
 use winit::{window::Window, dpi::PhysicalSize};
 use winit::event::{KeyboardInput, ModifiersState, MouseButton, Event, ElementState};
-use crate::app::{Viewport, Frame};
+use crate::app::Viewport;
+use crate::graphics::render_graph::{RenderGraph, Resource, PassDesc};
 use crate::game::Game;
 use crate::util::file;
 
@@ -88,7 +89,8 @@ impl UI {
         window: &Window, 
         viewport: &Viewport, 
         _game: &Game,
-        frame: &mut Frame, 
+        graph: &mut RenderGraph<'a>, 
+        target: Resource,
         _dt: f32) {
 
         self.platform.prepare_frame(self.imgui.io_mut(), window)
@@ -122,18 +124,13 @@ impl UI {
         }
 
         // Rendering done here
-        {
-            let mut rp = frame.render_pass_ui();
-
-            self.platform.prepare_render(ui, window);
-            self.renderer.render(
-                self.imgui.render(),
-                &viewport.queue,
-                &viewport.device,
-                &mut rp
-            ).expect("ERROR::editor::ui::render()::failed to render");
-        }
-        
+        self.platform.prepare_render(ui, window);
+        let draw_data = self.imgui.render();
+        let renderer = &mut self.renderer;
+        graph.add_render_pass(PassDesc::new("ui").color(target, wgpu::LoadOp::Load), move |rp, ctx| {
+            renderer.render(draw_data, ctx.queue, ctx.device, rp)
+                .expect("ERROR::editor::ui::render()::failed to render");
+        });
     }
 
     pub fn handle_modifiers(&self, _mod: &ModifiersState) {
@@ -0,0 +1,156 @@
+use std::{fmt, io};
+
+use crate::system::ecs::entity::Entity;
+
+// Every failure of the crate. Components are named by their hash, see `ComponentManager::type_hash`.
+#[derive(Debug)]
+pub enum Error {
+    // ECS
+    EntityNotFound(Entity),
+    EntityNotMapped(Entity),
+    SpawnedEntityMissing(usize), // index of the spawn command
+    ComponentNotRegistered(u64),
+    ComponentAlreadyRegistered(u64),
+    ComponentIdCollision { hash: u64, id: &'static str, other: &'static str },
+    ComponentNotEmpty(u64),
+    ComponentMismatch(u64),
+    ComponentNotAttached { entity: Entity, component: u64 },
+    ComponentAlreadyAttached { entity: Entity, component: u64 },
+    UnknownColumn { component: u64, column: String },
+    ReadOnlyColumn { component: u64, column: String },
+    InvalidRow { component: u64, msg: String },
+    HasChildren(Entity),
+    HierarchyCycle { parent: Entity, child: Entity },
+    OutOfBounds { index: usize, len: usize },
+    AccessConflict(u64), // component or resource hash
+    AccessNotDeclared(u64),
+    EventNotRegistered(&'static str),
+    DuplicateSystem(String),
+    UnknownSystem { system: String, dependency: String },
+    CrossStageOrdering { system: String, other: String },
+    OrderingCycle(String), // stage
+    System { name: String, source: Box<Error> },
+    SystemPanicked(String), // panic message
+    ThreadPool(String),
+    InvalidPrefab(&'static str),
+
+    // Files and saved data
+    Io(io::Error),
+    InvalidPath(String),
+    Serialize(String),
+    Deserialize { path: String, line: usize, col: usize, msg: String },
+    UnsupportedVersion { kind: String, version: u32, current: u32 },
+    Migration { kind: String, from: u32, source: Box<Error> },
+    InvalidData(String),
+
+    // Graphics
+    ShaderCompile { path: String, msg: String },
+    NotInitialized(&'static str), // GPU object that was never created
+    NoAdapter,
+    Gpu(String),
+    Image(String),
+    ResourceNotWritten { pass: String, resource: String },
+    PassCycle(String), // a pass on the cycle
+}
+
+impl Error {
+    pub fn deserialize(e: &serde_json::Error) -> Self {
+        Self::Deserialize {
+            path: String::new(),
+            line: e.line(),
+            col: e.column(),
+            msg: e.to_string(),
+        }
+    }
+
+    // Names the file a `Deserialize` error came from
+    pub fn in_file(self, file: &str) -> Self {
+        match self {
+            Self::Deserialize { line, col, msg, .. } => Self::Deserialize { path: String::from(file), line, col, msg },
+            e => e
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EntityNotFound(e) => write!(f, "entity {e:?} doesn't exist or is stale"),
+            Self::EntityNotMapped(e) => write!(f, "entity {e:?} has no entry in the entity map"),
+            Self::SpawnedEntityMissing(i) => write!(f, "entity of spawn command {i} doesn't exist"),
+            Self::ComponentNotRegistered(c) => write!(f, "component {c} isn't added"),
+            Self::ComponentAlreadyRegistered(c) => write!(f, "component {c} is already added"),
+            Self::ComponentIdCollision { hash, id, other } => {
+                write!(f, "component ids {id} and {other} of different types both hash to {hash}")
+            },
+            Self::ComponentNotEmpty(c) => write!(f, "component {c} still has rows"),
+            Self::ComponentMismatch(c) => write!(f, "component {c} can't be merged with another type"),
+            Self::ComponentNotAttached { entity, component } => {
+                write!(f, "entity {entity:?} has no row in component {component}")
+            },
+            Self::ComponentAlreadyAttached { entity, component } => {
+                write!(f, "entity {entity:?} already has a row in component {component}")
+            },
+            Self::UnknownColumn { component, column } => write!(f, "component {component} has no column {column}"),
+            Self::ReadOnlyColumn { component, column } => {
+                write!(f, "column {column} of component {component} is derived and can't be set")
+            },
+            Self::InvalidRow { component, msg } => write!(f, "invalid row for component {component}: {msg}"),
+            Self::HasChildren(e) => write!(f, "entity {e:?} still has children"),
+            Self::HierarchyCycle { parent, child } => {
+                write!(f, "{parent:?} is {child:?} or one of its descendants")
+            },
+            Self::OutOfBounds { index, len } => write!(f, "index {index} is out of bounds for length {len}"),
+            Self::AccessConflict(c) => write!(f, "conflicting access to {c}"),
+            Self::AccessNotDeclared(c) => write!(f, "access to {c} wasn't declared"),
+            Self::EventNotRegistered(name) => write!(f, "event {name} isn't registered"),
+            Self::DuplicateSystem(name) => write!(f, "system {name} already exist"),
+            Self::UnknownSystem { system, dependency } => write!(f, "{system} depends on unknown system {dependency}"),
+            Self::CrossStageOrdering { system, other } => {
+                write!(f, "{system} cannot be ordered against {other} in another stage")
+            },
+            Self::OrderingCycle(stage) => write!(f, "ordering cycle in stage {stage}"),
+            Self::System { name, source } => write!(f, "system {name}: {source}"),
+            Self::SystemPanicked(msg) => write!(f, "panicked: {msg}"),
+            Self::ThreadPool(msg) => write!(f, "cannot start worker threads: {msg}"),
+            Self::InvalidPrefab(msg) => write!(f, "invalid prefab: {msg}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::InvalidPath(path) => write!(f, "invalid path {path}"),
+            Self::Serialize(msg) => write!(f, "cannot serialize: {msg}"),
+            Self::Deserialize { path, line, col, msg } => match path.is_empty() {
+                true => write!(f, "cannot deserialize: {msg}"),
+                false => write!(f, "cannot deserialize {path}:{line}:{col}: {msg}")
+            },
+            Self::UnsupportedVersion { kind, version, current } => {
+                write!(f, "{kind} version {version} is newer than {current}")
+            },
+            Self::Migration { kind, from, source } => write!(f, "{kind} migration {from} -> {}: {source}", from + 1),
+            Self::InvalidData(msg) => write!(f, "invalid data: {msg}"),
+            Self::ShaderCompile { path, msg } => write!(f, "cannot compile shader {path}: {msg}"),
+            Self::NotInitialized(what) => write!(f, "{what} isn't initialized"),
+            Self::NoAdapter => write!(f, "no graphics adapter is available"),
+            Self::Gpu(msg) => write!(f, "gpu error: {msg}"),
+            Self::Image(msg) => write!(f, "image error: {msg}"),
+            Self::ResourceNotWritten { pass, resource } => {
+                write!(f, "pass {pass} reads {resource}, which no pass writes")
+            },
+            Self::PassCycle(pass) => write!(f, "render pass {pass} depends on itself"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::System { source, .. } | Self::Migration { source, .. } => Some(source.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -2,9 +2,13 @@ use serde::{Serialize, Deserialize};
 use winit::{window::Window, dpi::PhysicalSize};
 use winit::event::{KeyboardInput, ModifiersState, MouseButton, ElementState};
 
-use crate::app::{Viewport, Frame};
+use crate::app::Viewport;
+use crate::graphics::render_graph::{RenderGraph, Resource, PassDesc};
+use crate::graphics::depth::DEPTH_CLEAR;
 use crate::system::input::Input;
 
+const CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.2, g: 0.2, b: 0.2, a: 1.0 };
+
 #[derive(Serialize, Deserialize)]
 pub struct Game {
     pub input: Input,
@@ -23,13 +27,18 @@ impl Game {
 
     }
 
-    pub fn handle_render(&self, 
+    pub fn handle_render<'a>(&'a self, 
         _window: &Window, 
         _viewport: &Viewport, 
-        frame: &mut Frame, 
+        graph: &mut RenderGraph<'a>, 
+        target: Resource,
+        depth: Resource,
         _dt: f32
     ) {
-        let _rp = frame.render_pass_game();
+        let desc = PassDesc::new("game")
+            .color(target, wgpu::LoadOp::Clear(CLEAR_COLOR))
+            .depth(depth, wgpu::LoadOp::Clear(DEPTH_CLEAR), Some(wgpu::LoadOp::Clear(0)));
+        graph.add_render_pass(desc, |_rp, _ctx| {});
     }
 
     pub fn handle_resize(&mut self, _size: PhysicalSize<u32>) {
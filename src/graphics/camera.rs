@@ -1,28 +1,80 @@
-use std::io;
-
 use wgpu::{Buffer, BindGroup, Device, Queue, BindGroupLayout};
 use serde::{Serialize, Deserialize};
-use cgmath::{Point3, Vector3, Matrix4};
+use serde_json::Value;
+use cgmath::{Point3, Vector3, Matrix4, Deg};
 
 use super::uniform_buffer::CameraUBuffer;
+use crate::error::Error;
+use crate::util::migration::{Migrations, Versioned};
 
+// Maps the -1..1 clip depth of cgmath's OpenGL projections to the 0..1 wgpu expects
 const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    // Visible area is the viewport size multiplied by scale
+    Orthographic { scale: f32, znear: f32, zfar: f32 },
+    // Vertical field of view in degrees, aspect follows the viewport
+    Perspective { fovy: f32, znear: f32, zfar: f32 },
+}
+
+impl Projection {
+    pub fn orthographic() -> Self {
+        return Projection::Orthographic { scale: 1.0, znear: -1000.0, zfar: 1000.0 };
+    }
+
+    pub fn perspective(fovy: f32) -> Self {
+        return Projection::Perspective { fovy, znear: 0.1, zfar: 1000.0 };
+    }
+
+    pub fn is_perspective(&self) -> bool {
+        return matches!(self, Projection::Perspective { .. });
+    }
+
+    pub fn matrix(&self, width: f32, height: f32) -> Matrix4<f32> {
+        match *self {
+            Projection::Orthographic { scale, znear, zfar } => {
+                let w = width * scale;
+                let h = height * scale;
+
+                cgmath::ortho(
+                    w / 2.0,
+                    -w / 2.0,
+                    -h / 2.0,
+                    h / 2.0,
+                    znear,
+                    zfar
+                )
+            },
+            Projection::Perspective { fovy, znear, zfar } => {
+                let aspect = if height > 0.0 { width / height } else { 1.0 };
+
+                cgmath::perspective(Deg(fovy), aspect, znear, zfar)
+            }
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        return Projection::orthographic();
+    }
+}
+
+// Saved with `serialize::write_versioned`, older layouts are upgraded by its migrations
 #[derive(Serialize, Deserialize)]
 pub struct Camera {
-    projection: Matrix4<f32>,
-    eye: Point3<f32>,
-    target: Point3<f32>,
-    up: Vector3<f32>,
+    pub projection: Projection,
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
     width: f32,
     height: f32,
-    znear: f32,
-    zfar: f32,
 
     #[serde(skip)]
     pub camera_buffer: Option<CameraUBuffer>,
@@ -39,31 +91,41 @@ pub struct Camera {
 
 impl Camera {
     pub fn new(device: &Device, width: f32, height: f32) -> Self {
-        
-        let (camera_buffer, u_buffer) = CameraUBuffer::new(device);
-        let bind_group_layout = CameraUBuffer::layout(device);
-        let bind_group = CameraUBuffer::bind_group(device, &bind_group_layout, &u_buffer);
-        let znear = -1000.0;
-        let zfar = 1000.0;
-        
+        let mut camera = Camera::with_projection(Projection::orthographic(), width, height);
+        camera.init(device);
+
+        return camera;
+    }
+
+    // Camera without gpu resources, call init before modify_buffer
+    pub fn with_projection(projection: Projection, width: f32, height: f32) -> Self {
         Self {
-            projection: Camera::create_projection(width, height, znear, zfar),
+            projection,
             eye: Point3::new(0.0, 0.0, -1.0),
             target: Point3::new(0.0, 0.0, 0.0),
             up: Vector3::unit_y(),
             width,
             height,
-            znear,
-            zfar,
-            camera_buffer: Some(camera_buffer),
-            u_buffer: Some(u_buffer),
-            bind_group_layout: Some(bind_group_layout),
-            bind_group: Some(bind_group),
+            camera_buffer: None,
+            u_buffer: None,
+            bind_group_layout: None,
+            bind_group: None,
         }
     }
 
-    pub fn modify_buffer(&mut self, queue: &Queue) -> Result<(), io::Error> {
-        let vp = self.create_vp();
+    pub fn init(&mut self, device: &Device) {
+        let (camera_buffer, u_buffer) = CameraUBuffer::new(device);
+        let bind_group_layout = CameraUBuffer::layout(device);
+        let bind_group = CameraUBuffer::bind_group(device, &bind_group_layout, &u_buffer);
+
+        self.camera_buffer = Some(camera_buffer);
+        self.u_buffer = Some(u_buffer);
+        self.bind_group_layout = Some(bind_group_layout);
+        self.bind_group = Some(bind_group);
+    }
+
+    pub fn modify_buffer(&mut self, queue: &Queue) -> Result<(), Error> {
+        let vp = self.view_projection();
 
         match &mut self.camera_buffer {
             Some(c_buffer) => {
@@ -74,34 +136,78 @@ impl Camera {
                         Ok(())
                     },
                     None => {
-                        Err(io::Error::new(io::ErrorKind::NotFound, "TODO"))
+                        Err(Error::NotInitialized("camera uniform buffer"))
                     }
                 }
             },
             None => {
-                Err(io::Error::new(io::ErrorKind::NotFound, "TODO"))
+                Err(Error::NotInitialized("camera buffer"))
             }
         }
     }
 
     pub fn handle_resize(&mut self, w: f32, h: f32) {
-        self.projection = Camera::create_projection(w, h, self.znear, self.zfar);
+        self.width = w;
+        self.height = h;
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
     }
 
-    fn create_vp(&self) -> Matrix4<f32> {
-        let view = Matrix4::look_at_rh(self.eye, self.target, self.up);
+    pub fn size(&self) -> (f32, f32) {
+        return (self.width, self.height);
+    }
+
+    pub fn aspect(&self) -> f32 {
+        if self.height > 0.0 {
+            return self.width / self.height;
+        }
+
+        return 1.0;
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        return self.projection.matrix(self.width, self.height);
+    }
 
-        return OPENGL_TO_WGPU_MATRIX * self.projection * view;
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        return Matrix4::look_at_rh(self.eye, self.target, self.up);
     }
 
-    fn create_projection(w: f32, h: f32, near: f32, far: f32) -> Matrix4<f32> {
-        cgmath::ortho(
-            w / 2.0,
-            -w / 2.0,
-            -h / 2.0,
-            h / 2.0,
-            near,
-            far
-        )
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        return OPENGL_TO_WGPU_MATRIX * self.projection_matrix() * self.view_matrix();
     }
-}
\ No newline at end of file
+}
+
+impl Versioned for Camera {
+    fn migrations() -> Migrations {
+        Migrations::new("Camera")
+            .add(Self::projection_from_planes)
+    }
+}
+
+impl Camera {
+    // Cameras from before `Projection` kept an orthographic matrix and its planes,
+    // the matrix is rebuilt from the size anyway
+    fn projection_from_planes(value: &mut Value) -> Result<(), Error> {
+        let fields = match value.as_object_mut() {
+            Some(val) => val,
+            None => return Err(Error::InvalidData(String::from("camera isn't an object")))
+        };
+
+        let znear = fields.remove("znear").and_then(|v| v.as_f64());
+        let zfar = fields.remove("zfar").and_then(|v| v.as_f64());
+
+        let mut projection = Projection::orthographic();
+        if let Projection::Orthographic { znear: near, zfar: far, .. } = &mut projection {
+            *near = znear.map_or(*near, |v| v as f32);
+            *far = zfar.map_or(*far, |v| v as f32);
+        }
+
+        let projection = serde_json::to_value(projection).map_err(|e| Error::Serialize(e.to_string()))?;
+        fields.insert(String::from("projection"), projection);
+
+        Ok(())
+    }
+}
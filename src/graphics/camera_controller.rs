@@ -0,0 +1,176 @@
+use serde::{Serialize, Deserialize};
+use cgmath::{Vector3, InnerSpace, Quaternion, Rotation3, Rotation, Deg, Rad};
+use winit::event::{VirtualKeyCode, ModifiersState};
+
+use super::camera::{Camera, Projection};
+use crate::system::input::{Input, Key};
+
+pub const FORWARD: &str = "CameraForward";
+pub const BACK: &str = "CameraBack";
+pub const LEFT: &str = "CameraLeft";
+pub const RIGHT: &str = "CameraRight";
+pub const UP: &str = "CameraUp";
+pub const DOWN: &str = "CameraDown";
+
+const MIN_DISTANCE: f32 = 0.01;
+const MIN_SCALE: f32 = 0.01;
+const MIN_PITCH: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ControlMode {
+    // Left/right and up/down circle the target, forward/back change the distance
+    Orbit,
+    // Moves eye and target together along the view direction
+    Fly,
+    // Pans in the view plane, forward/back zoom
+    PanZoom,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraController {
+    pub mode: ControlMode,
+    // World units per second
+    pub speed: f32,
+    // Degrees per second
+    pub turn_speed: f32,
+    // Fraction of the distance or scale per second
+    pub zoom_speed: f32,
+}
+
+impl CameraController {
+    pub fn new(mode: ControlMode) -> Self {
+        Self {
+            mode,
+            speed: 10.0,
+            turn_speed: 90.0,
+            zoom_speed: 1.0,
+        }
+    }
+
+    pub fn default_hotkeys() -> Vec<(&'static str, Key)> {
+        let none = ModifiersState::empty();
+
+        return vec![
+            (FORWARD, Key::new(VirtualKeyCode::W, none)),
+            (BACK, Key::new(VirtualKeyCode::S, none)),
+            (LEFT, Key::new(VirtualKeyCode::A, none)),
+            (RIGHT, Key::new(VirtualKeyCode::D, none)),
+            (UP, Key::new(VirtualKeyCode::E, none)),
+            (DOWN, Key::new(VirtualKeyCode::Q, none)),
+        ];
+    }
+
+    // Existing bindings are left untouched
+    pub fn bind(input: &mut Input) {
+        for (name, key) in CameraController::default_hotkeys() {
+            input.keyboard.add_hotkey(String::from(name), key);
+        }
+    }
+
+    pub fn update(&self, camera: &mut Camera, input: &Input, dt: f32) {
+        let forward = axis(input, FORWARD, BACK);
+        let right = axis(input, RIGHT, LEFT);
+        let up = axis(input, UP, DOWN);
+
+        if forward == 0.0 && right == 0.0 && up == 0.0 {
+            return;
+        }
+
+        match self.mode {
+            ControlMode::Orbit => self.orbit(camera, forward, right, up, dt),
+            ControlMode::Fly => self.fly(camera, forward, right, up, dt),
+            ControlMode::PanZoom => self.pan_zoom(camera, forward, right, up, dt),
+        }
+    }
+
+    fn orbit(&self, camera: &mut Camera, forward: f32, right: f32, up: f32, dt: f32) {
+        let (_, side, _) = match basis(camera) {
+            Some(val) => val,
+            None => return
+        };
+
+        let offset = camera.eye - camera.target;
+        let world_up = camera.up.normalize();
+        let turn = self.turn_speed * dt;
+
+        let yaw = Quaternion::from_axis_angle(world_up, Deg(-right * turn));
+        let mut offset = yaw.rotate_vector(offset);
+
+        // Keep clear of the poles so the view basis stays defined
+        let pitch = Rad::from(Deg(up * turn)).0;
+        let angle = offset.angle(world_up).0;
+        let min = Rad::from(Deg(MIN_PITCH)).0;
+        let change = (angle - pitch).clamp(min, std::f32::consts::PI - min) - angle;
+        let side = yaw.rotate_vector(side);
+        offset = Quaternion::from_axis_angle(side, Rad(change)).rotate_vector(offset);
+
+        let distance = (offset.magnitude() * (1.0 - forward * self.zoom_speed * dt)).max(MIN_DISTANCE);
+        camera.eye = camera.target + offset.normalize() * distance;
+    }
+
+    fn fly(&self, camera: &mut Camera, forward: f32, right: f32, up: f32, dt: f32) {
+        let (dir, side, _) = match basis(camera) {
+            Some(val) => val,
+            None => return
+        };
+
+        let delta = (dir * forward + side * right + camera.up.normalize() * up) * self.speed * dt;
+        camera.eye += delta;
+        camera.target += delta;
+    }
+
+    fn pan_zoom(&self, camera: &mut Camera, forward: f32, right: f32, up: f32, dt: f32) {
+        let (dir, side, view_up) = match basis(camera) {
+            Some(val) => val,
+            None => return
+        };
+
+        let delta = (side * right + view_up * up) * self.speed * dt;
+        camera.eye += delta;
+        camera.target += delta;
+
+        let factor = 1.0 - forward * self.zoom_speed * dt;
+        match &mut camera.projection {
+            Projection::Orthographic { scale, .. } => {
+                *scale = (*scale * factor).max(MIN_SCALE);
+            },
+            Projection::Perspective { .. } => {
+                let distance = ((camera.eye - camera.target).magnitude() * factor).max(MIN_DISTANCE);
+                camera.eye = camera.target - dir * distance;
+            }
+        }
+    }
+}
+
+fn axis(input: &Input, positive: &str, negative: &str) -> f32 {
+    let mut value = 0.0;
+
+    if input.key_state(String::from(positive)) {
+        value += 1.0;
+    }
+
+    if input.key_state(String::from(negative)) {
+        value -= 1.0;
+    }
+
+    return value;
+}
+
+// View direction, right and up vectors, None when eye sits on the target or looks along up
+fn basis(camera: &Camera) -> Option<(Vector3<f32>, Vector3<f32>, Vector3<f32>)> {
+    let dir = camera.target - camera.eye;
+    if dir.magnitude2() <= f32::EPSILON {
+        return None;
+    }
+
+    let dir = dir.normalize();
+    let side = dir.cross(camera.up);
+    if side.magnitude2() <= f32::EPSILON {
+        return None;
+    }
+
+    let side = side.normalize();
+    let view_up = side.cross(dir);
+
+    return Some((dir, side, view_up));
+}
@@ -0,0 +1,173 @@
+use serde::{Serialize, Deserialize};
+
+// Depth with an 8 bit stencil, supported by every backend
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+// Value the depth attachment is cleared to, `Compare::Less` keeps what's nearer
+pub const DEPTH_CLEAR: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compare {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl From<Compare> for wgpu::CompareFunction {
+    fn from(c: Compare) -> Self {
+        match c {
+            Compare::Never => wgpu::CompareFunction::Never,
+            Compare::Less => wgpu::CompareFunction::Less,
+            Compare::Equal => wgpu::CompareFunction::Equal,
+            Compare::LessEqual => wgpu::CompareFunction::LessEqual,
+            Compare::Greater => wgpu::CompareFunction::Greater,
+            Compare::NotEqual => wgpu::CompareFunction::NotEqual,
+            Compare::GreaterEqual => wgpu::CompareFunction::GreaterEqual,
+            Compare::Always => wgpu::CompareFunction::Always,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Invert,
+    IncrementClamp,
+    DecrementClamp,
+    IncrementWrap,
+    DecrementWrap,
+}
+
+impl From<StencilOp> for wgpu::StencilOperation {
+    fn from(op: StencilOp) -> Self {
+        match op {
+            StencilOp::Keep => wgpu::StencilOperation::Keep,
+            StencilOp::Zero => wgpu::StencilOperation::Zero,
+            StencilOp::Replace => wgpu::StencilOperation::Replace,
+            StencilOp::Invert => wgpu::StencilOperation::Invert,
+            StencilOp::IncrementClamp => wgpu::StencilOperation::IncrementClamp,
+            StencilOp::DecrementClamp => wgpu::StencilOperation::DecrementClamp,
+            StencilOp::IncrementWrap => wgpu::StencilOperation::IncrementWrap,
+            StencilOp::DecrementWrap => wgpu::StencilOperation::DecrementWrap,
+        }
+    }
+}
+
+// Used for front and back faces alike. The reference value is set on the
+// render pass with `set_stencil_reference`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StencilState {
+    pub compare: Compare,
+    pub fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub pass: StencilOp,
+    pub read_mask: u32,
+    pub write_mask: u32,
+}
+
+impl StencilState {
+    // Marks every drawn pixel with the reference, e.g. the object to outline
+    pub fn write() -> Self {
+        Self {
+            compare: Compare::Always,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Replace,
+            read_mask: 0xff,
+            write_mask: 0xff,
+        }
+    }
+
+    // Draws only where the stored value passes `compare` against the reference.
+    // `NotEqual` leaves an outline around marked pixels, `Equal` masks to them.
+    pub fn test(compare: Compare) -> Self {
+        Self {
+            compare,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+            read_mask: 0xff,
+            write_mask: 0,
+        }
+    }
+
+    fn face(&self) -> wgpu::StencilFaceState {
+        wgpu::StencilFaceState {
+            compare: self.compare.into(),
+            fail_op: self.fail.into(),
+            depth_fail_op: self.depth_fail.into(),
+            pass_op: self.pass.into(),
+        }
+    }
+}
+
+// Depth and stencil state of a renderable's pipeline. Every pipeline is drawn in a pass
+// with the depth attachment, renderables without a state use `DepthState::disabled()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthState {
+    pub test: bool,
+    pub write: bool,
+    pub compare: Compare,
+    pub stencil: Option<StencilState>,
+}
+
+impl DepthState {
+    pub fn opaque() -> Self {
+        Self {
+            test: true,
+            write: true,
+            compare: Compare::Less,
+            stencil: None,
+        }
+    }
+
+    // Neither tested nor written, draws over whatever came before it
+    pub fn disabled() -> Self {
+        Self {
+            test: false,
+            write: false,
+            compare: Compare::Always,
+            stencil: None,
+        }
+    }
+
+    // Hidden behind opaque geometry without hiding anything itself, e.g. transparency
+    pub fn read_only() -> Self {
+        Self {
+            write: false,
+            ..DepthState::opaque()
+        }
+    }
+
+    pub fn with_stencil(mut self, stencil: StencilState) -> Self {
+        self.stencil = Some(stencil);
+        self
+    }
+
+    pub fn descriptor(&self) -> wgpu::DepthStencilState {
+        let stencil = match &self.stencil {
+            Some(s) => wgpu::StencilState {
+                front: s.face(),
+                back: s.face(),
+                read_mask: s.read_mask,
+                write_mask: s.write_mask,
+            },
+            None => wgpu::StencilState::default()
+        };
+
+        wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: self.write,
+            depth_compare: if self.test { self.compare.into() } else { wgpu::CompareFunction::Always },
+            stencil,
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+}
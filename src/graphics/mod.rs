@@ -1,5 +1,8 @@
 pub mod buffer;
 pub mod camera;
+pub mod camera_controller;
+pub mod depth;
+pub mod render_graph;
 pub mod renderable;
 pub mod shader;
 pub mod uniform_buffer;
\ No newline at end of file
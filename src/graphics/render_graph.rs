@@ -0,0 +1,500 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::marker::PhantomData;
+
+use crate::error::Error;
+
+// A texture or buffer of one `RenderGraph`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Resource(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferDesc {
+    pub size: u64,
+    pub usage: wgpu::BufferUsages,
+}
+
+// What a plan slot is allocated as, transient resources with the same desc share slots
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Attachment {
+    Texture(TextureDesc),
+    Buffer(BufferDesc),
+}
+
+enum ResourceKind<'a> {
+    Transient(Attachment),
+    View(&'a wgpu::TextureView),
+    Buffer(&'a wgpu::Buffer),
+}
+
+struct ResourceEntry<'a> {
+    name: String,
+    kind: ResourceKind<'a>,
+}
+
+// Depth attachment of a pass, a stencil of None leaves the stencil read-only
+type DepthAttachment = (Resource, wgpu::LoadOp<f32>, Option<wgpu::LoadOp<u32>>);
+
+// The resources a pass uses. Color and depth attachments are writes, and are cleared or loaded by `load`.
+pub struct PassDesc {
+    name: String,
+    reads: Vec<Resource>,
+    writes: Vec<Resource>,
+    colors: Vec<(Resource, wgpu::LoadOp<wgpu::Color>)>,
+    depth: Option<DepthAttachment>,
+}
+
+impl PassDesc {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            colors: Vec::new(),
+            depth: None,
+        }
+    }
+
+    pub fn read(mut self, r: Resource) -> Self {
+        self.reads.push(r);
+        self
+    }
+
+    pub fn write(mut self, r: Resource) -> Self {
+        self.writes.push(r);
+        self
+    }
+
+    pub fn color(mut self, r: Resource, load: wgpu::LoadOp<wgpu::Color>) -> Self {
+        self.writes.push(r);
+        self.colors.push((r, load));
+        self
+    }
+
+    pub fn depth(mut self, r: Resource, load: wgpu::LoadOp<f32>, stencil: Option<wgpu::LoadOp<u32>>) -> Self {
+        self.writes.push(r);
+        self.depth = Some((r, load, stencil));
+        self
+    }
+
+    fn uses(&self) -> impl Iterator<Item = Resource> + '_ {
+        self.reads.iter().chain(self.writes.iter()).copied()
+    }
+}
+
+// What a pass gets while it runs. Borrows of the graph's owner, `'a`, outlive it.
+pub struct PassContext<'p, 'a> {
+    pub device: &'p wgpu::Device,
+    pub queue: &'p wgpu::Queue,
+    views: &'p [Option<&'p wgpu::TextureView>],
+    buffers: &'p [Option<&'p wgpu::Buffer>],
+    _env: PhantomData<&'p &'a ()>,
+}
+
+impl<'p, 'a> PassContext<'p, 'a> {
+    pub fn view(&self, r: Resource) -> Option<&'p wgpu::TextureView> {
+        self.views.get(r.0).copied().flatten()
+    }
+
+    pub fn buffer(&self, r: Resource) -> Option<&'p wgpu::Buffer> {
+        self.buffers.get(r.0).copied().flatten()
+    }
+}
+
+type RenderFn<'a> = Box<dyn for<'p> FnOnce(&mut wgpu::RenderPass<'p>, &PassContext<'p, 'a>) + 'a>;
+type EncoderFn<'a> = Box<dyn for<'p> FnOnce(&mut wgpu::CommandEncoder, &PassContext<'p, 'a>) + 'a>;
+
+enum Execute<'a> {
+    Render(RenderFn<'a>),
+    Encoder(EncoderFn<'a>),
+}
+
+struct Pass<'a> {
+    desc: PassDesc,
+    execute: Execute<'a>,
+}
+
+// Order of the passes and the slot every used transient resource lives in
+#[derive(Debug)]
+pub struct Plan {
+    pub order: Vec<usize>,
+    pub slots: Vec<Attachment>,
+    pub assigned: HashMap<Resource, usize>, // <resource, index into `slots`>
+}
+
+// The passes of one frame. Built every frame, then `execute` orders the passes,
+// allocates the transient resources and records everything into a single encoder.
+//
+// Every write makes a new version of its resource, writers of a resource run in the order they were added.
+// A read sees the version of the latest write added before it, and runs before the write after that.
+// Passes may also be added ahead of the writes they need: such a read of an imported resource
+// sees what it held before the frame, a read of a transient sees its first write.
+pub struct RenderGraph<'a> {
+    resources: Vec<ResourceEntry<'a>>,
+    passes: Vec<Pass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self {
+            resources: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    pub fn create_texture(&mut self, name: &str, desc: TextureDesc) -> Resource {
+        self.add_resource(name, ResourceKind::Transient(Attachment::Texture(desc)))
+    }
+
+    pub fn create_buffer(&mut self, name: &str, desc: BufferDesc) -> Resource {
+        self.add_resource(name, ResourceKind::Transient(Attachment::Buffer(desc)))
+    }
+
+    // A view the graph doesn't own, e.g. the surface texture of the frame
+    pub fn import_view(&mut self, name: &str, view: &'a wgpu::TextureView) -> Resource {
+        self.add_resource(name, ResourceKind::View(view))
+    }
+
+    pub fn import_buffer(&mut self, name: &str, buffer: &'a wgpu::Buffer) -> Resource {
+        self.add_resource(name, ResourceKind::Buffer(buffer))
+    }
+
+    pub fn add_render_pass<F>(&mut self, desc: PassDesc, f: F)
+    where F: for<'p> FnOnce(&mut wgpu::RenderPass<'p>, &PassContext<'p, 'a>) + 'a {
+        self.passes.push(Pass { desc, execute: Execute::Render(Box::new(f)) });
+    }
+
+    // A pass recording into the encoder itself, for copies and compute
+    pub fn add_pass<F>(&mut self, desc: PassDesc, f: F)
+    where F: for<'p> FnOnce(&mut wgpu::CommandEncoder, &PassContext<'p, 'a>) + 'a {
+        self.passes.push(Pass { desc, execute: Execute::Encoder(Box::new(f)) });
+    }
+
+    pub fn pass_name(&self, index: usize) -> Option<&str> {
+        self.passes.get(index).map(|p| p.desc.name.as_str())
+    }
+
+    fn add_resource(&mut self, name: &str, kind: ResourceKind<'a>) -> Resource {
+        self.resources.push(ResourceEntry { name: String::from(name), kind });
+        Resource(self.resources.len() - 1)
+    }
+
+    // Orders the passes and assigns slots, without touching the GPU
+    pub fn compile(&self) -> Result<Plan, Error> {
+        let order = self.order()?;
+
+        // Position in `order` of the first and last pass using each transient
+        let mut lifetimes: HashMap<Resource, (usize, usize)> = HashMap::new();
+        for (step, index) in order.iter().enumerate() {
+            for r in self.passes[*index].desc.uses() {
+                if let ResourceKind::Transient(_) = self.resources[r.0].kind {
+                    lifetimes.entry(r).and_modify(|l| l.1 = step).or_insert((step, step));
+                }
+            }
+        }
+
+        // Slots are handed back once their resource's last pass ran
+        let mut slots: Vec<Attachment> = Vec::new();
+        let mut free: HashMap<Attachment, Vec<usize>> = HashMap::new();
+        let mut assigned = HashMap::new();
+        for step in 0..order.len() {
+            let mut starting: Vec<Resource> = lifetimes.iter()
+                .filter(|(_, l)| l.0 == step)
+                .map(|(r, _)| *r)
+                .collect();
+            starting.sort_by_key(|r| r.0);
+
+            for r in starting {
+                let attachment = match self.resources[r.0].kind {
+                    ResourceKind::Transient(val) => val,
+                    _ => continue
+                };
+
+                let slot = match free.get_mut(&attachment).and_then(|list| list.pop()) {
+                    Some(val) => val,
+                    None => {
+                        slots.push(attachment);
+                        slots.len() - 1
+                    }
+                };
+                assigned.insert(r, slot);
+            }
+
+            let mut ending: Vec<usize> = lifetimes.iter()
+                .filter(|(_, l)| l.1 == step)
+                .map(|(r, _)| assigned[r])
+                .collect();
+            ending.sort();
+
+            for slot in ending {
+                free.entry(slots[slot]).or_default().push(slot);
+            }
+        }
+
+        Ok(Plan { order, slots, assigned })
+    }
+
+    fn order(&self) -> Result<Vec<usize>, Error> {
+        let mut writers: HashMap<Resource, Vec<usize>> = HashMap::new();
+        for (index, p) in self.passes.iter().enumerate() {
+            for r in p.desc.writes.iter() {
+                if r.0 >= self.resources.len() {
+                    return Err(Error::OutOfBounds { index: r.0, len: self.resources.len() })
+                }
+
+                let list = writers.entry(*r).or_default();
+                if list.last() != Some(&index) {
+                    list.push(index);
+                }
+            }
+        }
+
+        let mut edges: Vec<HashSet<usize>> = vec![HashSet::new(); self.passes.len()];
+        for list in writers.values() {
+            for pair in list.windows(2) {
+                edges[pair[0]].insert(pair[1]);
+            }
+        }
+
+        for (index, p) in self.passes.iter().enumerate() {
+            for r in p.desc.reads.iter() {
+                let entry = match self.resources.get(r.0) {
+                    Some(val) => val,
+                    None => return Err(Error::OutOfBounds { index: r.0, len: self.resources.len() })
+                };
+
+                let list = writers.get(r).map(|l| l.as_slice()).unwrap_or(&[]);
+
+                // Position in `list` of the write whose version is read, None for the data from before the frame
+                let version = match list.iter().rposition(|w| *w < index) {
+                    Some(val) => Some(val),
+                    None => match entry.kind {
+                        ResourceKind::Transient(_) if list.is_empty() => {
+                            return Err(Error::ResourceNotWritten {
+                                pass: p.desc.name.clone(),
+                                resource: entry.name.clone(),
+                            })
+                        },
+                        ResourceKind::Transient(_) => Some(0),
+                        _ => None
+                    }
+                };
+
+                let next = match version {
+                    Some(v) => {
+                        if list[v] != index {
+                            edges[list[v]].insert(index);
+                        }
+                        list.get(v + 1)
+                    },
+                    None => list.first()
+                };
+
+                // The next write replaces the version, it waits for this read
+                if let Some(next) = next.filter(|w| **w != index) {
+                    edges[index].insert(*next);
+                }
+            }
+        }
+
+        // Kahn's algorithm, ready passes run in the order they were added
+        let mut incoming = vec![0; self.passes.len()];
+        for targets in edges.iter() {
+            for t in targets.iter() {
+                incoming[*t] += 1;
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = (0..self.passes.len()).filter(|i| incoming[*i] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(index) = ready.pop_first() {
+            order.push(index);
+            for t in edges[index].iter() {
+                incoming[*t] -= 1;
+                if incoming[*t] == 0 {
+                    ready.insert(*t);
+                }
+            }
+        }
+
+        if order.len() != self.passes.len() {
+            let index = (0..self.passes.len()).find(|i| incoming[*i] > 0).unwrap_or(0);
+            return Err(Error::PassCycle(self.passes[index].desc.name.clone()))
+        }
+
+        Ok(order)
+    }
+
+    pub fn execute(self, device: &wgpu::Device, queue: &wgpu::Queue, pool: &mut AttachmentPool) -> Result<(), Error> {
+        let plan = self.compile()?;
+        pool.prepare(device, &plan.slots);
+
+        let mut views = Vec::new();
+        let mut buffers = Vec::new();
+        for (index, entry) in self.resources.iter().enumerate() {
+            let (view, buffer) = match entry.kind {
+                ResourceKind::View(val) => (Some(val), None),
+                ResourceKind::Buffer(val) => (None, Some(val)),
+                ResourceKind::Transient(_) => match plan.assigned.get(&Resource(index)) {
+                    Some(slot) => (pool.view(plan.slots[*slot], *slot), pool.buffer(plan.slots[*slot], *slot)),
+                    None => (None, None)
+                }
+            };
+            views.push(view);
+            buffers.push(buffer);
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("render_graph"),
+        });
+
+        let mut passes: Vec<Option<Pass>> = self.passes.into_iter().map(Some).collect();
+        for index in plan.order.iter() {
+            let pass = match passes[*index].take() {
+                Some(val) => val,
+                None => continue
+            };
+
+            let ctx = PassContext {
+                device,
+                queue,
+                views: &views,
+                buffers: &buffers,
+                _env: PhantomData,
+            };
+
+            match pass.execute {
+                Execute::Render(f) => {
+                    let mut colors = Vec::new();
+                    for (r, load) in pass.desc.colors.iter() {
+                        let view = match views[r.0] {
+                            Some(val) => val,
+                            None => return Err(Error::NotInitialized("render graph color attachment"))
+                        };
+
+                        colors.push(Some(wgpu::RenderPassColorAttachment {
+                            view,
+                            resolve_target: None,
+                            ops: wgpu::Operations { load: *load, store: true },
+                        }));
+                    }
+
+                    let depth = match pass.desc.depth {
+                        Some((r, load, stencil)) => match views[r.0] {
+                            Some(view) => Some(wgpu::RenderPassDepthStencilAttachment {
+                                view,
+                                depth_ops: Some(wgpu::Operations { load, store: true }),
+                                stencil_ops: stencil.map(|load| wgpu::Operations { load, store: true }),
+                            }),
+                            None => return Err(Error::NotInitialized("render graph depth attachment"))
+                        },
+                        None => None
+                    };
+
+                    let mut rp = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some(&pass.desc.name),
+                        color_attachments: &colors,
+                        depth_stencil_attachment: depth,
+                    });
+                    f(&mut rp, &ctx);
+                },
+                Execute::Encoder(f) => f(&mut encoder, &ctx),
+            }
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+        Ok(())
+    }
+}
+
+// Transient textures and buffers kept between frames, so a graph only
+// creates them when a desc it didn't use last frame shows up
+pub struct AttachmentPool {
+    textures: HashMap<TextureDesc, Vec<(wgpu::Texture, wgpu::TextureView)>>,
+    buffers: HashMap<BufferDesc, Vec<wgpu::Buffer>>,
+    slots: Vec<usize>, // <plan slot, index into the list of its desc>
+}
+
+impl AttachmentPool {
+    pub fn new() -> Self {
+        Self {
+            textures: HashMap::new(),
+            buffers: HashMap::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    // Makes sure every slot has an allocation, drops the ones no slot uses anymore
+    fn prepare(&mut self, device: &wgpu::Device, slots: &[Attachment]) {
+        let mut counts: HashMap<Attachment, usize> = HashMap::new();
+        self.slots.clear();
+        for a in slots.iter() {
+            let count = counts.entry(*a).or_insert(0);
+            self.slots.push(*count);
+            *count += 1;
+        }
+
+        self.textures.retain(|desc, list| {
+            list.truncate(counts.get(&Attachment::Texture(*desc)).copied().unwrap_or(0));
+            !list.is_empty()
+        });
+        self.buffers.retain(|desc, list| {
+            list.truncate(counts.get(&Attachment::Buffer(*desc)).copied().unwrap_or(0));
+            !list.is_empty()
+        });
+
+        for (a, count) in counts {
+            match a {
+                Attachment::Texture(desc) => {
+                    let list = self.textures.entry(desc).or_default();
+                    while list.len() < count {
+                        let texture = device.create_texture(&wgpu::TextureDescriptor {
+                            label: Some("render_graph_attachment"),
+                            size: wgpu::Extent3d { width: desc.width, height: desc.height, depth_or_array_layers: 1 },
+                            mip_level_count: 1,
+                            sample_count: 1,
+                            dimension: wgpu::TextureDimension::D2,
+                            format: desc.format,
+                            usage: desc.usage,
+                            view_formats: &[],
+                        });
+                        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                        list.push((texture, view));
+                    }
+                },
+                Attachment::Buffer(desc) => {
+                    let list = self.buffers.entry(desc).or_default();
+                    while list.len() < count {
+                        list.push(device.create_buffer(&wgpu::BufferDescriptor {
+                            label: Some("render_graph_buffer"),
+                            size: desc.size,
+                            usage: desc.usage,
+                            mapped_at_creation: false,
+                        }));
+                    }
+                },
+            }
+        }
+    }
+
+    fn view(&self, a: Attachment, slot: usize) -> Option<&wgpu::TextureView> {
+        match a {
+            Attachment::Texture(desc) => self.textures.get(&desc)?.get(self.slots[slot]).map(|t| &t.1),
+            Attachment::Buffer(_) => None
+        }
+    }
+
+    fn buffer(&self, a: Attachment, slot: usize) -> Option<&wgpu::Buffer> {
+        match a {
+            Attachment::Buffer(desc) => self.buffers.get(&desc)?.get(self.slots[slot]),
+            Attachment::Texture(_) => None
+        }
+    }
+}
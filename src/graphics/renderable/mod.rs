@@ -18,5 +18,5 @@ pub trait Deserialized {
         shader: &crate::graphics::shader::Shader,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<(), std::io::Error>;
+    ) -> Result<(), crate::error::Error>;
 }
\ No newline at end of file
@@ -1,9 +1,10 @@
-use std::io;
 use wgpu::util::DeviceExt;
 use serde::{Serialize, Deserialize};
 
 use super::{Vertex, Deserialized};
 use crate::graphics::{buffer::VertexBuffer, shader::Shader};
+use crate::graphics::depth::DepthState;
+use crate::error::Error;
 
 #[derive(Serialize, Deserialize)]
 pub struct Index {
@@ -25,7 +26,7 @@ impl Index {
         index_list: Vec<u16>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<Self, io::Error> {
+    ) -> Result<Self, Error> {
         
         let r_vertex = Vertex::new(hash, device, config, shader, buffer_list, buffer_layouts, bind_layouts)?;
         let index_buffer = Some(Index::create_index_buffer(device, &index_list));
@@ -48,7 +49,7 @@ impl Index {
         index_list: Vec<u16>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<(), io::Error> {
+    ) -> Result<(), Error> {
 
         self.r_vertex.modify(device, config, shader, buffer_list, buffer_layouts, bind_layouts)?;
         self.index_buffer = Some(Index::create_index_buffer(device, &index_list));
@@ -58,6 +59,19 @@ impl Index {
         Ok(())
     }
 
+    pub fn set_depth(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &Shader,
+        depth: Option<DepthState>,
+        buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
+        bind_layouts: &Vec<&wgpu::BindGroupLayout>
+    ) -> Result<(), Error> {
+
+        self.r_vertex.set_depth(device, config, shader, depth, buffer_layouts, bind_layouts)
+    }
+
     fn create_index_buffer(
         device: &wgpu::Device,
         index_list: &Vec<u16>
@@ -79,7 +93,7 @@ impl Deserialized for Index {
         shader: &Shader,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<(), std::io::Error> {
+    ) -> Result<(), Error> {
 
         self.r_vertex.init(device, config, shader, buffer_layouts, bind_layouts)?;
         self.index_buffer = Some(Index::create_index_buffer(device, &self.index_list));
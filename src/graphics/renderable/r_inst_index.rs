@@ -1,11 +1,11 @@
-use std::io;
-
 use serde::{Serialize, Deserialize};
 use wgpu::{Device, SurfaceConfiguration};
 
 use super::{Index, Instance, Deserialized};
 use crate::graphics::shader::Shader;
 use crate::graphics::buffer::{VertexBuffer, InstanceBuffer, Layout};
+use crate::graphics::depth::DepthState;
+use crate::error::Error;
 
 #[derive(Serialize, Deserialize)]
 pub struct InstanceIndex {
@@ -24,7 +24,7 @@ impl InstanceIndex {
         inst_list: Vec<InstanceBuffer>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<Self, io::Error> {
+    ) -> Result<Self, Error> {
 
         buffer_layouts.insert(0, InstanceBuffer::layout());
 
@@ -47,7 +47,7 @@ impl InstanceIndex {
         inst_list: Vec<InstanceBuffer>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<(), io::Error> {
+    ) -> Result<(), Error> {
 
         buffer_layouts.insert(0, InstanceBuffer::layout());
 
@@ -56,6 +56,21 @@ impl InstanceIndex {
 
         Ok(())
     }
+
+    pub fn set_depth(
+        &mut self,
+        device: &Device,
+        config: &SurfaceConfiguration,
+        shader: &Shader,
+        depth: Option<DepthState>,
+        buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
+        bind_layouts: &Vec<&wgpu::BindGroupLayout>
+    ) -> Result<(), Error> {
+
+        buffer_layouts.insert(0, InstanceBuffer::layout());
+
+        self.r_index.set_depth(device, config, shader, depth, buffer_layouts, bind_layouts)
+    }
 }
 
 impl Deserialized for InstanceIndex {
@@ -66,7 +81,7 @@ impl Deserialized for InstanceIndex {
         shader: &Shader,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<(), std::io::Error> {
+    ) -> Result<(), Error> {
         
         self.r_index.init(device, config, shader, buffer_layouts, bind_layouts)?;
         self.r_instance.init(device, config, shader, buffer_layouts, bind_layouts)?;
@@ -1,11 +1,11 @@
-use std::io;
-
 use serde::{Serialize, Deserialize};
 use wgpu::{Device, SurfaceConfiguration};
 
 use super::{Deserialized, Vertex, Instance};
 use crate::graphics::shader::Shader;
 use crate::graphics::buffer::{VertexBuffer, InstanceBuffer, Layout};
+use crate::graphics::depth::DepthState;
+use crate::error::Error;
 
 #[derive(Serialize, Deserialize)]
 pub struct InstanceVertex {
@@ -23,7 +23,7 @@ impl InstanceVertex {
         inst_list: Vec<InstanceBuffer>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<Self, io::Error> {
+    ) -> Result<Self, Error> {
 
         buffer_layouts.insert(0, InstanceBuffer::layout());
 
@@ -45,7 +45,7 @@ impl InstanceVertex {
         inst_list: Vec<InstanceBuffer>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<(), io::Error> {
+    ) -> Result<(), Error> {
 
         buffer_layouts.insert(0, InstanceBuffer::layout());
 
@@ -54,6 +54,21 @@ impl InstanceVertex {
 
         Ok(())
     }
+
+    pub fn set_depth(
+        &mut self,
+        device: &Device,
+        config: &SurfaceConfiguration,
+        shader: &Shader,
+        depth: Option<DepthState>,
+        buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
+        bind_layouts: &Vec<&wgpu::BindGroupLayout>
+    ) -> Result<(), Error> {
+
+        buffer_layouts.insert(0, InstanceBuffer::layout());
+
+        self.r_vertex.set_depth(device, config, shader, depth, buffer_layouts, bind_layouts)
+    }
 }
 
 impl Deserialized for InstanceVertex {
@@ -64,7 +79,7 @@ impl Deserialized for InstanceVertex {
         shader: &Shader,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<(), std::io::Error> {
+    ) -> Result<(), Error> {
 
         self.r_vertex.init(device, config, shader, buffer_layouts, bind_layouts)?;
         self.r_instance.init(device, config, shader, buffer_layouts, bind_layouts)?;
@@ -1,12 +1,13 @@
-use std::io;
 use serde::{Serialize, Deserialize};
 use wgpu::{Device, util::DeviceExt, Queue, BufferAddress};
 
 use super::Deserialized;
 use crate::graphics::buffer::InstanceBuffer;
+use crate::error::Error;
 
 #[derive(Serialize, Deserialize)]
 pub struct Instance {
+    #[serde(with = "crate::util::serialize::pod_list")]
     pub inst_list: Vec<InstanceBuffer>,
 
     #[serde(skip)]
@@ -17,7 +18,7 @@ impl Instance {
     pub fn new(
         device: &Device,
         inst_list: Vec<InstanceBuffer>
-    ) -> Result<Self, io::Error> {
+    ) -> Result<Self, Error> {
 
         let inst_buffer = Some(Instance::create_inst_buffer(device, &inst_list));
 
@@ -31,7 +32,7 @@ impl Instance {
         &mut self,
         device: &Device,
         inst_list: Vec<InstanceBuffer>
-    ) -> Result<(), io::Error> {
+    ) -> Result<(), Error> {
 
         self.inst_buffer = Some(Instance::create_inst_buffer(device, &inst_list));
         self.inst_list = inst_list;
@@ -53,11 +54,10 @@ impl Instance {
         queue: &Queue, 
         index: usize, 
         data: InstanceBuffer
-    ) -> Result<(), io::Error> {
+    ) -> Result<(), Error> {
 
         if !self.bounds_check(index) {
-            return Err(io::Error::new(io::ErrorKind::Other, 
-                "ERROR::r_instance::modify_instance()::index out of bounds"))
+            return Err(Error::OutOfBounds { index, len: self.inst_list.len() })
         }
 
         match &self.inst_buffer {
@@ -68,18 +68,16 @@ impl Instance {
                     bytemuck::cast_slice(&[data]));
             },
             None => {
-                return Err(io::Error::new(io::ErrorKind::Other, 
-                    "ERROR::r_instance::modify_instance()::invalid instance buffer"))
+                return Err(Error::NotInitialized("instance buffer"))
             }
         }
 
         Ok(())
     }
 
-    pub fn remove_instance(&mut self, device: &Device, index: usize) -> Result<usize, io::Error> {
+    pub fn remove_instance(&mut self, device: &Device, index: usize) -> Result<usize, Error> {
         if !self.bounds_check(index) {
-            return Err(io::Error::new(io::ErrorKind::NotFound,
-                "ERROR::r_instance::remove_instance()::index out of bounds"))
+            return Err(Error::OutOfBounds { index, len: self.inst_list.len() })
         }
 
         let last = self.inst_list.len() - 1;
@@ -91,10 +89,9 @@ impl Instance {
         Ok(last)
     }
 
-    pub fn get_instance(&self, index: usize) -> Result<&InstanceBuffer, io::Error> {
+    pub fn get_instance(&self, index: usize) -> Result<&InstanceBuffer, Error> {
         if !self.bounds_check(index) {
-            return Err(io::Error::new(io::ErrorKind::NotFound,
-                "ERROR::r_instance::get_instance()::index out of bounds"));
+            return Err(Error::OutOfBounds { index, len: self.inst_list.len() });
         }
 
         return Ok(&self.inst_list[index]);
@@ -125,7 +122,7 @@ impl Deserialized for Instance {
         _shader: &crate::graphics::shader::Shader,
         _buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         _bind_layouts: &Vec<&wgpu::BindGroupLayout>
-    ) -> Result<(), std::io::Error> {
+    ) -> Result<(), Error> {
         
         self.inst_buffer = Some(Instance::create_inst_buffer(device, &self.inst_list));
 
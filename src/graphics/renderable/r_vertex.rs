@@ -1,10 +1,11 @@
-use std::io;
 use wgpu::BindGroupLayout;
 use wgpu::util::DeviceExt;
 use serde::{Serialize, Deserialize};
 
 use crate::graphics::shader::Shader;
 use crate::graphics::buffer::{VertexBuffer, Layout};
+use crate::graphics::depth::DepthState;
+use crate::error::Error;
 
 #[derive(Serialize, Deserialize)]
 pub struct Vertex {
@@ -12,6 +13,10 @@ pub struct Vertex {
     pub shader_hash: u64,
     pub buffer_list: Vec<VertexBuffer>,
 
+    // None draws with depth disabled, depth testing is opted into with `set_depth`
+    #[serde(default)]
+    pub depth: Option<DepthState>,
+
     #[serde(skip)]
     pub pipeline: Option<wgpu::RenderPipeline>,
 
@@ -28,9 +33,10 @@ impl Vertex {
         buffer_list: Vec<VertexBuffer>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&BindGroupLayout>
-    ) -> Result<Self, io::Error> {
+    ) -> Result<Self, Error> {
 
-        let pipeline = Some(Vertex::create_pipeline(device, config, shader, buffer_layouts, bind_layouts)?);
+        let depth = None;
+        let pipeline = Some(Vertex::create_pipeline(device, config, shader, depth, buffer_layouts, bind_layouts)?);
         let vertex_buffer = Some(Vertex::create_vertex_buffer(device, &buffer_list));
         let shader_hash = shader.hash;
 
@@ -40,6 +46,7 @@ impl Vertex {
             hash,
             shader_hash,
             buffer_list,
+            depth,
         })
     }
 
@@ -51,22 +58,40 @@ impl Vertex {
         buffer_list: Vec<VertexBuffer>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&BindGroupLayout>
-    ) -> Result<(), io::Error> {
+    ) -> Result<(), Error> {
 
-        self.pipeline = Some(Vertex::create_pipeline(device, config, shader, buffer_layouts, bind_layouts)?);
+        self.pipeline = Some(Vertex::create_pipeline(device, config, shader, self.depth, buffer_layouts, bind_layouts)?);
         self.vertex_buffer = Some(Vertex::create_vertex_buffer(device, &buffer_list));
         self.shader_hash = shader.hash;
 
         Ok(())
     }
 
+    // Rebuilds the pipeline with the new depth and stencil state
+    pub fn set_depth(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &Shader,
+        depth: Option<DepthState>,
+        buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
+        bind_layouts: &Vec<&BindGroupLayout>
+    ) -> Result<(), Error> {
+
+        self.pipeline = Some(Vertex::create_pipeline(device, config, shader, depth, buffer_layouts, bind_layouts)?);
+        self.depth = depth;
+
+        Ok(())
+    }
+
     fn create_pipeline(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
         shader: &Shader,
+        depth: Option<DepthState>,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&BindGroupLayout>
-    ) -> Result<wgpu::RenderPipeline, io::Error> {
+    ) -> Result<wgpu::RenderPipeline, Error> {
 
             buffer_layouts.insert(0, VertexBuffer::layout());
 
@@ -79,8 +104,7 @@ impl Vertex {
             let shader_module = match &shader.module {
                 Some(val) => val,
                 None => {
-                    return Err(io::Error::new(io::ErrorKind::InvalidData,
-                        "ERROR::renderable::modify()::shader module invalid"))
+                    return Err(Error::NotInitialized("shader module"))
                 }
             };
 
@@ -110,7 +134,7 @@ impl Vertex {
                     unclipped_depth: false,
                     conservative: false,
                 },
-                depth_stencil: None,
+                depth_stencil: Some(depth.unwrap_or(DepthState::disabled()).descriptor()),
                 multisample: wgpu::MultisampleState {
                     count: 1,
                     mask: !0, // all masks
@@ -143,9 +167,9 @@ impl super::Deserialized for Vertex {
         shader: &Shader,
         buffer_layouts: &mut Vec<wgpu::VertexBufferLayout<'static>>,
         bind_layouts: &Vec<&BindGroupLayout>
-    ) -> Result<(), std::io::Error> {
+    ) -> Result<(), Error> {
         
-        self.pipeline = Some(Vertex::create_pipeline(device, config, shader, buffer_layouts, bind_layouts)?); 
+        self.pipeline = Some(Vertex::create_pipeline(device, config, shader, self.depth, buffer_layouts, bind_layouts)?); 
         self.vertex_buffer = Some(Vertex::create_vertex_buffer(device, &self.buffer_list));
 
         Ok(())
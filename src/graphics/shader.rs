@@ -1,7 +1,7 @@
-use std::io;
 use wgpu::{ShaderModule, Device};
 use serde::{Serialize, Deserialize};
 use crate::util::{file, hash};
+use crate::error::Error;
 
 #[derive(Serialize, Deserialize)]
 pub struct Shader {
@@ -13,7 +13,7 @@ pub struct Shader {
 }
 
 impl Shader {
-    pub fn new(path: &str, device: &Device) -> Result<Self, io::Error> {
+    pub fn new(path: &str, device: &Device) -> Result<Self, Error> {
         let (path, hash, module) = Shader::create(path, device)?;
 
         Ok(Self {
@@ -23,7 +23,7 @@ impl Shader {
         })
     }
 
-    pub fn modify(&mut self, path: &str, device: &Device) -> Result<(), io::Error> {
+    pub fn modify(&mut self, path: &str, device: &Device) -> Result<(), Error> {
         let (path, hash, module) = Shader::create(path, device)?;
         self.path = path;
         self.hash = hash;
@@ -32,16 +32,23 @@ impl Shader {
         Ok(())
     }
 
-    fn create(path: &str, device: &Device) -> Result<(String, u64, Option<ShaderModule>), io::Error> {
+    fn create(path: &str, device: &Device) -> Result<(String, u64, Option<ShaderModule>), Error> {
         let path = file::absolute_path(path)?;
         let content = std::fs::read_to_string(&path)?;
         let hash = hash::get(&path);
-        let module = Some(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+
+        // Invalid WGSL is reported here instead of reaching the device's error handler
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some(&hash.to_string()),
             source: wgpu::ShaderSource::Wgsl(content.into()),
-        }));
+        });
+
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            return Err(Error::ShaderCompile { path, msg: e.to_string() })
+        }
 
-        Ok((path, hash, module))
+        Ok((path, hash, Some(module)))
     }
 }
 
@@ -7,6 +7,7 @@ mod editor;
 mod graphics;
 mod system;
 mod util;
+mod error;
 
 fn main() {
     
@@ -0,0 +1,133 @@
+use super::entity::Entity;
+use super::component_manager::{ComponentManager, component::Componentable};
+use super::events::PendingEvent;
+
+// Handle to an entity recorded with `Commands::spawn`, resolved when the commands are applied
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spawned(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Entity(Entity),
+    Spawned(usize),
+}
+
+impl From<Entity> for Target {
+    fn from(e: Entity) -> Self {
+        Target::Entity(e)
+    }
+}
+
+impl From<Spawned> for Target {
+    fn from(s: Spawned) -> Self {
+        Target::Spawned(s.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Spawn,
+    Despawn(Target),
+    DespawnRecursive(Target),
+    Attach(Target, u64),
+    Detach(Target, u64),
+    Reparent { child: Target, parent: Option<Target> },
+}
+
+// Structural changes recorded while the ECS is borrowed,
+// applied in order by `ECS::apply_commands` at the next sync point
+#[derive(Default)]
+pub struct Commands {
+    list: Vec<Command>,
+    spawn_count: usize,
+    events: Vec<Box<dyn PendingEvent>>, // sent after the structural commands
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            spawn_count: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn spawn(&mut self) -> Spawned {
+        let spawned = Spawned(self.spawn_count);
+        self.spawn_count += 1;
+        self.list.push(Command::Spawn);
+
+        return spawned
+    }
+
+    pub fn despawn(&mut self, target: impl Into<Target>) {
+        self.list.push(Command::Despawn(target.into()));
+    }
+
+    pub fn despawn_recursive(&mut self, target: impl Into<Target>) {
+        self.list.push(Command::DespawnRecursive(target.into()));
+    }
+
+    pub fn attach<T: Componentable>(&mut self, target: impl Into<Target>) {
+        let hash = ComponentManager::type_hash::<T>();
+        self.list.push(Command::Attach(target.into(), hash));
+    }
+
+    pub fn detach<T: Componentable>(&mut self, target: impl Into<Target>) {
+        let hash = ComponentManager::type_hash::<T>();
+        self.list.push(Command::Detach(target.into(), hash));
+    }
+
+    pub fn set_parent(&mut self, child: impl Into<Target>, parent: impl Into<Target>) {
+        self.list.push(Command::Reparent { child: child.into(), parent: Some(parent.into()) });
+    }
+
+    pub fn clear_parent(&mut self, child: impl Into<Target>) {
+        self.list.push(Command::Reparent { child: child.into(), parent: None });
+    }
+
+    pub fn send_event<T: Send + Sync + 'static>(&mut self, event: T) {
+        self.events.push(Box::new(event));
+    }
+
+    // Moves every command of `other` to the end of this queue
+    pub fn append(&mut self, other: Commands) {
+        let offset = self.spawn_count;
+        let shift = |t: Target| match t {
+            Target::Spawned(i) => Target::Spawned(i + offset),
+            Target::Entity(e) => Target::Entity(e),
+        };
+
+        for command in other.list {
+            self.list.push(match command {
+                Command::Spawn => Command::Spawn,
+                Command::Despawn(t) => Command::Despawn(shift(t)),
+                Command::DespawnRecursive(t) => Command::DespawnRecursive(shift(t)),
+                Command::Attach(t, hash) => Command::Attach(shift(t), hash),
+                Command::Detach(t, hash) => Command::Detach(shift(t), hash),
+                Command::Reparent { child, parent } => {
+                    Command::Reparent { child: shift(child), parent: parent.map(shift) }
+                },
+            });
+        }
+
+        self.spawn_count += other.spawn_count;
+        self.events.extend(other.events);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty() && self.events.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len() + self.events.len()
+    }
+
+    pub fn take_events(&mut self) -> Vec<Box<dyn PendingEvent>> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn drain(self) -> Vec<Command> {
+        self.list
+    }
+}
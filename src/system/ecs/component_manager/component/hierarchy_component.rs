@@ -1,182 +1,230 @@
-use std::collections::HashSet;
-use std::io;
+use std::collections::{HashMap, VecDeque};
 use serde::{Serialize, Deserialize};
 
 use super::{Componentable, Component};
-use crate::{system::ecs::Entity, game::Game, app::Viewport};
-use crate::util::hash;
+use crate::system::ecs::Entity;
+use crate::system::ecs::query::{Row, RowMut};
+use crate::error::Error;
 
-#[derive(Serialize, Deserialize)]
-struct Data {
+#[derive(Serialize, Deserialize, Componentable)]
+#[component(on_detach = unlink_parent)]
+pub struct HierarchyComponent {
+    pub component: Component,
+    #[entity]
     entity: Vec<Entity>,
+    #[column(manual, skip_row, map_entities)]
     parent: Vec<Option<Entity>>,
-    children: Vec<HashSet<Entity>>,
+    #[column(readonly, skip_row, map_entities)]
+    children: Vec<Vec<Entity>>, // in the order they were added, so walks are deterministic
+
+    // Bumped whenever a parent changes, `moved` keeps the count of each entity's last move
+    #[serde(skip)]
+    moves: u64,
+    #[serde(skip)]
+    moved: HashMap<Entity, u64>,
 }
 
-impl Data {
+impl HierarchyComponent {
     pub fn new() -> Self {
         Self {
+            component: Component::new(),
             entity: Vec::new(),
             parent: Vec::new(),
             children: Vec::new(),
+            moves: 0,
+            moved: HashMap::new(),
         }
     }
-}
 
-#[derive(Serialize, Deserialize)]
-pub struct HierarchyComponent {
-    pub component: Component,
-    data: Data,
-}
-
-#[typetag::serde]
-impl Componentable for HierarchyComponent {
-    fn attach(&mut self, entity: Entity) -> Result<usize, std::io::Error>  {
-        if self.component.does_exist(&entity) {
-            return Err(io::Error::new(io::ErrorKind::Other,
-                "ERROR::HierarchyComponent::attach()::entity already exist"))
+    pub fn add_child(&mut self, p_index: usize, c_index: usize) -> Result<(), Error> {
+        if !self.component.bounds_check(p_index) || 
+            !self.component.bounds_check(c_index) {
+                return Err(Error::OutOfBounds { index: p_index.max(c_index), len: self.entity.len() })
         }
 
-        let index = self.component.entities.len();
-        self.component.entities.insert(entity, index);
+        let p = self.entity[p_index];
+        let c = self.entity[c_index];
 
-        self.data.entity.push(entity);
-        self.data.parent.push(None);
-        self.data.children.push(HashSet::new());
+        if self.would_cycle(p_index, c) {
+            return Err(Error::HierarchyCycle { parent: p, child: c })
+        }
 
-        Ok(index)
-    }
+        // Unlink from the previous parent so its children list doesn't go stale
+        if let Some(old) = self.parent[c_index] {
+            if let Some(old_index) = self.component.find_index(&old) {
+                self.children[old_index].retain(|e| *e != c);
+                self.component.mark_changed(old);
+            }
+        }
 
-    fn detach(&mut self, entity: Entity) -> Result<(), std::io::Error>  {
-        if !self.component.does_exist(&entity) {
-            return Err(io::Error::new(io::ErrorKind::Other,
-                "ERROR::HierarchyComponent::detach()::entity doesn't exist"))
+        if !self.children[p_index].contains(&c) {
+            self.children[p_index].push(c);
         }
+        self.parent[c_index] = Some(p);
+        self.component.mark_changed(p);
+        self.component.mark_changed(c);
+        self.record_move(c);
 
-        let to_remove = self.component.entities[&entity];
-        let last = self.component.entities.len() - 1;
-        let swapped = self.data.entity[last];
+        Ok(())
+    }
 
-        // Remove from parent
-        if let Some(p) = self.data.parent[to_remove] {
-            if let Some(p_index) = self.component.find_index(&p) {
-                self.remove_child(p_index, to_remove)?
-            }
+    pub fn move_child(&mut self, old_p_index: usize, p_index: usize, c_index: usize) -> Result<(), Error> {
+        if !self.component.bounds_check(old_p_index) || 
+            !self.component.bounds_check(p_index) ||
+            !self.component.bounds_check(c_index) {
+                return Err(Error::OutOfBounds { index: old_p_index.max(p_index).max(c_index), len: self.entity.len() })
         }
 
-        self.data.entity.swap(to_remove, last);
-        self.data.parent.swap(to_remove, last);
-        self.data.children.swap(to_remove, last);
-
-        self.data.entity.pop();
-        self.data.parent.pop();
-        self.data.children.pop();
+        if self.would_cycle(p_index, self.entity[c_index]) {
+            return Err(Error::HierarchyCycle { parent: self.entity[p_index], child: self.entity[c_index] })
+        }
 
-        self.component.entities.insert(swapped, to_remove);
-        self.component.entities.remove(&entity);
+        self.remove_child(old_p_index, c_index)?;
+        self.add_child(p_index, c_index)?;
 
         Ok(())
     }
 
-    fn handle_update(&mut self, _dt: f32, _game: &Game) {
+    pub fn remove_child(&mut self, p_index: usize, c_index: usize) -> Result<(), Error> {
+        if !self.component.bounds_check(p_index) || 
+            !self.component.bounds_check(c_index) {
+                return Err(Error::OutOfBounds { index: p_index.max(c_index), len: self.entity.len() })
+        }
 
-    }
+        let c = self.entity[c_index];
 
-    fn handle_render(&mut self, _dt: f32, _game: &Game, _viewport: &Viewport) {
-        
+        self.children[p_index].retain(|e| *e != c);
+        self.parent[c_index] = None;
+        self.component.mark_changed(self.entity[p_index]);
+        self.component.mark_changed(c);
+        self.record_move(c);
+
+        Ok(())
     }
 
-    fn is_empty(&self) -> bool {
-        self.component.entities.is_empty() 
+    pub fn get_parent(&self, index: usize) -> Option<Entity> {
+        if !self.component.bounds_check(index) {
+            return None
+        }
+
+        return self.parent[index]
     }
 
-    fn get_hash(&self) -> u64 {
-        hash::get(&String::from(std::any::type_name::<HierarchyComponent>()))
+    // Entities whose parent changed after `moves()` returned `since`
+    pub fn moved_since(&self, since: u64) -> impl Iterator<Item = Entity> + '_ {
+        self.moved.iter()
+            .filter(move |(_, count)| **count > since)
+            .map(|(e, _)| *e)
     }
 
-    fn as_any(&self) -> &dyn std::any::Any {
-        self as &dyn std::any::Any
+    pub fn moves(&self) -> u64 {
+        return self.moves
     }
 
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-        self as &mut dyn std::any::Any
+    fn record_move(&mut self, entity: Entity) {
+        self.moves += 1;
+        self.moved.insert(entity, self.moves);
     }
-}
 
-impl HierarchyComponent {
-    pub fn new() -> Self {
-        Self {
-            component: Component::new(),
-            data: Data::new(),
+    fn unlink_parent(&mut self, index: usize) -> Result<(), Error> {
+        if let Some(p) = self.parent[index] {
+            if let Some(p_index) = self.component.find_index(&p) {
+                self.remove_child(p_index, index)?
+            }
         }
+
+        self.moved.remove(&self.entity[index]);
+
+        Ok(())
     }
 
-    pub fn add_child(&mut self, p_index: usize, c_index: usize) -> Result<(), io::Error> {
-        if !self.component.bounds_check(p_index) || 
-            !self.component.bounds_check(c_index) {
-                return Err(io::Error::new(io::ErrorKind::Other,
-                    "ERROR::HierarchyComponent::add_child()::out of bounds"))
+    // Pre-order walk of the subtree rooted at `index`, starting with its own entity.
+    // Siblings are visited in the order they were added.
+    pub fn iter_depth_first(&self, index: usize) -> impl Iterator<Item = Entity> + '_ {
+        let mut stack = Vec::new();
+        if let Some(e) = self.get_entity(index) {
+            stack.push(e);
         }
 
-        let p = self.data.entity[p_index];
-        let c = self.data.entity[c_index];
-
-        self.data.children[p_index].insert(c);
-        self.data.parent[c_index] = Some(p);
+        std::iter::from_fn(move || {
+            let e = stack.pop()?;
+            if let Some(i) = self.component.find_index(&e) {
+                stack.extend(self.children[i].iter().rev().copied());
+            }
 
-        Ok(())
+            Some(e)
+        })
     }
 
-    pub fn move_child(&mut self, old_p_index: usize, p_index: usize, c_index: usize) -> Result<(), io::Error> {
-        if !self.component.bounds_check(old_p_index) || 
-            !self.component.bounds_check(p_index) ||
-            !self.component.bounds_check(c_index) {
-                return Err(io::Error::new(io::ErrorKind::Other,
-                    "ERROR::HierarchyComponent::move_child()::out of bounds"))
+    // Level-order walk of the subtree rooted at `index`, starting with its own entity
+    pub fn iter_breadth_first(&self, index: usize) -> impl Iterator<Item = Entity> + '_ {
+        let mut queue = VecDeque::new();
+        if let Some(e) = self.get_entity(index) {
+            queue.push_back(e);
         }
 
-        self.remove_child(old_p_index, c_index)?;
-        self.add_child(p_index, c_index)?;
+        std::iter::from_fn(move || {
+            let e = queue.pop_front()?;
+            if let Some(i) = self.component.find_index(&e) {
+                queue.extend(self.children[i].iter().copied());
+            }
 
-        Ok(())
+            Some(e)
+        })
     }
 
-    pub fn remove_child(&mut self, p_index: usize, c_index: usize) -> Result<(), io::Error> {
-        if !self.component.bounds_check(p_index) || 
-            !self.component.bounds_check(c_index) {
-                return Err(io::Error::new(io::ErrorKind::Other,
-                    "ERROR::HierarchyComponent::remove_child()::out of bounds"))
+    // Parent first, root last
+    pub fn get_ancestors(&self, index: usize) -> Vec<Entity> {
+        let mut list = Vec::new();
+        let mut current = self.get_parent(index);
+
+        while let Some(p) = current {
+            list.push(p);
+            current = match self.component.find_index(&p) {
+                Some(i) => self.parent[i],
+                None => None
+            };
         }
 
-        let c = self.data.entity[c_index];
+        return list
+    }
 
-        self.data.children[p_index].remove(&c);
-        self.data.parent[c_index] = None;
+    pub fn get_root(&self, index: usize) -> Option<Entity> {
+        let entity = self.get_entity(index)?;
 
-        Ok(())
+        match self.get_ancestors(index).last() {
+            Some(root) => return Some(*root),
+            None => return Some(entity)
+        }
     }
 
-    pub fn get_entity(&self, index: usize) -> Option<Entity> {
+    // Roots have a depth of 0
+    pub fn get_depth(&self, index: usize) -> Option<usize> {
         if !self.component.bounds_check(index) {
             return None
         }
 
-        return Some(self.data.entity[index])
+        return Some(self.get_ancestors(index).len())
     }
 
-    pub fn get_parent(&self, index: usize) -> Option<Entity> {
-        if !self.component.bounds_check(index) {
-            return None
-        }
+    pub fn is_descendant(&self, index: usize, ancestor: Entity) -> bool {
+        return self.get_ancestors(index).contains(&ancestor)
+    }
 
-        return self.data.parent[index]
+    // Parenting `child` to `p_index` would loop if the parent is the child itself or below it
+    fn would_cycle(&self, p_index: usize, child: Entity) -> bool {
+        return self.entity[p_index] == child || self.is_descendant(p_index, child)
     }
+}
 
-    pub fn get_children(&self, index: usize) -> Option<&HashSet<Entity>> {
-        if !self.component.bounds_check(index) {
-            return None
-        }
+impl<'a> Row<'a, HierarchyComponent> {
+    pub fn parent(&self) -> Option<Entity> {
+        self.get().parent[self.index]
+    }
+}
 
-        return Some(&self.data.children[index])
+impl<'a> RowMut<'a, HierarchyComponent> {
+    pub fn parent(&self) -> Option<Entity> {
+        self.get().parent[self.index]
     }
-}
\ No newline at end of file
+}
@@ -1,31 +1,69 @@
 pub mod name_component;
 pub mod hierarchy_component;
+pub mod transform_component;
+pub mod reflect;
+
+// `#[derive(Componentable)]` shares the trait's name, see `iguana_eye_derive`
+pub use iguana_eye_derive::Componentable;
 
 #[typetag::serde(tag = "type")]
-pub trait Componentable {
-    fn attach(&mut self, entity: Entity) -> Result<usize, std::io::Error>;
-    fn detach(&mut self, entity: Entity) -> Result<(), std::io::Error>;
-    fn handle_update(&mut self, dt: f32, game: &Game);
+pub trait Componentable: Reflect + Send + Sync {
+    fn attach(&mut self, entity: Entity) -> Result<usize, Error>;
+    fn detach(&mut self, entity: Entity) -> Result<(), Error>;
+    fn handle_update(&mut self, dt: f32, game: &Game, commands: &mut Commands);
     fn handle_render(&mut self, dt: f32, game: &Game, viewport: &Viewport);
     fn is_empty(&self) -> bool;
+    // Stable name saved files use for the component, `#[component(id = "...")]`
+    fn id() -> &'static str where Self: Sized;
+    fn get_id(&self) -> &'static str;
     fn get_hash(&self) -> u64;
+    fn entity_at(&self, index: usize) -> Option<Entity>;
+    // Column values of a single row, keyed by column name
+    fn save_row(&self, index: usize) -> Option<serde_json::Value>;
+    fn load_row(&mut self, index: usize, value: &serde_json::Value) -> Result<(), Error>;
+    // Moves every row of `other`, a component of the same type, into this one.
+    // Entities are renamed through `map`, as are the ids in `#[column(map_entities)]` columns.
+    fn merge(&mut self, other: &mut dyn Componentable, map: &HashMap<Entity, Entity>) -> Result<(), Error>;
+    fn component(&self) -> &Component;
+    fn component_mut(&mut self) -> &mut Component;
     fn as_any(&self) -> &dyn std::any::Any;
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
 }
 
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
-use crate::{system::ecs::Entity, game::Game, app::Viewport};
+use crate::{system::ecs::Entity, game::Game, app::Viewport, error::Error};
+use crate::system::ecs::commands::Commands;
+use reflect::Reflect;
+
+// Ticks at which a row was attached and last modified
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Ticks {
+    pub added: u64,
+    pub changed: u64,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Component {
+    #[serde(with = "crate::util::serialize::map_as_list")]
     pub entities: HashMap<Entity, usize>, // <entity, index>
+
+    #[serde(default, with = "crate::util::serialize::map_as_list")]
+    ticks: HashMap<Entity, Ticks>,
+    #[serde(default)]
+    removed: Vec<(Entity, u64)>, // <entity, tick it was detached at>
+    #[serde(default)]
+    tick: u64,
 }
 
 impl Component {
     pub fn new() -> Self {
         Self {
             entities: HashMap::new(),
+            ticks: HashMap::new(),
+            removed: Vec::new(),
+            tick: 0,
         }
     }
 
@@ -43,4 +81,56 @@ impl Component {
     pub fn bounds_check(&self, index: usize) -> bool {
         return index < self.entities.len()
     }
-}
\ No newline at end of file
+
+    pub fn tick(&self) -> u64 {
+        return self.tick
+    }
+
+    pub fn set_tick(&mut self, tick: u64) {
+        self.tick = tick;
+    }
+
+    pub fn get_ticks(&self, entity: &Entity) -> Option<Ticks> {
+        return self.ticks.get(entity).copied()
+    }
+
+    pub fn mark_added(&mut self, entity: Entity) {
+        self.ticks.insert(entity, Ticks { added: self.tick, changed: self.tick });
+    }
+
+    pub fn mark_changed(&mut self, entity: Entity) {
+        if let Some(ticks) = self.ticks.get_mut(&entity) {
+            ticks.changed = self.tick;
+        }
+    }
+
+    pub fn mark_removed(&mut self, entity: Entity) {
+        self.ticks.remove(&entity);
+        self.removed.push((entity, self.tick));
+    }
+
+    // Rows without recorded ticks, e.g. loaded from an older save, count as tick 0
+    pub fn is_added_since(&self, entity: &Entity, since: u64) -> bool {
+        match self.ticks.get(entity) {
+            Some(ticks) => return ticks.added > since,
+            None => return false
+        }
+    }
+
+    pub fn is_changed_since(&self, entity: &Entity, since: u64) -> bool {
+        match self.ticks.get(entity) {
+            Some(ticks) => return ticks.changed > since,
+            None => return false
+        }
+    }
+
+    pub fn removed_since(&self, since: u64) -> impl Iterator<Item = Entity> + '_ {
+        self.removed.iter()
+            .filter(move |(_, tick)| *tick > since)
+            .map(|(e, _)| *e)
+    }
+
+    pub fn clear_removed(&mut self, until: u64) {
+        self.removed.retain(|(_, tick)| *tick > until);
+    }
+}
@@ -1,180 +1,192 @@
 use serde::{Serialize, Deserialize};
-use std::{collections::HashSet, io};
+use std::collections::{HashSet, HashMap, BTreeMap};
+use std::ops::Bound;
 use super::{Component, Componentable};
-use crate::util::hash;
-use crate::{system::ecs::Entity, game::Game, app::Viewport};
+use crate::util::{hash, glob};
+use crate::error::Error;
+use crate::system::ecs::Entity;
+use crate::system::ecs::query::RowMut;
 
-#[derive(Serialize, Deserialize)]
-struct Data {
+#[derive(Serialize, Deserialize, Componentable)]
+#[component(on_detach = release_row, before_load = release_row, on_load = claim_row)]
+pub struct NameComponent {
+    pub component: Component,
+    #[entity]
     entity: Vec<Entity>,
+    #[column(readonly, default = (0, String::from("")))]
     name: Vec<(u64, String)>,
+    #[column(readonly)]
     tags: Vec<HashSet<(u64, String)>>,
+
+    // Reverse lookups, unnamed entities aren't in `by_name`
+    #[serde(default)]
+    by_name: BTreeMap<String, Entity>,
+    #[serde(default)]
+    by_tag: HashMap<String, HashSet<Entity>>,
 }
 
-impl Data {
+impl NameComponent {
     pub fn new() -> Self {
         Self {
+            component: Component::new(),
             entity: Vec::new(),
             name: Vec::new(),
             tags: Vec::new(),
+            by_name: BTreeMap::new(),
+            by_tag: HashMap::new(),
         }
     }
-}
 
-#[derive(Serialize, Deserialize)]
-pub struct NameComponent {
-    pub component: Component,
-    data: Data,
-    hash_list: HashSet<u64>,
-}
-
-#[typetag::serde]
-impl Componentable for NameComponent {
-    fn attach(&mut self, entity: Entity) -> Result<usize, std::io::Error> {
-        if self.component.does_exist(&entity) {
-            return Err(io::Error::new(io::ErrorKind::Other,
-                "ERROR::NameComponent::attach()::entity already exist"))
+    pub fn set_name(&mut self, index: usize, name: String) -> bool {
+        if !self.component.bounds_check(index) {
+            return false
         }
 
-        let index = self.component.entities.len();
-
-        self.component.entities.insert(entity, index);
+        self.release_name(index);
+        self.name[index] = (0, name);
+        self.claim_name(index);
+        self.component.mark_changed(self.entity[index]);
 
-        self.data.entity.push(entity);
-        self.data.name.push((0, String::from("")));
-        self.data.tags.push(HashSet::new());
-
-        Ok(index)
+        return true
     }
 
-    fn detach(&mut self, entity: Entity) -> Result<(), std::io::Error> {
-        if !self.component.does_exist(&entity) {
-            return Err(io::Error::new(io::ErrorKind::NotFound,
-                "ERROR::NameComponent::detach()::entity doesn't exist"))
-        }
-
-        let to_remove = self.component.entities[&entity];
-        let last = self.component.entities.len() - 1;
-        let swapped = self.data.entity[last];
-
-        let hash = self.data.name[to_remove].0;
-        self.hash_list.remove(&hash);
-
-        self.data.entity.swap(to_remove, last);
-        self.data.name.swap(to_remove, last);
-        self.data.tags.swap(to_remove, last);
-
-        self.data.entity.pop();
-        self.data.name.pop();
-        self.data.tags.pop();
-
-        self.component.entities.insert(swapped, to_remove);
-        self.component.entities.remove(&entity);
-
-        return Ok(())
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.by_name.get(name).copied()
     }
 
-    fn handle_update(&mut self, _dt: f32, _game: &Game) {
-
+    pub fn find_by_tag<'a>(&'a self, tag: &str) -> impl Iterator<Item = Entity> + 'a {
+        self.by_tag.get(tag).into_iter().flatten().copied()
     }
 
-    fn handle_render(&mut self, _dt: f32, _game: &Game, _viewport: &Viewport){
-
+    // Named entities whose name starts with `prefix`, in name order
+    pub fn find_by_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = Entity> + 'a {
+        self.by_name.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(name, _)| name.starts_with(prefix))
+            .map(|(_, e)| *e)
     }
 
-    fn is_empty(&self) -> bool {
-        return self.component.entities.is_empty()
+    // Named entities whose name matches `pattern`, see `glob::matches`. In name order.
+    pub fn find_by_glob<'a>(&'a self, pattern: &'a str) -> impl Iterator<Item = Entity> + 'a {
+        self.by_name.iter()
+            .filter(move |(name, _)| glob::matches(pattern, name))
+            .map(|(_, e)| *e)
     }
 
-    fn get_hash(&self) -> u64 {
-        hash::get(&String::from(std::any::type_name::<NameComponent>()))
-    }
+    pub fn add_tag(&mut self, index: usize, name: String) -> bool {
+        if !self.component.bounds_check(index) {
+            return false
+        }
 
-    fn as_any(&self) -> &dyn std::any::Any {
-        self as &dyn std::any::Any
-    }
+        let hash = hash::get(&name);
+        if !self.tags[index].insert((hash, name.clone())) {
+            return false
+        }
+        self.by_tag.entry(name).or_default().insert(self.entity[index]);
 
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-        self as &mut dyn std::any::Any
+        self.component.mark_changed(self.entity[index]);
+        return true
     }
-}
 
-impl NameComponent {
-    pub fn new() -> Self {
-        Self {
-            component: Component::new(),
-            data: Data::new(),
-            hash_list: HashSet::from([0])
+    pub fn remove_tag(&mut self, index: usize, name: String) -> bool {
+        if !self.component.bounds_check(index) {
+            return false
         }
-    }
 
-    pub fn get_name(&self, index: usize) -> Option<&(u64, String)> {
-        if !self.component.bounds_check(index) {
-            return None
+        let hash = hash::get(&name);
+        if !self.tags[index].remove(&(hash, name.clone())) {
+            return false
         }
+        self.untag(&name, self.entity[index]);
 
-        return self.data.name.get(index)
+        self.component.mark_changed(self.entity[index]);
+        return true
     }
 
-    pub fn get_tags(&self, index: usize) -> Option<&HashSet<(u64, String)>> {
+    pub fn has_tag(&self, index: usize, name: String) -> bool {
         if !self.component.bounds_check(index) {
-            return None
+            return false
         }
 
-        return self.data.tags.get(index)
+        let hash = hash::get(&name);
+        return self.tags[index].contains(&(hash, name))
     }
 
-    pub fn set_name(&mut self, index: usize, name: String) -> bool {
-        if !self.component.bounds_check(index) {
-            return false
+    fn release_row(&mut self, index: usize) -> Result<(), Error> {
+        self.release_name(index);
+
+        let e = self.entity[index];
+        for (_, tag) in self.tags[index].clone() {
+            self.untag(&tag, e);
         }
 
-        let val = self.hash_name(name);
+        Ok(())
+    }
 
-        self.hash_list.insert(val.0);
-        self.data.name[index] = val;
+    // A loaded name may already be taken, it gets a unique one like `set_name` does.
+    // The row's previous name and tags were released by `before_load`.
+    fn claim_row(&mut self, index: usize) {
+        self.claim_name(index);
 
-        return true
+        let e = self.entity[index];
+        for (_, tag) in self.tags[index].iter() {
+            self.by_tag.entry(tag.clone()).or_default().insert(e);
+        }
     }
 
-    pub fn add_tag(&mut self, index: usize, name: String) -> bool {
-        if !self.component.bounds_check(index) {
-            return false
+    fn release_name(&mut self, index: usize) {
+        let name = &self.name[index].1;
+        if self.by_name.get(name) == Some(&self.entity[index]) {
+            self.by_name.remove(name);
         }
-
-        let hash = hash::get(&name);
-        return self.data.tags[index].insert((hash, name))
     }
 
-    pub fn remove_tag(&mut self, index: usize, name: String) -> bool {
-        if !self.component.bounds_check(index) {
-            return false
+    fn claim_name(&mut self, index: usize) {
+        let name = std::mem::take(&mut self.name[index].1);
+        if name.is_empty() {
+            self.name[index] = (0, name);
+            return
         }
 
-        let hash = hash::get(&name);
-        return self.data.tags[index].remove(&(hash, name))
+        let val = self.hash_name(name);
+        self.by_name.insert(val.1.clone(), self.entity[index]);
+        self.name[index] = val;
     }
 
-    pub fn has_tag(&self, index: usize, name: String) -> bool {
-        if !self.component.bounds_check(index) {
-            return false
+    fn untag(&mut self, tag: &str, e: Entity) {
+        if let Some(set) = self.by_tag.get_mut(tag) {
+            set.remove(&e);
+            if set.is_empty() {
+                self.by_tag.remove(tag);
+            }
         }
-
-        let hash = hash::get(&name);
-        return self.data.tags[index].contains(&(hash, name))
     }
 
     fn hash_name(&self, name: String) -> (u64, String) {
-        let mut hash = hash::get(&name);
         let mut temp = name.clone();
         let mut count = 0;
 
-        while self.hash_list.contains(&hash) {
+        while self.by_name.contains_key(&temp) {
             temp = name.clone() + "_" + &count.to_string();
-            hash = hash::get(&temp);
             count += 1;
         }
 
-        return (hash, temp)
+        return (hash::get(&temp), temp)
+    }
+}
+
+impl<'a> RowMut<'a, NameComponent> {
+    pub fn set_name(&mut self, name: String) -> bool {
+        let index = self.index;
+        self.get_mut().set_name(index, name)
     }
-}
\ No newline at end of file
+
+    pub fn add_tag(&mut self, name: String) -> bool {
+        let index = self.index;
+        self.get_mut().add_tag(index, name)
+    }
+
+    pub fn remove_tag(&mut self, name: String) -> bool {
+        let index = self.index;
+        self.get_mut().remove_tag(index, name)
+    }
+}
@@ -0,0 +1,38 @@
+use serde_json::Value;
+
+use crate::system::ecs::entity::Entity;
+use crate::error::Error;
+
+// A column of a component, see `Reflect`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub ty: &'static str, // `std::any::type_name` of a single value
+    pub readonly: bool, // `#[column(readonly)]` or derived from other columns, `#[column(skip_row)]`
+}
+
+// A column's value for one entity
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub info: FieldInfo,
+    pub value: Value,
+}
+
+// Access to the columns of any component without knowing its type, generated by
+// `#[derive(Componentable)]`. Values are JSON trees, like the rows of `save_row`.
+pub trait Reflect {
+    fn field_infos(&self) -> Vec<FieldInfo>;
+    fn get_field(&self, entity: Entity, name: &str) -> Result<Value, Error>;
+    // Written through `load_row`, so the component's load hooks keep its own indices current
+    fn set_field(&mut self, entity: Entity, name: &str, value: Value) -> Result<(), Error>;
+
+    fn fields(&self, entity: Entity) -> Result<Vec<Field>, Error> {
+        let mut list = Vec::new();
+        for info in self.field_infos() {
+            let value = self.get_field(entity, info.name)?;
+            list.push(Field { info, value });
+        }
+
+        Ok(list)
+    }
+}
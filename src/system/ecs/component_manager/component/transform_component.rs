@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+use cgmath::{Vector3, Quaternion, Matrix4, SquareMatrix, One};
+
+use super::{Componentable, Component};
+use super::hierarchy_component::HierarchyComponent;
+use crate::system::ecs::Entity;
+use crate::system::ecs::query::{Row, RowMut};
+
+#[derive(Serialize, Deserialize, Componentable)]
+#[component(on_load = loaded)]
+pub struct TransformComponent {
+    pub component: Component,
+    #[entity]
+    entity: Vec<Entity>,
+    #[column(manual, default = Vector3::new(0.0, 0.0, 0.0))]
+    translation: Vec<Vector3<f32>>,
+    #[column(manual, default = Quaternion::one())]
+    rotation: Vec<Quaternion<f32>>,
+    #[column(manual, default = Vector3::new(1.0, 1.0, 1.0))]
+    scale: Vec<Vector3<f32>>,
+    #[column(manual, skip_row, default = Matrix4::identity())]
+    local: Vec<Matrix4<f32>>,
+    #[column(manual, skip_row, default = Matrix4::identity())]
+    world: Vec<Matrix4<f32>>,
+    #[column(manual, skip_row, default = true)]
+    dirty: Vec<bool>,
+
+    // `HierarchyComponent::moves()` as of the last propagation
+    #[serde(skip)]
+    seen_moves: u64,
+}
+
+impl TransformComponent {
+    pub fn new() -> Self {
+        Self {
+            component: Component::new(),
+            entity: Vec::new(),
+            translation: Vec::new(),
+            rotation: Vec::new(),
+            scale: Vec::new(),
+            local: Vec::new(),
+            world: Vec::new(),
+            dirty: Vec::new(),
+            seen_moves: 0,
+        }
+    }
+
+    pub fn get_translation(&self, index: usize) -> Option<Vector3<f32>> {
+        if !self.component.bounds_check(index) {
+            return None
+        }
+
+        return Some(self.translation[index])
+    }
+
+    pub fn get_rotation(&self, index: usize) -> Option<Quaternion<f32>> {
+        if !self.component.bounds_check(index) {
+            return None
+        }
+
+        return Some(self.rotation[index])
+    }
+
+    pub fn get_scale(&self, index: usize) -> Option<Vector3<f32>> {
+        if !self.component.bounds_check(index) {
+            return None
+        }
+
+        return Some(self.scale[index])
+    }
+
+    pub fn set_translation(&mut self, index: usize, translation: Vector3<f32>) -> bool {
+        if !self.component.bounds_check(index) {
+            return false
+        }
+
+        self.translation[index] = translation;
+        self.dirty[index] = true;
+        self.component.mark_changed(self.entity[index]);
+
+        return true
+    }
+
+    pub fn set_rotation(&mut self, index: usize, rotation: Quaternion<f32>) -> bool {
+        if !self.component.bounds_check(index) {
+            return false
+        }
+
+        self.rotation[index] = rotation;
+        self.dirty[index] = true;
+        self.component.mark_changed(self.entity[index]);
+
+        return true
+    }
+
+    pub fn set_scale(&mut self, index: usize, scale: Vector3<f32>) -> bool {
+        if !self.component.bounds_check(index) {
+            return false
+        }
+
+        self.scale[index] = scale;
+        self.dirty[index] = true;
+        self.component.mark_changed(self.entity[index]);
+
+        return true
+    }
+
+    // Local and world matrices are only current after `propagate()`
+    pub fn get_local_matrix(&self, index: usize) -> Option<Matrix4<f32>> {
+        if !self.component.bounds_check(index) {
+            return None
+        }
+
+        return Some(self.local[index])
+    }
+
+    pub fn get_world_matrix(&self, index: usize) -> Option<Matrix4<f32>> {
+        if !self.component.bounds_check(index) {
+            return None
+        }
+
+        return Some(self.world[index])
+    }
+
+    // World matrix laid out for `InstanceBuffer::model`
+    pub fn get_model(&self, index: usize) -> Option<[[f32; 4]; 4]> {
+        return self.get_world_matrix(index).map(|m| m.into())
+    }
+
+    pub fn is_dirty(&self, index: usize) -> bool {
+        if !self.component.bounds_check(index) {
+            return false
+        }
+
+        return self.dirty[index]
+    }
+
+    pub fn mark_dirty(&mut self, entity: &Entity) -> bool {
+        match self.component.find_index(entity) {
+            Some(index) => {
+                self.dirty[index] = true;
+                return true
+            },
+            None => return false
+        }
+    }
+
+    fn loaded(&mut self, index: usize) {
+        self.dirty[index] = true;
+    }
+
+    // Recomputes world matrices for every dirty entity and everything below it.
+    // Subtrees without a dirty entity are left alone.
+    pub fn propagate(&mut self, hc: &HierarchyComponent) {
+        // Reparented subtrees sit under a new world matrix, however they were moved.
+        // A count below the last one seen means the hierarchy was replaced.
+        let since = if hc.moves() < self.seen_moves { 0 } else { self.seen_moves };
+        for moved in hc.moved_since(since) {
+            if let Some(h_index) = hc.component.find_index(&moved) {
+                for e in hc.iter_depth_first(h_index) {
+                    self.mark_dirty(&e);
+                }
+            }
+        }
+        self.seen_moves = hc.moves();
+
+        let dirty: HashSet<Entity> = self.entity.iter()
+            .zip(self.dirty.iter())
+            .filter(|(_, d)| **d)
+            .map(|(e, _)| *e)
+            .collect();
+
+        for e in dirty.iter() {
+            let ancestors = match hc.component.find_index(e) {
+                Some(h_index) => hc.get_ancestors(h_index),
+                None => Vec::new()
+            };
+
+            // A dirty ancestor will recompute this entity as part of its own subtree
+            if ancestors.iter().any(|a| dirty.contains(a)) {
+                continue
+            }
+
+            let parent_world = ancestors.iter()
+                .find_map(|a| self.component.find_index(a))
+                .map(|i| self.world[i])
+                .unwrap_or_else(Matrix4::identity);
+
+            self.propagate_subtree(hc, *e, parent_world);
+        }
+    }
+
+    // Entities below `root` without a transform pass their parent's world matrix through
+    fn propagate_subtree(&mut self, hc: &HierarchyComponent, root: Entity, parent_world: Matrix4<f32>) {
+        let mut stack = vec![(root, parent_world)];
+
+        while let Some((e, parent_world)) = stack.pop() {
+            let world = match self.component.find_index(&e) {
+                Some(index) => {
+                    let local = Matrix4::from_translation(self.translation[index])
+                        * Matrix4::from(self.rotation[index])
+                        * Matrix4::from_nonuniform_scale(
+                            self.scale[index].x,
+                            self.scale[index].y,
+                            self.scale[index].z);
+
+                    self.local[index] = local;
+                    self.world[index] = parent_world * local;
+                    self.dirty[index] = false;
+                    self.component.mark_changed(e);
+                    self.world[index]
+                },
+                None => parent_world
+            };
+
+            if let Some(h_index) = hc.component.find_index(&e) {
+                if let Some(children) = hc.get_children(h_index) {
+                    stack.extend(children.iter().map(|c| (*c, world)));
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Row<'a, TransformComponent> {
+    pub fn translation(&self) -> Vector3<f32> {
+        self.get().translation[self.index]
+    }
+
+    pub fn rotation(&self) -> Quaternion<f32> {
+        self.get().rotation[self.index]
+    }
+
+    pub fn scale(&self) -> Vector3<f32> {
+        self.get().scale[self.index]
+    }
+
+    pub fn world(&self) -> Matrix4<f32> {
+        self.get().world[self.index]
+    }
+}
+
+impl<'a> RowMut<'a, TransformComponent> {
+    pub fn translation(&self) -> Vector3<f32> {
+        self.get().translation[self.index]
+    }
+
+    pub fn rotation(&self) -> Quaternion<f32> {
+        self.get().rotation[self.index]
+    }
+
+    pub fn scale(&self) -> Vector3<f32> {
+        self.get().scale[self.index]
+    }
+
+    pub fn world(&self) -> Matrix4<f32> {
+        self.get().world[self.index]
+    }
+
+    pub fn set_translation(&mut self, translation: Vector3<f32>) -> bool {
+        let index = self.index;
+        self.get_mut().set_translation(index, translation)
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quaternion<f32>) -> bool {
+        let index = self.index;
+        self.get_mut().set_rotation(index, rotation)
+    }
+
+    pub fn set_scale(&mut self, scale: Vector3<f32>) -> bool {
+        let index = self.index;
+        self.get_mut().set_scale(index, scale)
+    }
+}
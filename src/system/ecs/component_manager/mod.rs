@@ -1,17 +1,28 @@
 pub mod component;
+pub mod signature_index;
 
 use std::collections::{HashMap, HashSet};
-use std::io::{Error, ErrorKind};
 use serde::{Serialize, Deserialize};
 
 use super::entity::Entity;
 use component::{Componentable, hierarchy_component::HierarchyComponent};
-use crate::{util::hash, game::Game, app::Viewport};
+use signature_index::{SignatureIndex, StorageMode};
+use super::commands::Commands;
+use crate::{util::hash, game::Game, app::Viewport, error::Error};
 
 #[derive(Serialize, Deserialize)]
 pub struct ComponentManager {
     indices: HashMap<u64, usize>,
     components: Vec<Box<dyn Componentable>>,
+
+    #[serde(default)]
+    tick: u64,
+
+    #[serde(default)]
+    mode: StorageMode,
+    // Row index only kept up to date in `StorageMode::Indexed`
+    #[serde(default, alias = "archetypes")]
+    signatures: SignatureIndex,
 }
 
 impl ComponentManager {
@@ -19,12 +30,96 @@ impl ComponentManager {
         Self {
             indices: HashMap::new(),
             components: Vec::new(),
+            // Starts above 0 so a system's first run (last run 0) sees every existing row
+            tick: 1,
+            mode: StorageMode::Sparse,
+            signatures: SignatureIndex::new(),
+        }
+    }
+
+    pub fn storage_mode(&self) -> StorageMode {
+        return self.mode
+    }
+
+    // Switching to `StorageMode::Indexed` indexes every existing row
+    pub fn set_storage_mode(&mut self, mode: StorageMode) {
+        if self.mode == mode {
+            return
+        }
+
+        self.mode = mode;
+        self.signatures = match mode {
+            StorageMode::Sparse => SignatureIndex::new(),
+            StorageMode::Indexed => SignatureIndex::build(self.iter()),
+        };
+    }
+
+    // None unless the world uses `StorageMode::Indexed`
+    pub fn signature_index(&self) -> Option<&SignatureIndex> {
+        match self.mode {
+            StorageMode::Sparse => return None,
+            StorageMode::Indexed => return Some(&self.signatures)
+        }
+    }
+
+    pub fn tick(&self) -> u64 {
+        return self.tick
+    }
+
+    // Every row change from now on is recorded at `tick`
+    pub fn set_tick(&mut self, tick: u64) {
+        self.tick = tick;
+        for c in self.components.iter_mut() {
+            c.component_mut().set_tick(tick);
+        }
+    }
+
+    // Forgets removals recorded at or before `until`
+    pub fn clear_removed(&mut self, until: u64) {
+        for c in self.components.iter_mut() {
+            c.component_mut().clear_removed(until);
+        }
+    }
+
+    // Every row counts as added at the current tick, e.g. after a scene was loaded
+    pub fn mark_all_added(&mut self) {
+        for c in self.components.iter_mut() {
+            let entities: Vec<Entity> = c.component().entities.keys().copied().collect();
+            for e in entities {
+                c.component_mut().mark_added(e);
+            }
+        }
+    }
+
+    // Moves every row of `other` into this manager with its entities renamed through `map`.
+    // Every component type of `other` must already be added here.
+    pub fn merge(&mut self, other: &mut ComponentManager, map: &HashMap<Entity, Entity>) -> Result<(), Error> {
+        if let Some(c) = other.iter().find(|c| !self.has(c.get_hash())) {
+            return Err(Error::ComponentNotRegistered(c.get_hash()))
+        }
+
+        for oc in other.components.iter_mut() {
+            let hash = oc.get_hash();
+            let c = &mut self.components[self.indices[&hash]];
+
+            let start = c.component().entities.len();
+            c.merge(oc.as_mut(), map)?;
+
+            if self.mode == StorageMode::Indexed {
+                for row in start..c.component().entities.len() {
+                    if let Some(e) = c.entity_at(row) {
+                        self.signatures.insert(e, hash, row);
+                    }
+                }
+            }
         }
+
+        Ok(())
     }
 
-    pub fn handle_update(&mut self, dt: f32, game: &Game) {
+    pub fn handle_update(&mut self, dt: f32, game: &Game, commands: &mut Commands) {
         for c in self.components.iter_mut() {
-            c.handle_update(dt, game);
+            c.handle_update(dt, game, commands);
         }
     }
 
@@ -34,12 +129,11 @@ impl ComponentManager {
         }
     }
 
-    pub fn add(&mut self, c: Box<dyn Componentable>) -> Result<(), Error> {
+    pub fn add(&mut self, mut c: Box<dyn Componentable>) -> Result<(), Error> {
         let hash = c.get_hash();
-        if self.indices.contains_key(&hash) {
-            return Err(Error::new(ErrorKind::NotFound,
-                "ERROR::ComponentManager::add()::already exist"))
-        }
+        self.check_unregistered(c.as_ref())?;
+
+        c.component_mut().set_tick(self.tick);
 
         let index = self.components.len();
         self.indices.insert(hash, index);
@@ -51,20 +145,17 @@ impl ComponentManager {
     pub fn insert(
         &mut self, 
         index: usize, 
-        c: Box<dyn Componentable>
+        mut c: Box<dyn Componentable>
     ) -> Result<(), Error> {
 
         let hash = c.get_hash();
-        if self.indices.contains_key(&hash) {
-            return Err(Error::new(ErrorKind::NotFound,
-                "ERROR::ComponentManager::insert()::already exist"))
-        }
+        self.check_unregistered(c.as_ref())?;
 
         if index >= self.components.len() {
-            return Err(Error::new(ErrorKind::NotFound,
-                "ERROR::ComponentManager::insert()::out of bounds"))
+            return Err(Error::OutOfBounds { index, len: self.components.len() })
         }
 
+        c.component_mut().set_tick(self.tick);
         self.indices.insert(hash, index);
         self.components.insert(index, c);
         self.update_indices(index);
@@ -75,15 +166,13 @@ impl ComponentManager {
     pub fn remove<T: Componentable>(&mut self) -> Result<(), Error> {
         let hash = ComponentManager::type_hash::<T>();
         if !self.indices.contains_key(&hash) {
-            return Err(Error::new(ErrorKind::NotFound,
-                "ERROR::ComponentManager::remove()::doesn't exist"))
+            return Err(Error::ComponentNotRegistered(hash))
         }
 
         let index = self.indices[&hash];
         let c = &self.components[index];
         if !c.is_empty() {
-            return Err(Error::new(ErrorKind::NotFound,
-                "ERROR::ComponentManager::remove()::component not empty"))
+            return Err(Error::ComponentNotEmpty(hash))
         }
 
         self.indices.remove(&hash);
@@ -103,13 +192,22 @@ impl ComponentManager {
         hash: u64
     ) -> Result<usize, Error> {
 
-        match self.get_by_hash_mut(hash) {
-            Some(c) => c.attach(e),
+        let index = match self.get_by_hash_mut(hash) {
+            Some(c) => {
+                let index = c.attach(e)?;
+                c.component_mut().mark_added(e);
+                index
+            },
             None => {
-                return Err(Error::new(ErrorKind::NotFound,
-                    "ERROR::component_manager::attach()::cannot find component"));
+                return Err(Error::ComponentNotRegistered(hash));
             }
+        };
+
+        if self.mode == StorageMode::Indexed {
+            self.signatures.insert(e, hash, index);
         }
+
+        return Ok(index)
     }
 
     pub fn detach(
@@ -118,13 +216,27 @@ impl ComponentManager {
         hash: u64
     ) -> Result<(), Error> {
 
-        match self.get_by_hash_mut(hash) {
-            Some(c) => c.detach(e),
+        // The row swapped into the removed one, if any
+        let moved = match self.get_by_hash_mut(hash) {
+            Some(c) => {
+                let row = c.component().find_index(&e);
+                c.detach(e)?;
+                c.component_mut().mark_removed(e);
+                row.and_then(|r| Some((c.entity_at(r)?, r)))
+            },
             None => {
-                return Err(Error::new(ErrorKind::NotFound,
-                    "ERROR::component_manager::detach()::cannot find component"));
+                return Err(Error::ComponentNotRegistered(hash));
+            }
+        };
+
+        if self.mode == StorageMode::Indexed {
+            self.signatures.remove(e, hash);
+            if let Some((moved, row)) = moved {
+                self.signatures.set_row(moved, hash, row);
             }
         }
+
+        return Ok(())
     }
 
     pub fn get<T: Componentable + 'static>(&self) -> Option<&T> {
@@ -157,6 +269,27 @@ impl ComponentManager {
         return Some(c.as_mut());
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Componentable> + '_ {
+        self.components.iter().map(|c| c.as_ref())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn Componentable>> {
+        self.components.iter_mut()
+    }
+
+    // Mutable components alongside the signature index, for views that borrow both
+    pub fn iter_mut_with_signature_index(&mut self) -> (
+        impl Iterator<Item = &mut Box<dyn Componentable>>,
+        Option<&SignatureIndex>
+    ) {
+        let signatures = match self.mode {
+            StorageMode::Sparse => None,
+            StorageMode::Indexed => Some(&self.signatures)
+        };
+
+        (self.components.iter_mut(), signatures)
+    }
+
     pub fn find_index<T: Componentable>(&self) -> Option<usize> {
         let hash = ComponentManager::type_hash::<T>();
         Some(*self.indices.get(&hash)?)
@@ -166,8 +299,36 @@ impl ComponentManager {
         self.has_children(e)?;
 
         for hash in hash_list {
-            if let Some(c) = self.get_by_hash_mut(*hash) {
-                c.detach(e)?;
+            if self.has(*hash) {
+                self.detach(e, *hash)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Everything `purge_entity` checks except children, so a whole subtree
+    // can be validated before any of it is removed
+    pub fn check_purge(&self, e: Entity, hash_list: &HashSet<u64>) -> Result<(), Error> {
+        match self.get::<HierarchyComponent>() {
+            Some(hc) => {
+                if hc.component.find_index(&e).is_none() {
+                    return Err(Error::ComponentNotAttached {
+                        entity: e,
+                        component: ComponentManager::type_hash::<HierarchyComponent>(),
+                    })
+                }
+            },
+            None => {
+                return Err(Error::ComponentNotRegistered(ComponentManager::type_hash::<HierarchyComponent>()))
+            }
+        }
+
+        for hash in hash_list {
+            if let Some(c) = self.get_by_hash(*hash) {
+                if c.component().find_index(&e).is_none() {
+                    return Err(Error::ComponentNotAttached { entity: e, component: *hash })
+                }
             }
         }
 
@@ -181,20 +342,20 @@ impl ComponentManager {
                     Some(index) => {
                         if let Some(children) = hc.get_children(index) {
                             if !children.is_empty() {
-                                return Err(Error::new(ErrorKind::Other,
-                                    "ERROR::component_manager::has_children()::children list not empty"))
+                                return Err(Error::HasChildren(e))
                             }
                         }
                     },
                     None => {
-                        return Err(Error::new(ErrorKind::NotFound,
-                            "ERROR::component_manager::has_children()::cannot find index"))
+                        return Err(Error::ComponentNotAttached {
+                            entity: e,
+                            component: ComponentManager::type_hash::<HierarchyComponent>(),
+                        })
                     }
                 }
             },
             None => {
-                return Err(Error::new(ErrorKind::NotFound,
-                    "ERROR::component_manager::has_children()::cannot find hierarchy component"))
+                return Err(Error::ComponentNotRegistered(ComponentManager::type_hash::<HierarchyComponent>()))
             }
         }
 
@@ -208,7 +369,23 @@ impl ComponentManager {
         }
     }
 
+    // Two types whose ids hash the same can't both be added
+    fn check_unregistered(&self, c: &dyn Componentable) -> Result<(), Error> {
+        let hash = c.get_hash();
+        let other = match self.indices.get(&hash) {
+            Some(val) => &self.components[*val],
+            None => return Ok(())
+        };
+
+        if other.as_any().type_id() != c.as_any().type_id() {
+            return Err(Error::ComponentIdCollision { hash, id: c.get_id(), other: other.get_id() })
+        }
+
+        return Err(Error::ComponentAlreadyRegistered(hash))
+    }
+
+    // FNV-1a of the component's id, the same in every build
     pub fn type_hash<T: Componentable>() -> u64 {
-        hash::get(&String::from(std::any::type_name::<T>()))
+        hash::fnv1a(T::id().as_bytes())
     }
 }
\ No newline at end of file
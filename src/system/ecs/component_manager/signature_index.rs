@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+use crate::system::ecs::entity::Entity;
+use super::component::Componentable;
+
+// How a world finds the component rows of an entity.
+// Component data always stays in each component's own columns, the modes only differ in lookup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum StorageMode {
+    // Each component maps entities to its own rows, joins look every entity up per component
+    #[default]
+    Sparse,
+    // Entities are also indexed by their exact component set, joins scan the matching groups
+    // and read each row directly instead of looking it up per component.
+    // Saved as `Archetype` before it was renamed.
+    #[serde(alias = "Archetype")]
+    Indexed,
+}
+
+// Entities with the exact same set of components.
+// `columns` holds the row of every entity in each component, in `signature` order,
+// the component values themselves aren't moved here.
+#[derive(Serialize, Deserialize)]
+pub struct SignatureGroup {
+    signature: Vec<u64>, // sorted component hashes
+    entities: Vec<Entity>,
+    columns: Vec<Vec<usize>>,
+}
+
+impl SignatureGroup {
+    fn new(signature: Vec<u64>) -> Self {
+        let columns = signature.iter().map(|_| Vec::new()).collect();
+
+        Self {
+            signature,
+            entities: Vec::new(),
+            columns,
+        }
+    }
+
+    pub fn signature(&self) -> &[u64] {
+        &self.signature
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn contains(&self, hash: u64) -> bool {
+        return self.signature.binary_search(&hash).is_ok()
+    }
+
+    // Rows in component `hash`, parallel to `entities()`
+    pub fn column(&self, hash: u64) -> Option<&[usize]> {
+        let index = self.signature.binary_search(&hash).ok()?;
+        return Some(&self.columns[index])
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn push(&mut self, e: Entity, rows: Vec<usize>) -> usize {
+        let slot = self.entities.len();
+        self.entities.push(e);
+        for (column, row) in self.columns.iter_mut().zip(rows) {
+            column.push(row);
+        }
+
+        slot
+    }
+
+    // Returns the removed rows and the entity moved into `slot`, if any
+    fn swap_remove(&mut self, slot: usize) -> (Vec<usize>, Option<Entity>) {
+        self.entities.swap_remove(slot);
+        let rows = self.columns.iter_mut()
+            .map(|c| c.swap_remove(slot))
+            .collect();
+
+        (rows, self.entities.get(slot).copied())
+    }
+}
+
+// Index from component sets to the entities and rows that have them.
+// Kept beside the component storage, not a replacement for it.
+// Entities without any component don't belong to a group.
+#[derive(Default)]
+pub struct SignatureIndex {
+    list: Vec<SignatureGroup>,
+    by_signature: HashMap<Vec<u64>, usize>,
+    locations: HashMap<Entity, (usize, usize)>, // <entity, (group, slot)>
+}
+
+impl SignatureIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Indexes the rows already stored in `components`
+    pub fn build<'a>(components: impl Iterator<Item = &'a dyn Componentable>) -> Self {
+        let mut rows: HashMap<Entity, Vec<(u64, usize)>> = HashMap::new();
+        for c in components {
+            let hash = c.get_hash();
+            for (e, index) in c.component().entities.iter() {
+                rows.entry(*e).or_default().push((hash, *index));
+            }
+        }
+
+        let mut signatures = Self::new();
+        for (e, mut list) in rows {
+            list.sort_unstable_by_key(|(h, _)| *h);
+            signatures.place(e, list);
+        }
+
+        signatures
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SignatureGroup> {
+        self.list.iter()
+    }
+
+    // Non-empty groups that contain every hash in `hashes`
+    pub fn matching<'a>(&'a self, hashes: &'a [u64]) -> impl Iterator<Item = &'a SignatureGroup> + 'a {
+        self.list.iter()
+            .filter(move |a| !a.is_empty() && hashes.iter().all(|h| a.contains(*h)))
+    }
+
+    pub fn count(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn find(&self, e: Entity) -> Option<&SignatureGroup> {
+        let (index, _) = self.locations.get(&e)?;
+        return self.list.get(*index)
+    }
+
+    pub fn row(&self, e: Entity, hash: u64) -> Option<usize> {
+        let (index, slot) = self.locations.get(&e)?;
+        let column = self.list[*index].column(hash)?;
+        return Some(column[*slot])
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.by_signature.clear();
+        self.locations.clear();
+    }
+
+    // `e` was given `row` in component `hash`
+    pub fn insert(&mut self, e: Entity, hash: u64, row: usize) {
+        let mut rows = self.take(e);
+        rows.retain(|(h, _)| *h != hash);
+        rows.push((hash, row));
+        rows.sort_unstable_by_key(|(h, _)| *h);
+
+        self.place(e, rows);
+    }
+
+    // `e` no longer has a row in component `hash`
+    pub fn remove(&mut self, e: Entity, hash: u64) {
+        let mut rows = self.take(e);
+        rows.retain(|(h, _)| *h != hash);
+
+        if !rows.is_empty() {
+            self.place(e, rows);
+        }
+    }
+
+    // Component `hash` moved the row of `e`, as a swap remove does to its last row
+    pub fn set_row(&mut self, e: Entity, hash: u64, row: usize) {
+        if let Some((index, slot)) = self.locations.get(&e) {
+            let group = &mut self.list[*index];
+            if let Ok(c) = group.signature.binary_search(&hash) {
+                group.columns[c][*slot] = row;
+            }
+        }
+    }
+
+    // Removes `e` from its group, returning its rows sorted by hash
+    fn take(&mut self, e: Entity) -> Vec<(u64, usize)> {
+        let (index, slot) = match self.locations.remove(&e) {
+            Some(val) => val,
+            None => return Vec::new()
+        };
+
+        let group = &mut self.list[index];
+        let (rows, moved) = group.swap_remove(slot);
+        if let Some(moved) = moved {
+            self.locations.insert(moved, (index, slot));
+        }
+
+        group.signature.iter().copied().zip(rows).collect()
+    }
+
+    fn place(&mut self, e: Entity, rows: Vec<(u64, usize)>) {
+        let signature: Vec<u64> = rows.iter().map(|(h, _)| *h).collect();
+        let index = match self.by_signature.get(&signature) {
+            Some(val) => *val,
+            None => {
+                let index = self.list.len();
+                self.list.push(SignatureGroup::new(signature.clone()));
+                self.by_signature.insert(signature, index);
+                index
+            }
+        };
+
+        let slot = self.list[index].push(e, rows.into_iter().map(|(_, r)| r).collect());
+        self.locations.insert(e, (index, slot));
+    }
+}
+
+impl Serialize for SignatureIndex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.list.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for SignatureIndex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let list: Vec<SignatureGroup> = Vec::deserialize(deserializer)?;
+
+        let mut by_signature = HashMap::new();
+        let mut locations = HashMap::new();
+        for (index, a) in list.iter().enumerate() {
+            by_signature.insert(a.signature.clone(), index);
+            for (slot, e) in a.entities.iter().enumerate() {
+                locations.insert(*e, (index, slot));
+            }
+        }
+
+        Ok(Self { list, by_signature, locations })
+    }
+}
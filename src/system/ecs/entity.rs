@@ -1,20 +1,58 @@
+use std::collections::{HashMap, HashSet};
 use serde::{Serialize, Deserialize};
 
-#[derive(Clone, Copy)]
-#[derive(Hash, Eq)]
+#[derive(Clone, Copy, Debug)]
+#[derive(Hash, PartialEq, Eq)]
 #[derive(Serialize, Deserialize)]
 pub struct Entity {
     pub id: u64,
+    pub generation: u32,
 }
 
 impl Entity {
     pub fn new(id: u64) -> Self {
-        Self { id }
+        Self { id, generation: 0 }
+    }
+
+    pub fn with_generation(id: u64, generation: u32) -> Self {
+        Self { id, generation }
+    }
+}
+
+// Values holding entity ids that need renaming when entities are moved between worlds,
+// see `#[column(map_entities)]`. Ids missing from `map` are left as they are.
+pub trait MapEntities {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>);
+}
+
+impl MapEntities for Entity {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>) {
+        if let Some(e) = map.get(self) {
+            *self = *e;
+        }
     }
 }
 
-impl PartialEq for Entity {
-    fn eq(&self, other: &Self) -> bool {
-        return self.id == other.id
+impl<T: MapEntities> MapEntities for Option<T> {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>) {
+        if let Some(val) = self {
+            val.map_entities(map);
+        }
     }
-}
\ No newline at end of file
+}
+
+impl<T: MapEntities> MapEntities for Vec<T> {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>) {
+        for val in self.iter_mut() {
+            val.map_entities(map);
+        }
+    }
+}
+
+impl MapEntities for HashSet<Entity> {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>) {
+        *self = self.drain()
+            .map(|e| map.get(&e).copied().unwrap_or(e))
+            .collect();
+    }
+}
@@ -5,7 +5,9 @@ use super::entity::Entity;
 
 #[derive(Serialize, Deserialize)]
 pub struct EntityManager {
+    #[serde(with = "crate::util::serialize::map_as_list")]
     entities: HashMap<Entity, HashSet<u64>>,
+    generations: HashMap<u64, u32>, // <id, current generation>
     free_ids: Vec<u64>,
     next_id: u64,
 }
@@ -14,6 +16,7 @@ impl EntityManager {
     pub fn new() -> Self {
         Self {
             entities: HashMap::new(),
+            generations: HashMap::new(),
             free_ids: Vec::new(),
             next_id: 0
         }
@@ -23,11 +26,11 @@ impl EntityManager {
         let mut entity = Entity::new(0);
 
         match self.free_ids.pop() {
-            Some(id) => entity.id = id,
+            Some(id) => entity = Entity::with_generation(id, self.generation(id)),
             None => {
                 self.next_id += 1;
                 entity.id = self.next_id;
-                while self.does_exist(entity) {
+                while self.is_id_used(entity.id) {
                     self.next_id += 1;
                     entity.id = self.next_id;
                 }
@@ -42,6 +45,8 @@ impl EntityManager {
     pub fn remove(&mut self, entity: Entity) -> bool {
         match self.entities.remove(&entity) {
             Some(_) => {
+                // Bump the generation so stale handles to this id are rejected
+                self.generations.insert(entity.id, entity.generation.wrapping_add(1));
                 self.free_ids.push(entity.id);
                 return true
             },
@@ -84,7 +89,22 @@ impl EntityManager {
         return self.entities.contains_key(&entity);
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.keys().copied()
+    }
+
+    pub fn generation(&self, id: u64) -> u32 {
+        match self.generations.get(&id) {
+            Some(generation) => return *generation,
+            None => return 0
+        }
+    }
+
     pub fn count(&self) -> usize {
         return self.entities.len()
     }
-}
\ No newline at end of file
+
+    fn is_id_used(&self, id: u64) -> bool {
+        return self.does_exist(Entity::with_generation(id, self.generation(id)))
+    }
+}
@@ -0,0 +1,190 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use super::entity::Entity;
+use crate::error::Error;
+
+// Built-in events sent by the ECS itself
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntityEvent {
+    Created(Entity),
+    Removed(Entity),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComponentEvent {
+    Attached { entity: Entity, hash: u64 },
+    Detached { entity: Entity, hash: u64 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HierarchyEvent {
+    Reparented { child: Entity, old_parent: Option<Entity>, new_parent: Option<Entity> },
+}
+
+// Read position of a single reader, each reader keeps its own
+pub struct EventCursor<T> {
+    next: u64,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventCursor<T> {
+    fn default() -> Self {
+        Self { next: 0, marker: PhantomData }
+    }
+}
+
+impl<T> EventCursor<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// Double-buffered channel. Events survive one `update()` so readers running
+// anywhere in the frame after the send still see them, then they are dropped.
+pub struct Events<T> {
+    previous: Vec<(u64, T)>,
+    current: Vec<(u64, T)>,
+    next_id: u64,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push((self.next_id, event));
+        self.next_id += 1;
+    }
+
+    // Events sent since `cursor` last read, oldest first
+    pub fn read<'a>(&'a self, cursor: &mut EventCursor<T>) -> impl Iterator<Item = &'a T> + 'a {
+        let start = cursor.next;
+        cursor.next = self.next_id;
+
+        self.previous.iter()
+            .chain(self.current.iter())
+            .filter(move |(id, _)| *id >= start)
+            .map(|(_, e)| e)
+    }
+
+    // Drops the older buffer, called once per frame
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+trait EventStore: Send + Sync {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Send + Sync + 'static> EventStore for Events<T> {
+    fn update(&mut self) {
+        Events::update(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Every event channel of the ECS, keyed by event type
+#[derive(Default)]
+pub struct EventRegistry {
+    stores: HashMap<TypeId, Box<dyn EventStore>>,
+}
+
+impl EventRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registry with the ECS's own events already added
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.add::<EntityEvent>();
+        registry.add::<ComponentEvent>();
+        registry.add::<HierarchyEvent>();
+
+        registry
+    }
+
+    // Adding an event type twice keeps the existing channel
+    pub fn add<T: Send + Sync + 'static>(&mut self) {
+        self.stores.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Events::<T>::new()));
+    }
+
+    pub fn has<T: Send + Sync + 'static>(&self) -> bool {
+        self.stores.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&Events<T>> {
+        self.stores.get(&TypeId::of::<T>())?.as_any().downcast_ref::<Events<T>>()
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut Events<T>> {
+        self.stores.get_mut(&TypeId::of::<T>())?.as_any_mut().downcast_mut::<Events<T>>()
+    }
+
+    pub fn send<T: Send + Sync + 'static>(&mut self, event: T) -> Result<(), Error> {
+        match self.get_mut::<T>() {
+            Some(events) => {
+                events.send(event);
+                return Ok(())
+            },
+            None => {
+                return Err(Error::EventNotRegistered(std::any::type_name::<T>()))
+            }
+        }
+    }
+
+    pub fn update(&mut self) {
+        for store in self.stores.values_mut() {
+            store.update();
+        }
+    }
+}
+
+// An event recorded in `Commands`, sent when the commands are applied
+pub trait PendingEvent: Send {
+    fn send(self: Box<Self>, registry: &mut EventRegistry) -> Result<(), Error>;
+}
+
+impl<T: Send + Sync + 'static> PendingEvent for T {
+    fn send(self: Box<Self>, registry: &mut EventRegistry) -> Result<(), Error> {
+        registry.send(*self)
+    }
+}
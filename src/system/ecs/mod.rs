@@ -1,19 +1,49 @@
 pub mod entity;
 pub mod entity_manager;
 pub mod component_manager;
+pub mod query;
+pub mod schedule;
+pub mod commands;
+pub mod events;
+pub mod resources;
+pub mod prefab;
 
-use std::{io::Error, collections::HashSet};
+use std::collections::{HashSet, HashMap, BTreeMap};
 
 use serde::{Serialize, Deserialize};
-use entity::Entity;
+use entity::{Entity, MapEntities};
 use entity_manager::EntityManager;
 use component_manager::{ComponentManager, component::*, component::Componentable};
-use crate::{game::Game, app::Viewport};
+use component_manager::signature_index::{SignatureIndex, StorageMode};
+use component_manager::component::reflect::Field;
+use query::{Query, QueryData, ReadOnlyQueryData, QueryFilter, ComponentStore, Request};
+use schedule::{Schedule, Stage, System, Conflict, ExecutionMode, StageData};
+use resources::{Resources, SavedResource};
+use commands::{Commands, Command, Target};
+use prefab::{Prefab, PrefabEntity, PrefabInstance, Overrides};
+use events::{EventRegistry, Events, EventCursor, EntityEvent, ComponentEvent, HierarchyEvent};
+use cgmath::Matrix4;
+use crate::{game::Game, app::Viewport, error::Error, util::serialize::{self, Format}};
+use crate::util::{hash, migration::{self, Migrations, Versioned}};
+use serde_json::Value;
 
 #[derive(Serialize, Deserialize)]
 pub struct ECS {
     entity_manager: EntityManager,
     component_manager: ComponentManager,
+
+    #[serde(skip)]
+    schedule: Schedule,
+
+    #[serde(skip, default = "EventRegistry::with_builtins")]
+    events: EventRegistry,
+
+    // Only resources inserted with `insert_saved_resource` are written out
+    #[serde(default)]
+    resources: Resources,
+
+    #[serde(default)]
+    prefab_instances: Vec<PrefabInstance>,
 }
 
 impl ECS {
@@ -21,21 +51,226 @@ impl ECS {
         let mut component_manager = ComponentManager::new();
         component_manager.add(Box::new(name_component::NameComponent::new()))?;
         component_manager.add(Box::new(hierarchy_component::HierarchyComponent::new()))?;
+        component_manager.add(Box::new(transform_component::TransformComponent::new()))?;
 
         Ok(Self {
             component_manager,
             entity_manager: EntityManager::new(),
+            schedule: Schedule::new(),
+            events: EventRegistry::with_builtins(),
+            resources: Resources::new(),
+            prefab_instances: Vec::new(),
         })
     }
 
-    pub fn handle_update(&mut self, dt: f32, game: &Game) {
-        self.component_manager.handle_update(dt, game);
+    pub fn handle_update(&mut self, dt: f32, game: &Game) -> Vec<Error> {
+        self.advance_tick();
+        self.events.update();
+
+        let mut commands = Commands::new();
+        self.component_manager.handle_update(dt, game, &mut commands);
+
+        let mut errors = self.apply_commands(commands);
+        errors.extend(self.run_systems(dt));
+
+        errors
     }
 
     pub fn handle_render(&mut self, dt: f32, game: &Game, viewport: &Viewport) {
         self.component_manager.handle_render(dt, game, viewport);
     }
 
+    pub fn add_system(&mut self, stage: Stage, system: Box<dyn System>) -> Result<(), Error> {
+        self.schedule.add_system(stage, system)
+    }
+
+    pub fn remove_system(&mut self, name: &str) -> bool {
+        self.schedule.remove_system(name)
+    }
+
+    pub fn build_schedule(&mut self) -> Result<(), Error> {
+        self.schedule.build()
+    }
+
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) {
+        self.schedule.set_execution_mode(mode);
+    }
+
+    // Can be switched at any time, existing rows are re-indexed
+    pub fn set_storage_mode(&mut self, mode: StorageMode) {
+        self.component_manager.set_storage_mode(mode);
+    }
+
+    pub fn storage_mode(&self) -> StorageMode {
+        self.component_manager.storage_mode()
+    }
+
+    pub fn signature_index(&self) -> Option<&SignatureIndex> {
+        self.component_manager.signature_index()
+    }
+
+    pub fn schedule_conflicts(&self) -> Result<Vec<Conflict>, Error> {
+        self.schedule.conflicts()
+    }
+
+    // Runs every stage, applying the commands systems recorded at the end of each one
+    pub fn run_systems(&mut self, dt: f32) -> Vec<Error> {
+        if let Err(e) = self.schedule.prepare() {
+            return vec![e]
+        }
+
+        let mut errors = Vec::new();
+        for stage in Stage::ALL {
+            // Render prep systems see world matrices that include this frame's changes
+            if stage == Stage::RenderPrep {
+                self.propagate_transforms();
+            }
+
+            let data = StageData {
+                entity_manager: &self.entity_manager,
+                component_manager: &mut self.component_manager,
+                resources: &mut self.resources,
+                events: &self.events,
+            };
+            let (commands, stage_errors) = self.schedule.run_stage(stage, dt, data);
+
+            errors.extend(stage_errors);
+            errors.extend(self.apply_commands(commands));
+        }
+
+        // Removals every system has had a chance to see are dropped
+        let seen = self.schedule.oldest_run().unwrap_or(self.component_manager.tick());
+        self.component_manager.clear_removed(seen);
+
+        errors
+    }
+
+    // Current change tick, rows modified from now on are recorded at this tick
+    pub fn tick(&self) -> u64 {
+        self.component_manager.tick()
+    }
+
+    pub fn advance_tick(&mut self) -> u64 {
+        let tick = self.component_manager.tick() + 1;
+        self.component_manager.set_tick(tick);
+
+        tick
+    }
+
+    // Applies recorded commands in order. A failing command doesn't stop the rest,
+    // its error is collected and returned instead.
+    pub fn apply_commands(&mut self, mut commands: Commands) -> Vec<Error> {
+        if commands.is_empty() {
+            return Vec::new()
+        }
+
+        // Keeps these changes visible to systems that ran at the previous tick
+        self.advance_tick();
+
+        let events = commands.take_events();
+        let mut spawned: Vec<Option<Entity>> = Vec::new();
+        let mut errors = Vec::new();
+
+        for command in commands.drain() {
+            let result = match command {
+                Command::Spawn => {
+                    match self.create_entity() {
+                        Ok(e) => {
+                            spawned.push(Some(e));
+                            Ok(())
+                        },
+                        Err(e) => {
+                            spawned.push(None);
+                            Err(e)
+                        }
+                    }
+                },
+                Command::Despawn(t) => {
+                    ECS::resolve(&spawned, t).and_then(|e| self.remove_entity(e))
+                },
+                Command::DespawnRecursive(t) => {
+                    ECS::resolve(&spawned, t).and_then(|e| self.remove_entity_recursive(e))
+                },
+                Command::Attach(t, hash) => {
+                    ECS::resolve(&spawned, t).and_then(|e| self.attach_component_by_hash(e, hash).map(|_| ()))
+                },
+                Command::Detach(t, hash) => {
+                    ECS::resolve(&spawned, t).and_then(|e| self.detach_component_by_hash(e, hash))
+                },
+                Command::Reparent { child, parent } => {
+                    ECS::resolve(&spawned, child).and_then(|c| {
+                        match parent {
+                            Some(p) => self.reparent_subtree(c, Some(ECS::resolve(&spawned, p)?)),
+                            None => self.reparent_subtree(c, None)
+                        }
+                    })
+                },
+            };
+
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+
+        for event in events {
+            if let Err(e) = event.send(&mut self.events) {
+                errors.push(e);
+            }
+        }
+
+        errors
+    }
+
+    // Replaces any resource of the same type. Plain resources aren't saved with the ECS.
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, resource: T) {
+        self.resources.insert(resource);
+    }
+
+    pub fn insert_saved_resource<T: SavedResource + 'static>(&mut self, resource: T) {
+        self.resources.insert_saved(resource);
+    }
+
+    pub fn remove_resource<T: 'static>(&mut self) -> bool {
+        self.resources.remove::<T>()
+    }
+
+    pub fn has_resource<T: 'static>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    pub fn resource<T: 'static>(&self) -> Option<&T> {
+        self.resources.get::<T>()
+    }
+
+    pub fn resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.resources.get_mut::<T>()
+    }
+
+    // Registers an event channel, adding the same type twice keeps the existing one
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) {
+        self.events.add::<T>();
+    }
+
+    pub fn send_event<T: Send + Sync + 'static>(&mut self, event: T) -> Result<(), Error> {
+        self.events.send(event)
+    }
+
+    pub fn get_events<T: Send + Sync + 'static>(&self) -> Option<&Events<T>> {
+        self.events.get::<T>()
+    }
+
+    pub fn get_events_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut Events<T>> {
+        self.events.get_mut::<T>()
+    }
+
+    // Events of type `T` sent since `cursor` last read
+    pub fn read_events<T: Send + Sync + 'static>(&self, cursor: &mut EventCursor<T>) -> Vec<&T> {
+        match self.events.get::<T>() {
+            Some(events) => events.read(cursor).collect(),
+            None => Vec::new()
+        }
+    }
+
     pub fn add_component(&mut self, c: Box<dyn Componentable>) -> Result<(), Error> {
         self.component_manager.add(c)
     }
@@ -68,22 +303,98 @@ impl ECS {
         self.component_manager.get_by_hash_mut(hash)
     }
 
+    // The fields of every component `e` has, by component id
+    pub fn reflect_entity(&self, e: Entity) -> Result<Vec<(&'static str, Vec<Field>)>, Error> {
+        let hash_list = match self.entity_manager.get_attached(e) {
+            Some(val) => val,
+            None => return Err(Error::EntityNotFound(e))
+        };
+
+        let mut list = Vec::new();
+        for hash in hash_list.iter() {
+            let c = match self.component_manager.get_by_hash(*hash) {
+                Some(val) => val,
+                None => return Err(Error::ComponentNotRegistered(*hash))
+            };
+            list.push((c.get_id(), c.fields(e)?));
+        }
+        list.sort_by_key(|(id, _)| *id);
+
+        Ok(list)
+    }
+
     pub fn create_entity(&mut self) -> Result<Entity, Error> {
         let e = self.entity_manager.create();
-        self.attach_component::<name_component::NameComponent>(e)?;
-        self.attach_component::<hierarchy_component::HierarchyComponent>(e)?;
+        let builtin = [
+            ComponentManager::type_hash::<name_component::NameComponent>(),
+            ComponentManager::type_hash::<hierarchy_component::HierarchyComponent>(),
+            ComponentManager::type_hash::<transform_component::TransformComponent>(),
+        ];
+
+        for hash in builtin {
+            if let Err(err) = self.component_manager.attach(e, hash) {
+                // Nobody has seen the entity yet, so it's freed without events
+                if let Some(hash_list) = self.entity_manager.get_attached(e) {
+                    _ = self.component_manager.purge_entity(e, hash_list);
+                }
+                self.entity_manager.remove(e);
+
+                return Err(err)
+            }
+
+            self.entity_manager.attach_component(e, hash);
+        }
+
+        _ = self.events.send(EntityEvent::Created(e));
+        for hash in builtin {
+            _ = self.events.send(ComponentEvent::Attached { entity: e, hash });
+        }
 
         Ok(e)
     }
 
     pub fn remove_entity(&mut self, e: Entity) -> Result<(), Error> {
+        self.validate_entity(e)?;
 
         // purge entity from all attached components
         if let Some(hash_list) = self.entity_manager.get_attached(e) {
             self.component_manager.purge_entity(e, hash_list)?;
+
+            for hash in hash_list.iter() {
+                _ = self.events.send(ComponentEvent::Detached { entity: e, hash: *hash });
+            }
         }
 
         self.entity_manager.remove(e);
+        _ = self.events.send(EntityEvent::Removed(e));
+
+        Ok(())
+    }
+
+    // Removes `e` and every entity below it in the hierarchy, children first
+    pub fn remove_entity_recursive(&mut self, e: Entity) -> Result<(), Error> {
+        self.validate_entity(e)?;
+
+        let subtree: Vec<Entity> = match self.component_manager.get::<hierarchy_component::HierarchyComponent>() {
+            Some(hc) => match hc.component.find_index(&e) {
+                Some(index) => hc.iter_depth_first(index).collect(),
+                None => vec![e]
+            },
+            None => vec![e]
+        };
+
+        // Nothing is removed unless every entity of the subtree can be
+        for entity in subtree.iter() {
+            self.validate_entity(*entity)?;
+            if let Some(hash_list) = self.entity_manager.get_attached(*entity) {
+                self.component_manager.check_purge(*entity, hash_list)?;
+            }
+        }
+
+        // Reversed pre-order puts every descendant before its ancestors
+        for entity in subtree.into_iter().rev() {
+            self.remove_entity(entity)?;
+        }
 
         Ok(())
     }
@@ -93,10 +404,21 @@ impl ECS {
         e: Entity
     ) -> Result<usize, Error> {
 
-        let hash = ComponentManager::type_hash::<T>();
+        self.attach_component_by_hash(e, ComponentManager::type_hash::<T>())
+    }
+
+    pub fn attach_component_by_hash(
+        &mut self, 
+        e: Entity,
+        hash: u64
+    ) -> Result<usize, Error> {
+
+        self.validate_entity(e)?;
+
         match self.component_manager.attach(e, hash) {
             Ok(index) => {
                 self.entity_manager.attach_component(e, hash);
+                _ = self.events.send(ComponentEvent::Attached { entity: e, hash });
                 return Ok(index)
             },
             Err(e) => return Err(e)
@@ -108,16 +430,412 @@ impl ECS {
         e: Entity
     ) -> Result<(), Error> {
 
-        let hash = ComponentManager::type_hash::<T>();
+        self.detach_component_by_hash(e, ComponentManager::type_hash::<T>())
+    }
+
+    pub fn detach_component_by_hash(
+        &mut self, 
+        e: Entity,
+        hash: u64
+    ) -> Result<(), Error> {
+
+        self.validate_entity(e)?;
+
         match self.component_manager.detach(e, hash) {
             Ok(()) => {
                 self.entity_manager.detach_component(e, hash);
+                _ = self.events.send(ComponentEvent::Detached { entity: e, hash });
                 return Ok(())
             },
             Err(e) => return Err(e)
         }
     }
 
+    pub fn query<Q: ReadOnlyQueryData>(&self) -> Result<Query<'_, Q>, Error> {
+        Query::new_ref(&self.entity_manager, &self.component_manager)
+    }
+
+    pub fn query_filtered<Q: ReadOnlyQueryData, F: QueryFilter>(&self) -> Result<Query<'_, Q, F>, Error> {
+        Query::new_ref(&self.entity_manager, &self.component_manager)
+    }
+
+    pub fn query_mut<Q: QueryData>(&mut self) -> Result<Query<'_, Q>, Error> {
+        Query::new(&self.entity_manager, &mut self.component_manager)
+    }
+
+    pub fn query_filtered_mut<Q: QueryData, F: QueryFilter>(&mut self) -> Result<Query<'_, Q, F>, Error> {
+        Query::new(&self.entity_manager, &mut self.component_manager)
+    }
+
+    // `Added` and `Changed` filters match rows touched after `since`, see `tick()`
+    pub fn query_filtered_since<Q: ReadOnlyQueryData, F: QueryFilter>(&self, since: u64) -> Result<Query<'_, Q, F>, Error> {
+        Query::new_ref_since(&self.entity_manager, &self.component_manager, since)
+    }
+
+    pub fn query_filtered_mut_since<Q: QueryData, F: QueryFilter>(&mut self, since: u64) -> Result<Query<'_, Q, F>, Error> {
+        Query::new_since(&self.entity_manager, &mut self.component_manager, since)
+    }
+
+    // Entities that lost `T` after `since`. Removals are kept until every system has seen them.
+    pub fn removed_since<T: Componentable + 'static>(&self, since: u64) -> Vec<Entity> {
+        match self.component_manager.get::<T>() {
+            Some(c) => c.component().removed_since(since).collect(),
+            None => Vec::new()
+        }
+    }
+
+    // Moves `child` and everything below it under `parent`,
+    // or makes it a root when `parent` is None
+    pub fn reparent_subtree(&mut self, child: Entity, parent: Option<Entity>) -> Result<(), Error> {
+        self.validate_entity(child)?;
+        if let Some(p) = parent {
+            self.validate_entity(p)?;
+        }
+
+        let hc = match self.component_manager.get_mut::<hierarchy_component::HierarchyComponent>() {
+            Some(val) => val,
+            None => {
+                return Err(Error::ComponentNotRegistered(ComponentManager::type_hash::<hierarchy_component::HierarchyComponent>()))
+            }
+        };
+
+        let c_index = match hc.component.find_index(&child) {
+            Some(val) => val,
+            None => {
+                return Err(Error::ComponentNotAttached { entity: child, component: hc.get_hash() })
+            }
+        };
+
+        let p_index = match parent {
+            Some(p) => match hc.component.find_index(&p) {
+                Some(val) => Some(val),
+                None => {
+                    return Err(Error::ComponentNotAttached { entity: p, component: hc.get_hash() })
+                }
+            },
+            None => None
+        };
+
+        let old_parent = hc.get_parent(c_index);
+        match p_index {
+            Some(p_index) => hc.add_child(p_index, c_index)?,
+            None => {
+                if let Some(old) = hc.get_parent(c_index) {
+                    if let Some(old_index) = hc.component.find_index(&old) {
+                        hc.remove_child(old_index, c_index)?;
+                    }
+                }
+            }
+        }
+
+        _ = self.events.send(HierarchyEvent::Reparented { child, old_parent, new_parent: parent });
+
+        Ok(())
+    }
+
+    pub fn save_scene(&self, path: &str) -> Result<(), Error> {
+        self.save_scene_as(path, Format::Json)
+    }
+
+    // `load_scene` and `merge_scene` read either format
+    pub fn save_scene_as(&self, path: &str, format: Format) -> Result<(), Error> {
+        serialize::write_versioned(path, self, format)
+    }
+
+    // Replaces every entity with the ones saved at `path`, keeping their ids.
+    // Systems, component types, plain resources and the storage mode are kept,
+    // saved resources are swapped for the scene's. Loaded rows count as added.
+    pub fn load_scene(&mut self, path: &str) -> Result<(), Error> {
+        let mut scene: ECS = serialize::read_versioned(path)?;
+        self.check_scene(&scene)?;
+        self.clear()?;
+
+        let map: HashMap<Entity, Entity> = scene.entity_manager.iter()
+            .map(|e| (e, e))
+            .collect();
+        self.component_manager.merge(&mut scene.component_manager, &map)?;
+        self.entity_manager = scene.entity_manager;
+
+        for e in self.entity_manager.iter() {
+            _ = self.events.send(EntityEvent::Created(e));
+        }
+
+        self.resources.replace_saved(scene.resources);
+        self.prefab_instances = scene.prefab_instances;
+
+        Ok(())
+    }
+
+    // Adds the entities saved at `path` next to the existing ones. Every entity gets a
+    // fresh id and references to it, like hierarchy parents and children, are renamed.
+    // Resources of the scene are ignored. Returns <scene entity, new entity>.
+    pub fn merge_scene(&mut self, path: &str) -> Result<HashMap<Entity, Entity>, Error> {
+        let mut scene: ECS = serialize::read_versioned(path)?;
+        self.check_scene(&scene)?;
+
+        let map: HashMap<Entity, Entity> = scene.entity_manager.iter()
+            .map(|e| (e, self.entity_manager.create()))
+            .collect();
+
+        for (old, new) in map.iter() {
+            for hash in scene.entity_manager.get_attached(*old).into_iter().flatten() {
+                self.entity_manager.attach_component(*new, *hash);
+            }
+            _ = self.events.send(EntityEvent::Created(*new));
+        }
+
+        self.component_manager.merge(&mut scene.component_manager, &map)?;
+
+        // Instances with entities that no longer existed when the scene was saved are dropped
+        for mut instance in scene.prefab_instances {
+            if instance.entities.iter().all(|e| map.contains_key(e)) {
+                instance.entities.map_entities(&map);
+                self.prefab_instances.push(instance);
+            }
+        }
+
+        Ok(map)
+    }
+
+    // Removes every entity, component types, systems and resources are kept
+    pub fn clear(&mut self) -> Result<(), Error> {
+        let roots: Vec<Entity> = self.entity_manager.iter()
+            .filter(|e| self.parent_of(*e).is_none())
+            .collect();
+
+        for e in roots {
+            self.remove_entity_recursive(e)?;
+        }
+
+        Ok(())
+    }
+
+    // Captures `root` and everything below it, parents before children
+    pub fn create_prefab(&self, root: Entity) -> Result<Prefab, Error> {
+        self.validate_entity(root)?;
+
+        let hc = self.component_manager.get::<hierarchy_component::HierarchyComponent>();
+        let subtree: Vec<Entity> = match hc.and_then(|hc| Some((hc, hc.component.find_index(&root)?))) {
+            Some((hc, index)) => hc.iter_breadth_first(index).collect(),
+            None => vec![root]
+        };
+        let positions: HashMap<Entity, usize> = subtree.iter()
+            .enumerate()
+            .map(|(i, e)| (*e, i))
+            .collect();
+
+        let mut prefab = Prefab::new();
+        for e in subtree.iter() {
+            let parent = match hc {
+                Some(hc) if *e != root => hc.component.find_index(e)
+                    .and_then(|i| hc.get_parent(i))
+                    .and_then(|p| positions.get(&p).copied()),
+                _ => None
+            };
+
+            let mut components = BTreeMap::new();
+            for hash in self.entity_manager.get_attached(*e).into_iter().flatten() {
+                let row = self.component_manager.get_by_hash(*hash)
+                    .and_then(|c| c.save_row(c.component().find_index(e)?));
+
+                match row {
+                    Some(val) => components.insert(*hash, val),
+                    None => {
+                        return Err(Error::ComponentNotAttached { entity: *e, component: *hash })
+                    }
+                };
+            }
+
+            prefab.entities.push(PrefabEntity { parent, components });
+        }
+
+        Ok(prefab)
+    }
+
+    pub fn save_prefab(&self, root: Entity, path: &str) -> Result<(), Error> {
+        self.create_prefab(root)?.write(path)
+    }
+
+    // Spawns a copy of `prefab` and returns its root.
+    // Nothing is left behind if any entity fails to spawn.
+    pub fn spawn_prefab(&mut self, prefab: &Prefab, overrides: &Overrides) -> Result<Entity, Error> {
+        let spawned = self.spawn_prefab_entities(prefab, overrides)?;
+        Ok(spawned[0])
+    }
+
+    pub fn instantiate_prefab(&mut self, path: &str) -> Result<Entity, Error> {
+        self.instantiate_prefab_with(path, Overrides::new())
+    }
+
+    // Instances remember their overrides, `reload_prefab` applies them again
+    pub fn instantiate_prefab_with(&mut self, path: &str, overrides: Overrides) -> Result<Entity, Error> {
+        let prefab = Prefab::read(path)?;
+        let entities = self.spawn_prefab_entities(&prefab, &overrides)?;
+        let root = entities[0];
+
+        self.prefab_instances.push(PrefabInstance {
+            path: String::from(path),
+            entities,
+            overrides,
+        });
+
+        Ok(root)
+    }
+
+    // Roots of the live instances of the prefab at `path`
+    pub fn prefab_instances(&self, path: &str) -> Vec<Entity> {
+        self.prefab_instances.iter()
+            .filter(|i| i.path == path && self.entity_manager.does_exist(i.root()))
+            .map(|i| i.root())
+            .collect()
+    }
+
+    // Reads the prefab at `path` again and writes its rows into every live instance,
+    // overrides still win. Components added to the prefab are attached, removed ones are kept.
+    // Returns how many instances were updated.
+    pub fn reload_prefab(&mut self, path: &str) -> Result<usize, Error> {
+        let prefab = Prefab::read(path)?;
+
+        let entity_manager = &self.entity_manager;
+        self.prefab_instances.retain(|i| entity_manager.does_exist(i.root()));
+
+        let targets: Vec<(Vec<Entity>, Overrides)> = self.prefab_instances.iter()
+            .filter(|i| i.path == path)
+            .map(|i| (i.entities.clone(), i.overrides.clone()))
+            .collect();
+
+        if targets.iter().any(|(entities, _)| entities.len() != prefab.len()) {
+            return Err(Error::InvalidPrefab("prefab entities changed, instances must be spawned again"))
+        }
+
+        for (entities, overrides) in targets.iter() {
+            for (index, (e, pe)) in entities.iter().zip(prefab.entities.iter()).enumerate() {
+                // Entities removed from the instance stay removed
+                if self.entity_manager.does_exist(*e) {
+                    self.load_prefab_rows(*e, index, pe, overrides)?;
+                }
+            }
+        }
+
+        Ok(targets.len())
+    }
+
+    fn spawn_prefab_entities(&mut self, prefab: &Prefab, overrides: &Overrides) -> Result<Vec<Entity>, Error> {
+        if prefab.is_empty() {
+            return Err(Error::InvalidPrefab("prefab is empty"))
+        }
+
+        let mut spawned = Vec::new();
+        for (index, pe) in prefab.entities.iter().enumerate() {
+            let result = self.create_entity()
+                .and_then(|e| {
+                    spawned.push(e);
+                    self.load_prefab_entity(e, index, pe, &spawned, overrides)
+                });
+
+            if let Err(e) = result {
+                for entity in spawned.iter().rev() {
+                    _ = self.remove_entity(*entity);
+                }
+                return Err(e)
+            }
+        }
+
+        Ok(spawned)
+    }
+
+    fn load_prefab_entity(
+        &mut self,
+        e: Entity,
+        index: usize,
+        pe: &PrefabEntity,
+        spawned: &[Entity],
+        overrides: &Overrides
+    ) -> Result<(), Error> {
+
+        if let Some(p) = pe.parent {
+            match spawned.get(p) {
+                Some(parent) if p < index => self.reparent_subtree(e, Some(*parent))?,
+                _ => {
+                    return Err(Error::InvalidPrefab("parent must come before its children"))
+                }
+            }
+        }
+
+        // Built-in components the prefab entity didn't have
+        let extra: Vec<u64> = self.entity_manager.get_attached(e)
+            .into_iter()
+            .flatten()
+            .filter(|h| !pe.components.contains_key(h))
+            .copied()
+            .collect();
+        for hash in extra {
+            self.detach_component_by_hash(e, hash)?;
+        }
+
+        self.load_prefab_rows(e, index, pe, overrides)
+    }
+
+    fn load_prefab_rows(
+        &mut self,
+        e: Entity,
+        index: usize,
+        pe: &PrefabEntity,
+        overrides: &Overrides
+    ) -> Result<(), Error> {
+
+        for (hash, row) in pe.components.iter() {
+            if !self.entity_manager.has_component(e, *hash) {
+                self.attach_component_by_hash(e, *hash)?;
+            }
+
+            let row = overrides.apply(index, *hash, row);
+            let c = match self.component_manager.get_by_hash_mut(*hash) {
+                Some(val) => val,
+                None => {
+                    return Err(Error::ComponentNotRegistered(*hash))
+                }
+            };
+
+            match c.component().find_index(&e) {
+                Some(i) => c.load_row(i, &row)?,
+                None => {
+                    return Err(Error::ComponentNotAttached { entity: e, component: *hash })
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Brings every dirty world matrix up to date with its hierarchy
+    pub fn propagate_transforms(&mut self) {
+        let requests = [
+            Request { hash: ComponentManager::type_hash::<transform_component::TransformComponent>(), mutable: true },
+            Request { hash: ComponentManager::type_hash::<hierarchy_component::HierarchyComponent>(), mutable: false },
+        ];
+
+        let mut borrowed = match self.component_manager.borrow_many(&requests) {
+            Ok(val) => val.into_iter(),
+            Err(_) => return
+        };
+
+        let tc = borrowed.next().flatten().and_then(|b| b.into_mut::<transform_component::TransformComponent>());
+        let hc = borrowed.next().flatten().and_then(|b| b.into_ref::<hierarchy_component::HierarchyComponent>());
+
+        if let (Some(tc), Some(hc)) = (tc, hc) {
+            tc.propagate(hc);
+        }
+    }
+
+    // Last propagated world matrix of `e`, see `propagate_transforms()`
+    pub fn world_matrix(&self, e: Entity) -> Option<Matrix4<f32>> {
+        let tc = self.component_manager.get::<transform_component::TransformComponent>()?;
+        let index = tc.component.find_index(&e)?;
+
+        return tc.get_world_matrix(index)
+    }
+
     pub fn get_attached(&self, entity: Entity) -> Option<&HashSet<u64>> {
         self.entity_manager.get_attached(entity)
     }
@@ -133,4 +851,106 @@ impl ECS {
     pub fn count(&self) -> usize {
         self.entity_manager.count()
     }
-}
\ No newline at end of file
+
+    fn resolve(spawned: &[Option<Entity>], target: Target) -> Result<Entity, Error> {
+        match target {
+            Target::Entity(e) => Ok(e),
+            Target::Spawned(i) => match spawned.get(i) {
+                Some(Some(e)) => Ok(*e),
+                _ => Err(Error::SpawnedEntityMissing(i))
+            }
+        }
+    }
+
+    fn parent_of(&self, e: Entity) -> Option<Entity> {
+        let hc = self.component_manager.get::<hierarchy_component::HierarchyComponent>()?;
+        hc.get_parent(hc.component.find_index(&e)?)
+    }
+
+    // Everything merging the scene could fail on, checked before the world is touched
+    fn check_scene(&self, scene: &ECS) -> Result<(), Error> {
+        for c in scene.component_manager.iter() {
+            match self.component_manager.get_by_hash(c.get_hash()) {
+                Some(own) if own.get_id() == c.get_id() => {},
+                Some(_) => return Err(Error::ComponentMismatch(c.get_hash())),
+                None => return Err(Error::ComponentNotRegistered(c.get_hash()))
+            }
+
+            if let Some(e) = c.component().entities.keys().find(|e| !scene.entity_manager.does_exist(**e)) {
+                return Err(Error::EntityNotMapped(*e))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_entity(&self, e: Entity) -> Result<(), Error> {
+        if !self.entity_manager.does_exist(e) {
+            return Err(Error::EntityNotFound(e))
+        }
+
+        Ok(())
+    }
+}
+
+// Scenes written by `save_scene`
+impl Versioned for ECS {
+    fn migrations() -> Migrations {
+        Migrations::new("ECS")
+            .add(migration::unversioned)
+            .add(stable_component_ids)
+    }
+}
+
+// Component hashes used to come from the Rust type name, they're now the FNV-1a of the
+// component's id. Saved components carry their id as the type tag, every hash is renamed through it.
+fn stable_component_ids(value: &mut Value) -> Result<(), Error> {
+    match rename_component_hashes(value) {
+        Some(()) => Ok(()),
+        None => Err(Error::InvalidData(String::from("scene component hashes can't be renamed")))
+    }
+}
+
+fn rename_component_hashes(value: &mut Value) -> Option<()> {
+    let mut renamed = HashMap::new();
+    let mut indices = serde_json::Map::new();
+    for (key, index) in value.pointer("/component_manager/indices")?.as_object()? {
+        let tag = value["component_manager"]["components"][index.as_u64()? as usize]["type"].as_str()?;
+        let new = hash::fnv1a(tag.as_bytes());
+        renamed.insert(key.parse::<u64>().ok()?, new);
+        indices.insert(new.to_string(), index.clone());
+    }
+    let rename = |hash: &Value| hash.as_u64().and_then(|h| renamed.get(&h)).map(|h| Value::from(*h));
+
+    *value.pointer_mut("/component_manager/indices")? = Value::Object(indices);
+
+    // [entity, [hash, ...]]
+    for pair in value.pointer_mut("/entity_manager/entities")?.as_array_mut()? {
+        let hashes = pair.get_mut(1)?.as_array_mut()?;
+        *hashes = hashes.iter().map(rename).collect::<Option<_>>()?;
+    }
+
+    // Signatures stay sorted, the columns follow their hash
+    if let Some(list) = value.pointer_mut("/component_manager/archetypes") {
+        for a in list.as_array_mut()? {
+            let mut pairs = a["signature"].as_array()?.iter()
+                .zip(a["columns"].as_array()?)
+                .map(|(h, c)| Some((rename(h)?.as_u64()?, c.clone())))
+                .collect::<Option<Vec<_>>>()?;
+            pairs.sort_by_key(|p| p.0);
+            a["signature"] = pairs.iter().map(|p| Value::from(p.0)).collect();
+            a["columns"] = pairs.into_iter().map(|p| p.1).collect();
+        }
+    }
+
+    // [prefab entity, hash, column, value]
+    if let Some(list) = value.pointer_mut("/prefab_instances") {
+        for instance in list.as_array_mut()? {
+            for o in instance.pointer_mut("/overrides/list")?.as_array_mut()? {
+                o[1] = rename(&o[1])?;
+            }
+        }
+    }
+
+    Some(())
+}
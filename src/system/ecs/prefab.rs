@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use super::entity::Entity;
+use super::component_manager::{ComponentManager, component::Componentable};
+use super::component_manager::component::{
+    name_component::NameComponent,
+    hierarchy_component::HierarchyComponent,
+    transform_component::TransformComponent};
+use crate::error::Error;
+use crate::util::serialize::{self, Format};
+use crate::util::migration::{self, Migrations, Versioned};
+
+// A single entity of a prefab with the rows of every component it has
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PrefabEntity {
+    pub parent: Option<usize>, // index into `Prefab::entities`, always before this entity
+    pub components: BTreeMap<u64, Value>, // <component hash, row>
+}
+
+// A saved entity subtree. The first entity is the root.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Prefab {
+    pub entities: Vec<PrefabEntity>,
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(path: &str) -> Result<Self, Error> {
+        serialize::read_versioned(path)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Error> {
+        serialize::write_versioned(path, self, Format::Json)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+impl Versioned for Prefab {
+    fn migrations() -> Migrations {
+        Migrations::new("Prefab")
+            .add(migration::unversioned)
+            .add(stable_component_ids)
+    }
+}
+
+// Prefab rows don't name their component, so only the built in components, whose old
+// type name hash this build can still compute, are renamed, see `ECS::migrations`
+fn stable_component_ids(value: &mut Value) -> Result<(), Error> {
+    let renamed: HashMap<String, String> = HashMap::from([
+        legacy_hash::<NameComponent>(),
+        legacy_hash::<HierarchyComponent>(),
+        legacy_hash::<TransformComponent>(),
+    ]);
+
+    let entities = match value.get_mut("entities").and_then(Value::as_array_mut) {
+        Some(val) => val,
+        None => return Err(Error::InvalidData(String::from("prefab has no entities")))
+    };
+
+    for e in entities {
+        let components = match e.get_mut("components").and_then(Value::as_object_mut) {
+            Some(val) => val,
+            None => return Err(Error::InvalidData(String::from("prefab entity has no components")))
+        };
+
+        for (key, row) in std::mem::take(components) {
+            match renamed.get(&key) {
+                Some(val) => components.insert(val.clone(), row),
+                None => return Err(Error::InvalidData(format!("unknown component {key}, the prefab has to be saved again")))
+            };
+        }
+    }
+
+    Ok(())
+}
+
+fn legacy_hash<T: Componentable>() -> (String, String) {
+    let old = crate::util::hash::get(&String::from(std::any::type_name::<T>()));
+    (old.to_string(), ComponentManager::type_hash::<T>().to_string())
+}
+
+// Column values of a single instance written over the prefab's own.
+// Only components the prefab entity has are affected.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Overrides {
+    list: Vec<(usize, u64, String, Value)>, // <prefab entity, component hash, column, value>
+}
+
+impl Overrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replaces an earlier override of the same column
+    pub fn set<T: Componentable + 'static>(
+        &mut self,
+        index: usize,
+        column: &str,
+        value: impl Serialize
+    ) -> Result<(), Error> {
+
+        let value = match serde_json::to_value(value) {
+            Ok(val) => val,
+            Err(e) => return Err(Error::Serialize(e.to_string()))
+        };
+
+        let hash = ComponentManager::type_hash::<T>();
+        self.list.retain(|(i, h, c, _)| !(*i == index && *h == hash && c == column));
+        self.list.push((index, hash, String::from(column), value));
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn apply(&self, index: usize, hash: u64, row: &Value) -> Value {
+        let mut row = row.clone();
+        if let Value::Object(columns) = &mut row {
+            for (_, _, column, value) in self.list.iter().filter(|(i, h, _, _)| *i == index && *h == hash) {
+                columns.insert(column.clone(), value.clone());
+            }
+        }
+
+        row
+    }
+}
+
+// Entities spawned from the prefab at `path`, kept so edits to the file reach them
+#[derive(Serialize, Deserialize)]
+pub struct PrefabInstance {
+    pub path: String,
+    pub entities: Vec<Entity>, // <prefab entity index, spawned entity>
+    pub overrides: Overrides,
+}
+
+impl PrefabInstance {
+    pub fn root(&self) -> Entity {
+        self.entities[0]
+    }
+}
@@ -0,0 +1,714 @@
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use super::{entity::Entity, entity_manager::EntityManager};
+use super::component_manager::ComponentManager;
+use super::component_manager::signature_index::SignatureIndex;
+use super::component_manager::component::{Component, Componentable};
+use crate::error::Error;
+
+// A component borrowed out of a store for the lifetime of a query
+pub enum Borrowed<'w> {
+    Ref(&'w dyn Componentable),
+    Mut(&'w mut dyn Componentable),
+}
+
+impl<'w> Borrowed<'w> {
+    pub fn into_ref<T: Componentable + 'static>(self) -> Option<&'w T> {
+        let c: &'w dyn Componentable = match self {
+            Borrowed::Ref(c) => c,
+            Borrowed::Mut(c) => c,
+        };
+
+        return c.as_any().downcast_ref::<T>()
+    }
+
+    pub fn reborrow(&mut self) -> Borrowed<'_> {
+        match self {
+            Borrowed::Ref(c) => Borrowed::Ref(*c),
+            Borrowed::Mut(c) => Borrowed::Mut(&mut **c),
+        }
+    }
+
+    pub fn into_mut<T: Componentable + 'static>(self) -> Option<&'w mut T> {
+        match self {
+            Borrowed::Ref(_) => return None,
+            Borrowed::Mut(c) => return c.as_any_mut().downcast_mut::<T>()
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Request {
+    pub hash: u64,
+    pub mutable: bool,
+}
+
+pub trait ComponentStore {
+    fn borrow_ref(&self, hash: u64) -> Option<&dyn Componentable>;
+    fn borrow_many(&mut self, requests: &[Request]) -> Result<Vec<Option<Borrowed<'_>>>, Error>;
+    // None when the store only has sparse rows
+    fn signature_index(&self) -> Option<&SignatureIndex>;
+}
+
+// Hands out disjoint borrows of `items` to each request.
+// A component may be shared by any number of readers or held by exactly one writer.
+pub fn distribute<'w>(
+    items: impl Iterator<Item = (u64, Borrowed<'w>)>,
+    requests: &[Request]
+) -> Result<Vec<Option<Borrowed<'w>>>, Error> {
+
+    let mut list: Vec<Option<Borrowed<'w>>> = requests.iter().map(|_| None).collect();
+
+    for (hash, borrowed) in items {
+        let slots: Vec<usize> = requests.iter()
+            .enumerate()
+            .filter(|(_, r)| r.hash == hash)
+            .map(|(i, _)| i)
+            .collect();
+
+        if slots.is_empty() {
+            continue;
+        }
+
+        let wants_mut = slots.iter().any(|i| requests[*i].mutable);
+        if wants_mut && slots.len() > 1 {
+            return Err(Error::AccessConflict(hash))
+        }
+
+        match borrowed {
+            Borrowed::Mut(c) if wants_mut => list[slots[0]] = Some(Borrowed::Mut(c)),
+            Borrowed::Ref(_) if wants_mut => {
+                return Err(Error::AccessNotDeclared(hash))
+            },
+            Borrowed::Ref(c) => {
+                for i in slots {
+                    list[i] = Some(Borrowed::Ref(c));
+                }
+            },
+            Borrowed::Mut(c) => {
+                let c: &'w dyn Componentable = c;
+                for i in slots {
+                    list[i] = Some(Borrowed::Ref(c));
+                }
+            },
+        }
+    }
+
+    Ok(list)
+}
+
+impl ComponentStore for ComponentManager {
+    fn borrow_ref(&self, hash: u64) -> Option<&dyn Componentable> {
+        self.get_by_hash(hash)
+    }
+
+    fn borrow_many(&mut self, requests: &[Request]) -> Result<Vec<Option<Borrowed<'_>>>, Error> {
+        let items = self.iter_mut().map(|c| (c.get_hash(), Borrowed::Mut(c.as_mut())));
+        distribute(items, requests)
+    }
+
+    fn signature_index(&self) -> Option<&SignatureIndex> {
+        ComponentManager::signature_index(self)
+    }
+}
+
+pub struct Row<'a, T> {
+    pub entity: Entity,
+    pub index: usize,
+    component: &'a T,
+}
+
+impl<'a, T> Row<'a, T> {
+    pub fn new(entity: Entity, index: usize, component: &'a T) -> Self {
+        Self { entity, index, component }
+    }
+
+    pub fn get(&self) -> &'a T {
+        self.component
+    }
+}
+
+pub struct RowMut<'a, T> {
+    pub entity: Entity,
+    pub index: usize,
+    component: &'a mut T,
+}
+
+impl<'a, T: Componentable> RowMut<'a, T> {
+    pub fn new(entity: Entity, index: usize, component: &'a mut T) -> Self {
+        Self { entity, index, component }
+    }
+
+    pub fn get(&self) -> &T {
+        self.component
+    }
+
+    // Mutable access counts as a change to this row
+    pub fn get_mut(&mut self) -> &mut T {
+        self.component.component_mut().mark_changed(self.entity);
+        self.component
+    }
+}
+
+// A single element of a query, e.g. `&T`, `&mut T` or `Option<&T>`
+pub trait Fetch {
+    type State<'w>;
+    type Item<'r>;
+
+    fn request() -> Request;
+    // Hash every matching entity must have, None for optional elements
+    fn required() -> Option<u64>;
+    // Ok(None) means a required component isn't registered, so nothing can match
+    fn init(borrowed: Option<Borrowed<'_>>) -> Result<Option<Self::State<'_>>, Error>;
+    // Component used to drive iteration, None for optional elements
+    fn component<'a>(state: &'a Self::State<'_>) -> Option<&'a Component>;
+    fn fetch<'r>(state: &'r mut Self::State<'_>, e: Entity) -> Option<Self::Item<'r>>;
+    // Same as `fetch` with the row already known, None means `e` has no row
+    fn fetch_at<'r>(state: &'r mut Self::State<'_>, e: Entity, index: Option<usize>) -> Option<Self::Item<'r>>;
+}
+
+pub trait ReadOnlyFetch: Fetch {
+    fn fetch_ref<'w>(state: &Self::State<'w>, e: Entity) -> Option<Self::Item<'w>>;
+    fn fetch_ref_at<'w>(state: &Self::State<'w>, e: Entity, index: Option<usize>) -> Option<Self::Item<'w>>;
+}
+
+impl<T: Componentable + 'static> Fetch for &T {
+    type State<'w> = &'w T;
+    type Item<'r> = Row<'r, T>;
+
+    fn request() -> Request {
+        Request { hash: ComponentManager::type_hash::<T>(), mutable: false }
+    }
+
+    fn required() -> Option<u64> {
+        Some(ComponentManager::type_hash::<T>())
+    }
+
+    fn init(borrowed: Option<Borrowed<'_>>) -> Result<Option<Self::State<'_>>, Error> {
+        match borrowed {
+            Some(b) => Ok(b.into_ref::<T>()),
+            None => Ok(None)
+        }
+    }
+
+    fn component<'a>(state: &'a Self::State<'_>) -> Option<&'a Component> {
+        Some(state.component())
+    }
+
+    fn fetch<'r>(state: &'r mut Self::State<'_>, e: Entity) -> Option<Self::Item<'r>> {
+        let c: &'r T = state;
+        let index = c.component().find_index(&e)?;
+        Some(Row::new(e, index, c))
+    }
+
+    fn fetch_at<'r>(state: &'r mut Self::State<'_>, e: Entity, index: Option<usize>) -> Option<Self::Item<'r>> {
+        let c: &'r T = state;
+        Some(Row::new(e, index?, c))
+    }
+}
+
+impl<T: Componentable + 'static> ReadOnlyFetch for &T {
+    fn fetch_ref<'w>(state: &Self::State<'w>, e: Entity) -> Option<Self::Item<'w>> {
+        let c: &'w T = state;
+        let index = c.component().find_index(&e)?;
+        Some(Row::new(e, index, c))
+    }
+
+    fn fetch_ref_at<'w>(state: &Self::State<'w>, e: Entity, index: Option<usize>) -> Option<Self::Item<'w>> {
+        let c: &'w T = state;
+        Some(Row::new(e, index?, c))
+    }
+}
+
+impl<T: Componentable + 'static> Fetch for &mut T {
+    type State<'w> = &'w mut T;
+    type Item<'r> = RowMut<'r, T>;
+
+    fn request() -> Request {
+        Request { hash: ComponentManager::type_hash::<T>(), mutable: true }
+    }
+
+    fn required() -> Option<u64> {
+        Some(ComponentManager::type_hash::<T>())
+    }
+
+    fn init(borrowed: Option<Borrowed<'_>>) -> Result<Option<Self::State<'_>>, Error> {
+        match borrowed {
+            Some(b) => match b.into_mut::<T>() {
+                Some(c) => Ok(Some(c)),
+                None => Err(Error::AccessNotDeclared(ComponentManager::type_hash::<T>()))
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn component<'a>(state: &'a Self::State<'_>) -> Option<&'a Component> {
+        Some(state.component())
+    }
+
+    fn fetch<'r>(state: &'r mut Self::State<'_>, e: Entity) -> Option<Self::Item<'r>> {
+        let index = state.component().find_index(&e)?;
+        Some(RowMut::new(e, index, &mut **state))
+    }
+
+    fn fetch_at<'r>(state: &'r mut Self::State<'_>, e: Entity, index: Option<usize>) -> Option<Self::Item<'r>> {
+        Some(RowMut::new(e, index?, &mut **state))
+    }
+}
+
+impl<F: Fetch> Fetch for Option<F> {
+    type State<'w> = Option<F::State<'w>>;
+    type Item<'r> = Option<F::Item<'r>>;
+
+    fn request() -> Request {
+        F::request()
+    }
+
+    fn required() -> Option<u64> {
+        None
+    }
+
+    fn init(borrowed: Option<Borrowed<'_>>) -> Result<Option<Self::State<'_>>, Error> {
+        Ok(Some(F::init(borrowed)?))
+    }
+
+    fn component<'a>(_state: &'a Self::State<'_>) -> Option<&'a Component> {
+        None
+    }
+
+    fn fetch<'r>(state: &'r mut Self::State<'_>, e: Entity) -> Option<Self::Item<'r>> {
+        match state {
+            Some(s) => Some(F::fetch(s, e)),
+            None => Some(None)
+        }
+    }
+
+    fn fetch_at<'r>(state: &'r mut Self::State<'_>, e: Entity, index: Option<usize>) -> Option<Self::Item<'r>> {
+        match state {
+            Some(s) => Some(F::fetch_at(s, e, index)),
+            None => Some(None)
+        }
+    }
+}
+
+impl<F: ReadOnlyFetch> ReadOnlyFetch for Option<F> {
+    fn fetch_ref<'w>(state: &Self::State<'w>, e: Entity) -> Option<Self::Item<'w>> {
+        match state {
+            Some(s) => Some(F::fetch_ref(s, e)),
+            None => Some(None)
+        }
+    }
+
+    fn fetch_ref_at<'w>(state: &Self::State<'w>, e: Entity, index: Option<usize>) -> Option<Self::Item<'w>> {
+        match state {
+            Some(s) => Some(F::fetch_ref_at(s, e, index)),
+            None => Some(None)
+        }
+    }
+}
+
+// The full element list of a query, implemented for tuples of `Fetch`
+pub trait QueryData {
+    type State<'w>;
+    type Item<'r>;
+
+    fn requests() -> Vec<Request>;
+    fn required() -> Vec<u64>;
+    fn init<'w>(
+        borrowed: &mut std::vec::IntoIter<Option<Borrowed<'w>>>
+    ) -> Result<Option<Self::State<'w>>, Error>;
+    fn driver<'a>(state: &'a Self::State<'_>) -> Option<&'a Component>;
+    fn fetch<'r>(state: &'r mut Self::State<'_>, e: Entity) -> Option<Self::Item<'r>>;
+    // `rows` holds one row per element, in `requests()` order
+    fn fetch_at<'r>(state: &'r mut Self::State<'_>, e: Entity, rows: &[Option<usize>]) -> Option<Self::Item<'r>>;
+}
+
+pub trait ReadOnlyQueryData: QueryData {
+    fn fetch_ref<'w>(state: &Self::State<'w>, e: Entity) -> Option<Self::Item<'w>>;
+    fn fetch_ref_at<'w>(state: &Self::State<'w>, e: Entity, rows: &[Option<usize>]) -> Option<Self::Item<'w>>;
+}
+
+macro_rules! impl_query_data {
+    ($($f:ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($f: Fetch),+> QueryData for ($($f,)+) {
+            type State<'w> = ($($f::State<'w>,)+);
+            type Item<'r> = ($($f::Item<'r>,)+);
+
+            fn requests() -> Vec<Request> {
+                vec![$($f::request()),+]
+            }
+
+            fn required() -> Vec<u64> {
+                [$($f::required()),+].into_iter().flatten().collect()
+            }
+
+            fn init<'w>(
+                borrowed: &mut std::vec::IntoIter<Option<Borrowed<'w>>>
+            ) -> Result<Option<Self::State<'w>>, Error> {
+                $(
+                    let $f = $f::init(borrowed.next().flatten())?;
+                )+
+                let state = (|| Some(($($f?,)+)))();
+                Ok(state)
+            }
+
+            fn driver<'a>(state: &'a Self::State<'_>) -> Option<&'a Component> {
+                let ($($f,)+) = state;
+                let mut driver: Option<&'a Component> = None;
+                $(
+                    if let Some(c) = $f::component($f) {
+                        match driver {
+                            Some(d) if d.entities.len() <= c.entities.len() => {},
+                            _ => driver = Some(c),
+                        }
+                    }
+                )+
+                driver
+            }
+
+            fn fetch<'r>(state: &'r mut Self::State<'_>, e: Entity) -> Option<Self::Item<'r>> {
+                let ($($f,)+) = state;
+                Some(($($f::fetch($f, e)?,)+))
+            }
+
+            fn fetch_at<'r>(state: &'r mut Self::State<'_>, e: Entity, rows: &[Option<usize>]) -> Option<Self::Item<'r>> {
+                let ($($f,)+) = state;
+                let mut rows = rows.iter().copied();
+                Some(($($f::fetch_at($f, e, rows.next().flatten())?,)+))
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($f: ReadOnlyFetch),+> ReadOnlyQueryData for ($($f,)+) {
+            fn fetch_ref<'w>(state: &Self::State<'w>, e: Entity) -> Option<Self::Item<'w>> {
+                let ($($f,)+) = state;
+                Some(($($f::fetch_ref($f, e)?,)+))
+            }
+
+            fn fetch_ref_at<'w>(state: &Self::State<'w>, e: Entity, rows: &[Option<usize>]) -> Option<Self::Item<'w>> {
+                let ($($f,)+) = state;
+                let mut rows = rows.iter().copied();
+                Some(($($f::fetch_ref_at($f, e, rows.next().flatten())?,)+))
+            }
+        }
+    };
+}
+
+impl_query_data!(F0);
+impl_query_data!(F0, F1);
+impl_query_data!(F0, F1, F2);
+impl_query_data!(F0, F1, F2, F3);
+impl_query_data!(F0, F1, F2, F3, F4);
+impl_query_data!(F0, F1, F2, F3, F4, F5);
+
+// Filters are evaluated against state captured when the query is created,
+// `since` is the tick change filters compare against
+pub trait QueryFilter {
+    type State;
+
+    fn init(store: &dyn ComponentStore, since: u64) -> Self::State;
+    fn matches(state: &Self::State, entity_manager: &EntityManager, e: Entity) -> bool;
+}
+
+pub struct With<T>(PhantomData<T>);
+pub struct Without<T>(PhantomData<T>);
+pub struct Added<T>(PhantomData<T>);
+pub struct Changed<T>(PhantomData<T>);
+
+impl QueryFilter for () {
+    type State = ();
+
+    fn init(_store: &dyn ComponentStore, _since: u64) -> Self::State {}
+
+    fn matches(_state: &Self::State, _entity_manager: &EntityManager, _e: Entity) -> bool {
+        true
+    }
+}
+
+impl<T: Componentable + 'static> QueryFilter for With<T> {
+    type State = ();
+
+    fn init(_store: &dyn ComponentStore, _since: u64) -> Self::State {}
+
+    fn matches(_state: &Self::State, entity_manager: &EntityManager, e: Entity) -> bool {
+        entity_manager.has_component(e, ComponentManager::type_hash::<T>())
+    }
+}
+
+impl<T: Componentable + 'static> QueryFilter for Without<T> {
+    type State = ();
+
+    fn init(_store: &dyn ComponentStore, _since: u64) -> Self::State {}
+
+    fn matches(_state: &Self::State, entity_manager: &EntityManager, e: Entity) -> bool {
+        !entity_manager.has_component(e, ComponentManager::type_hash::<T>())
+    }
+}
+
+// Rows of `T` attached after `since`
+impl<T: Componentable + 'static> QueryFilter for Added<T> {
+    type State = HashSet<Entity>;
+
+    fn init(store: &dyn ComponentStore, since: u64) -> Self::State {
+        match store.borrow_ref(ComponentManager::type_hash::<T>()) {
+            Some(c) => {
+                let component = c.component();
+                component.entities.keys()
+                    .filter(|e| component.is_added_since(e, since))
+                    .copied()
+                    .collect()
+            },
+            None => HashSet::new()
+        }
+    }
+
+    fn matches(state: &Self::State, _entity_manager: &EntityManager, e: Entity) -> bool {
+        state.contains(&e)
+    }
+}
+
+// Rows of `T` attached or modified after `since`
+impl<T: Componentable + 'static> QueryFilter for Changed<T> {
+    type State = HashSet<Entity>;
+
+    fn init(store: &dyn ComponentStore, since: u64) -> Self::State {
+        match store.borrow_ref(ComponentManager::type_hash::<T>()) {
+            Some(c) => {
+                let component = c.component();
+                component.entities.keys()
+                    .filter(|e| component.is_changed_since(e, since))
+                    .copied()
+                    .collect()
+            },
+            None => HashSet::new()
+        }
+    }
+
+    fn matches(state: &Self::State, _entity_manager: &EntityManager, e: Entity) -> bool {
+        state.contains(&e)
+    }
+}
+
+macro_rules! impl_query_filter {
+    ($(($f:ident, $i:tt)),+) => {
+        impl<$($f: QueryFilter),+> QueryFilter for ($($f,)+) {
+            type State = ($($f::State,)+);
+
+            fn init(store: &dyn ComponentStore, since: u64) -> Self::State {
+                ($($f::init(store, since),)+)
+            }
+
+            fn matches(state: &Self::State, entity_manager: &EntityManager, e: Entity) -> bool {
+                $($f::matches(&state.$i, entity_manager, e))&&+
+            }
+        }
+    };
+}
+
+impl_query_filter!((F0, 0));
+impl_query_filter!((F0, 0), (F1, 1));
+impl_query_filter!((F0, 0), (F1, 1), (F2, 2));
+impl_query_filter!((F0, 0), (F1, 1), (F2, 2), (F3, 3));
+
+// Entities gathered from every matching group with their rows,
+// `rows` holds one row per query element for each entity
+struct Matched {
+    entities: Vec<Entity>,
+    rows: Vec<Option<usize>>,
+    stride: usize,
+}
+
+impl Matched {
+    // None for queries without a required element, those can't be answered by the index alone
+    fn collect<Q: QueryData>(signatures: &SignatureIndex) -> Option<Self> {
+        let required = Q::required();
+        if required.is_empty() {
+            return None
+        }
+
+        let requests = Q::requests();
+        let mut matched = Self {
+            entities: Vec::new(),
+            rows: Vec::new(),
+            stride: requests.len(),
+        };
+
+        for group in signatures.matching(&required) {
+            let columns: Vec<Option<&[usize]>> = requests.iter()
+                .map(|r| group.column(r.hash))
+                .collect();
+
+            for (slot, e) in group.entities().iter().enumerate() {
+                matched.entities.push(*e);
+                matched.rows.extend(columns.iter().map(|c| c.map(|c| c[slot])));
+            }
+        }
+
+        Some(matched)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Entity, &[Option<usize>])> {
+        self.entities.iter()
+            .copied()
+            .zip(self.rows.chunks(self.stride))
+    }
+}
+
+pub struct Query<'w, Q: QueryData, F: QueryFilter = ()> {
+    entity_manager: &'w EntityManager,
+    state: Option<Q::State<'w>>,
+    filter: F::State,
+    matched: Option<Matched>,
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Query<'w, Q, F> {
+    pub fn new(
+        entity_manager: &'w EntityManager,
+        store: &'w mut impl ComponentStore
+    ) -> Result<Self, Error> {
+
+        Query::new_since(entity_manager, store, 0)
+    }
+
+    // Change filters match rows added or modified after `since`
+    pub fn new_since(
+        entity_manager: &'w EntityManager,
+        store: &'w mut impl ComponentStore,
+        since: u64
+    ) -> Result<Self, Error> {
+
+        let filter = F::init(store, since);
+        let matched = store.signature_index().and_then(Matched::collect::<Q>);
+
+        let requests = Q::requests();
+        let borrowed = store.borrow_many(&requests)?;
+        let state = Q::init(&mut borrowed.into_iter())?;
+
+        Ok(Self { entity_manager, state, filter, matched })
+    }
+
+    // Entities matching the query, collected up front so rows can be borrowed mutably
+    pub fn entities(&self) -> Vec<Entity> {
+        let state = match &self.state {
+            Some(val) => val,
+            None => return Vec::new()
+        };
+
+        if let Some(matched) = &self.matched {
+            return matched.entities.iter()
+                .copied()
+                .filter(|e| F::matches(&self.filter, self.entity_manager, *e))
+                .collect()
+        }
+
+        match Q::driver(state) {
+            Some(driver) => {
+                driver.entities.keys()
+                    .copied()
+                    .filter(|e| F::matches(&self.filter, self.entity_manager, *e))
+                    .collect()
+            },
+            None => {
+                // Every element is optional, fall back to all entities
+                self.entity_manager.iter()
+                    .filter(|e| F::matches(&self.filter, self.entity_manager, *e))
+                    .collect()
+            }
+        }
+    }
+
+    pub fn get(&mut self, e: Entity) -> Option<Q::Item<'_>> {
+        if !self.entity_manager.does_exist(e) || !F::matches(&self.filter, self.entity_manager, e) {
+            return None
+        }
+
+        Q::fetch(self.state.as_mut()?, e)
+    }
+
+    pub fn for_each(&mut self, mut f: impl FnMut(Entity, Q::Item<'_>)) {
+        if let (Some(matched), Some(state)) = (&self.matched, &mut self.state) {
+            for (e, rows) in matched.iter() {
+                if !F::matches(&self.filter, self.entity_manager, e) {
+                    continue
+                }
+
+                if let Some(item) = Q::fetch_at(state, e, rows) {
+                    f(e, item);
+                }
+            }
+
+            return
+        }
+
+        let entities = self.entities();
+        let state = match &mut self.state {
+            Some(val) => val,
+            None => return
+        };
+
+        for e in entities {
+            if let Some(item) = Q::fetch(state, e) {
+                f(e, item);
+            }
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.entities().len()
+    }
+}
+
+impl<'w, Q: ReadOnlyQueryData, F: QueryFilter> Query<'w, Q, F> {
+    pub fn new_ref(
+        entity_manager: &'w EntityManager,
+        store: &'w impl ComponentStore
+    ) -> Result<Self, Error> {
+
+        Query::new_ref_since(entity_manager, store, 0)
+    }
+
+    pub fn new_ref_since(
+        entity_manager: &'w EntityManager,
+        store: &'w impl ComponentStore,
+        since: u64
+    ) -> Result<Self, Error> {
+
+        let filter = F::init(store, since);
+        let matched = store.signature_index().and_then(Matched::collect::<Q>);
+
+        let borrowed: Vec<Option<Borrowed<'w>>> = Q::requests().iter()
+            .map(|r| store.borrow_ref(r.hash).map(Borrowed::Ref))
+            .collect();
+        let state = Q::init(&mut borrowed.into_iter())?;
+
+        Ok(Self { entity_manager, state, filter, matched })
+    }
+
+    pub fn get_ref(&self, e: Entity) -> Option<Q::Item<'w>> {
+        if !self.entity_manager.does_exist(e) || !F::matches(&self.filter, self.entity_manager, e) {
+            return None
+        }
+
+        Q::fetch_ref(self.state.as_ref()?, e)
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Entity, Q::Item<'w>)> + '_> {
+        if let Some(matched) = &self.matched {
+            return Box::new(matched.iter()
+                .filter(move |(e, _)| F::matches(&self.filter, self.entity_manager, *e))
+                .filter_map(move |(e, rows)| {
+                    let state = self.state.as_ref()?;
+                    Some((e, Q::fetch_ref_at(state, e, rows)?))
+                }))
+        }
+
+        Box::new(self.entities()
+            .into_iter()
+            .filter_map(move |e| {
+                let state = self.state.as_ref()?;
+                Some((e, Q::fetch_ref(state, e)?))
+            }))
+    }
+}
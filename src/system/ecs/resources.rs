@@ -0,0 +1,204 @@
+use std::any::Any;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+use super::schedule::Access;
+use crate::util::hash;
+use crate::error::Error;
+
+// A resource that is saved with the ECS. Plain resources inserted with
+// `Resources::insert` are skipped when serializing.
+#[typetag::serde(tag = "type")]
+pub trait SavedResource: Send + Sync {
+    fn get_hash(&self) -> u64;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+pub enum Slot {
+    Plain(Box<dyn Any + Send + Sync>),
+    Saved(Box<dyn SavedResource>),
+}
+
+impl Slot {
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        match self {
+            Slot::Plain(r) => r.downcast_ref::<T>(),
+            Slot::Saved(r) => r.as_any().downcast_ref::<T>(),
+        }
+    }
+
+    pub fn downcast_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        match self {
+            Slot::Plain(r) => r.downcast_mut::<T>(),
+            Slot::Saved(r) => r.as_any_mut().downcast_mut::<T>(),
+        }
+    }
+}
+
+// Typed singletons stored alongside the entities, one per type
+#[derive(Default)]
+pub struct Resources {
+    slots: HashMap<u64, Slot>, // <type hash, resource>
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replaces any resource of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, resource: T) {
+        self.slots.insert(Resources::type_hash::<T>(), Slot::Plain(Box::new(resource)));
+    }
+
+    pub fn insert_saved<T: SavedResource + 'static>(&mut self, resource: T) {
+        self.slots.insert(Resources::type_hash::<T>(), Slot::Saved(Box::new(resource)));
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> bool {
+        self.slots.remove(&Resources::type_hash::<T>()).is_some()
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.slots.contains_key(&Resources::type_hash::<T>())
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.slots.get(&Resources::type_hash::<T>())?.downcast_ref::<T>()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.slots.get_mut(&Resources::type_hash::<T>())?.downcast_mut::<T>()
+    }
+
+    #[cfg(test)]
+    pub fn count(&self) -> usize {
+        self.slots.len()
+    }
+
+    // Swaps every saved resource for the ones in `other`, plain resources are kept
+    pub fn replace_saved(&mut self, other: Resources) {
+        self.slots.retain(|_, s| matches!(s, Slot::Plain(_)));
+        self.slots.extend(other.slots);
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (u64, &mut Slot)> {
+        self.slots.iter_mut().map(|(h, s)| (*h, s))
+    }
+
+    pub fn type_hash<T: 'static>() -> u64 {
+        hash::get(&String::from(std::any::type_name::<T>()))
+    }
+}
+
+impl Serialize for Resources {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.slots.values().filter_map(|s| match s {
+            Slot::Saved(r) => Some(r),
+            Slot::Plain(_) => None,
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for Resources {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let list: Vec<Box<dyn SavedResource>> = Vec::deserialize(deserializer)?;
+
+        Ok(Self {
+            slots: list.into_iter()
+                .map(|r| (r.get_hash(), Slot::Saved(r)))
+                .collect()
+        })
+    }
+}
+
+enum SlotBorrow<'w> {
+    Ref(&'w Slot),
+    Mut(&'w mut Slot),
+}
+
+// The resources a system declared access to
+pub struct ResourceView<'w> {
+    declared: Access,
+    list: Vec<(u64, SlotBorrow<'w>)>,
+}
+
+impl<'w> ResourceView<'w> {
+    pub fn new(resources: &'w mut Resources, access: &Access) -> Self {
+        let list = resources.iter_mut()
+            .filter_map(|(hash, slot)| {
+                if access.can_write_resource(hash) {
+                    Some((hash, SlotBorrow::Mut(slot)))
+                } else if access.can_read_resource(hash) {
+                    Some((hash, SlotBorrow::Ref(slot)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Self { declared: access.clone(), list }
+    }
+
+    // Same rules as `ComponentView::split`, applied to resources
+    pub fn split(resources: &'w mut Resources, accesses: &[&Access]) -> Result<Vec<Self>, Error> {
+        let mut views: Vec<Self> = accesses.iter()
+            .map(|a| Self { declared: (*a).clone(), list: Vec::new() })
+            .collect();
+
+        for (hash, slot) in resources.iter_mut() {
+            let writers: Vec<usize> = (0..accesses.len())
+                .filter(|i| accesses[*i].can_write_resource(hash))
+                .collect();
+            let readers: Vec<usize> = (0..accesses.len())
+                .filter(|i| accesses[*i].can_read_resource(hash))
+                .collect();
+
+            match writers.len() {
+                0 => {
+                    let slot: &'w Slot = slot;
+                    for i in readers {
+                        views[i].list.push((hash, SlotBorrow::Ref(slot)));
+                    }
+                },
+                1 if readers.len() == 1 => {
+                    views[writers[0]].list.push((hash, SlotBorrow::Mut(slot)));
+                },
+                _ => {
+                    return Err(Error::AccessConflict(hash))
+                }
+            }
+        }
+
+        Ok(views)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        let hash = Resources::type_hash::<T>();
+        if !self.declared.can_read_resource(hash) {
+            return None
+        }
+
+        self.list.iter()
+            .find(|(h, _)| *h == hash)
+            .and_then(|(_, b)| match b {
+                SlotBorrow::Ref(s) => s.downcast_ref::<T>(),
+                SlotBorrow::Mut(s) => s.downcast_ref::<T>(),
+            })
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        let hash = Resources::type_hash::<T>();
+        if !self.declared.can_write_resource(hash) {
+            return None
+        }
+
+        self.list.iter_mut()
+            .find(|(h, _)| *h == hash)
+            .and_then(|(_, b)| match b {
+                SlotBorrow::Ref(_) => None,
+                SlotBorrow::Mut(s) => s.downcast_mut::<T>(),
+            })
+    }
+}
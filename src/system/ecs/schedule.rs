@@ -0,0 +1,842 @@
+use std::collections::{HashMap, HashSet};
+
+use rayon::prelude::*;
+
+use super::entity::Entity;
+use super::entity_manager::EntityManager;
+use super::commands::Commands;
+use super::events::{EventRegistry, EventCursor};
+use super::resources::{Resources, ResourceView};
+use super::component_manager::{ComponentManager, component::Componentable, signature_index::SignatureIndex};
+use super::query::{Borrowed, ComponentStore, Request, Query, QueryData, ReadOnlyQueryData, QueryFilter, distribute};
+use crate::error::Error;
+
+#[derive(Clone, Copy, Debug)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    PreUpdate,
+    Update,
+    PostUpdate,
+    RenderPrep,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [
+        Stage::PreUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::RenderPrep,
+    ];
+}
+
+// Component and resource types a system reads and writes, by type hash
+#[derive(Clone, Default)]
+pub struct Access {
+    reads: HashSet<u64>,
+    writes: HashSet<u64>,
+    resource_reads: HashSet<u64>,
+    resource_writes: HashSet<u64>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self {
+            reads: HashSet::new(),
+            writes: HashSet::new(),
+            resource_reads: HashSet::new(),
+            resource_writes: HashSet::new(),
+        }
+    }
+
+    pub fn read<T: Componentable>(mut self) -> Self {
+        self.reads.insert(ComponentManager::type_hash::<T>());
+        self
+    }
+
+    pub fn write<T: Componentable>(mut self) -> Self {
+        self.writes.insert(ComponentManager::type_hash::<T>());
+        self
+    }
+
+    pub fn read_resource<T: 'static>(mut self) -> Self {
+        self.resource_reads.insert(Resources::type_hash::<T>());
+        self
+    }
+
+    pub fn write_resource<T: 'static>(mut self) -> Self {
+        self.resource_writes.insert(Resources::type_hash::<T>());
+        self
+    }
+
+    pub fn reads(&self) -> &HashSet<u64> {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &HashSet<u64> {
+        &self.writes
+    }
+
+    pub fn can_read(&self, hash: u64) -> bool {
+        self.reads.contains(&hash) || self.writes.contains(&hash)
+    }
+
+    pub fn can_write(&self, hash: u64) -> bool {
+        self.writes.contains(&hash)
+    }
+
+    // Hashes both accesses touch where at least one of them writes
+    pub fn conflicts(&self, other: &Access) -> Vec<u64> {
+        let mut list: Vec<u64> = self.writes.iter()
+            .filter(|h| other.can_read(**h))
+            .chain(other.writes.iter().filter(|h| self.reads.contains(h)))
+            .copied()
+            .collect();
+
+        list.sort();
+        list.dedup();
+        list
+    }
+
+    pub fn can_read_resource(&self, hash: u64) -> bool {
+        self.resource_reads.contains(&hash) || self.resource_writes.contains(&hash)
+    }
+
+    pub fn can_write_resource(&self, hash: u64) -> bool {
+        self.resource_writes.contains(&hash)
+    }
+
+    pub fn resource_conflicts(&self, other: &Access) -> Vec<u64> {
+        let mut list: Vec<u64> = self.resource_writes.iter()
+            .filter(|h| other.can_read_resource(**h))
+            .chain(other.resource_writes.iter().filter(|h| self.resource_reads.contains(h)))
+            .copied()
+            .collect();
+
+        list.sort();
+        list.dedup();
+        list
+    }
+}
+
+pub trait System: Send {
+    fn name(&self) -> &str;
+    fn access(&self) -> Access;
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error>;
+
+    // Names of systems that must run before this one
+    fn after(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    // Names of systems that must run after this one
+    fn before(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// Two systems in the same stage touching the same component or resource,
+// with nothing ordering one before the other
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub stage: Stage,
+    pub first: String,
+    pub second: String,
+    pub components: Vec<u64>,
+    pub resources: Vec<u64>,
+}
+
+// The components a system declared access to, borrowed out of the ComponentManager
+pub struct ComponentView<'w> {
+    declared: Access,
+    list: Vec<(u64, Borrowed<'w>)>,
+    signatures: Option<&'w SignatureIndex>,
+}
+
+impl<'w> ComponentView<'w> {
+    pub fn new(cm: &'w mut ComponentManager, access: &Access) -> Self {
+        let (components, signatures) = cm.iter_mut_with_signature_index();
+        let list = components
+            .filter_map(|c| {
+                let hash = c.get_hash();
+                if access.can_write(hash) {
+                    Some((hash, Borrowed::Mut(c.as_mut())))
+                } else if access.can_read(hash) {
+                    Some((hash, Borrowed::Ref(&**c)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Self { declared: access.clone(), list, signatures }
+    }
+
+    // Splits the ComponentManager into one view per access list.
+    // Accesses must not conflict with each other; a written component goes to its only writer
+    // and read components are shared by every reader.
+    pub fn split(cm: &'w mut ComponentManager, accesses: &[&Access]) -> Result<Vec<Self>, Error> {
+        let (components, signatures) = cm.iter_mut_with_signature_index();
+        let mut views: Vec<Self> = accesses.iter()
+            .map(|a| Self { declared: (*a).clone(), list: Vec::new(), signatures })
+            .collect();
+
+        for c in components {
+            let hash = c.get_hash();
+            let writers: Vec<usize> = (0..accesses.len())
+                .filter(|i| accesses[*i].can_write(hash))
+                .collect();
+            let readers: Vec<usize> = (0..accesses.len())
+                .filter(|i| accesses[*i].can_read(hash))
+                .collect();
+
+            match writers.len() {
+                0 => {
+                    let c: &'w dyn Componentable = &**c;
+                    for i in readers {
+                        views[i].list.push((hash, Borrowed::Ref(c)));
+                    }
+                },
+                1 if readers.len() == 1 => {
+                    views[writers[0]].list.push((hash, Borrowed::Mut(c.as_mut())));
+                },
+                _ => {
+                    return Err(Error::AccessConflict(hash))
+                }
+            }
+        }
+
+        Ok(views)
+    }
+}
+
+impl ComponentStore for ComponentView<'_> {
+    fn borrow_ref(&self, hash: u64) -> Option<&dyn Componentable> {
+        if !self.declared.can_read(hash) {
+            return None
+        }
+
+        self.list.iter()
+            .find(|(h, _)| *h == hash)
+            .map(|(_, b)| match b {
+                Borrowed::Ref(c) => *c,
+                Borrowed::Mut(c) => &**c,
+            })
+    }
+
+    fn borrow_many(&mut self, requests: &[Request]) -> Result<Vec<Option<Borrowed<'_>>>, Error> {
+        for r in requests {
+            if !self.declared.can_read(r.hash) || (r.mutable && !self.declared.can_write(r.hash)) {
+                return Err(Error::AccessNotDeclared(r.hash))
+            }
+        }
+
+        let items = self.list.iter_mut().map(|(h, b)| (*h, b.reborrow()));
+        distribute(items, requests)
+    }
+    fn signature_index(&self) -> Option<&SignatureIndex> {
+        self.signatures
+    }
+}
+
+pub struct SystemContext<'w> {
+    pub dt: f32,
+    pub commands: Commands,
+    entity_manager: &'w EntityManager,
+    components: ComponentView<'w>,
+    events: Option<&'w EventRegistry>,
+    resources: Option<ResourceView<'w>>,
+    last_run: u64,
+}
+
+impl<'w> SystemContext<'w> {
+    pub fn new(dt: f32, entity_manager: &'w EntityManager, components: ComponentView<'w>) -> Self {
+        Self { dt, commands: Commands::new(), entity_manager, components, events: None, resources: None, last_run: 0 }
+    }
+
+    // Tick of the system's previous run, 0 on its first run
+    pub fn last_run(&self) -> u64 {
+        self.last_run
+    }
+
+    pub fn entity_manager(&self) -> &EntityManager {
+        self.entity_manager
+    }
+
+    pub fn get<T: Componentable + 'static>(&self) -> Option<&T> {
+        let hash = ComponentManager::type_hash::<T>();
+        self.components.borrow_ref(hash)?.as_any().downcast_ref::<T>()
+    }
+
+    pub fn get_mut<T: Componentable + 'static>(&mut self) -> Option<&mut T> {
+        let request = Request { hash: ComponentManager::type_hash::<T>(), mutable: true };
+        let borrowed = self.components.borrow_many(&[request]).ok()?.pop()??;
+        borrowed.into_mut::<T>()
+    }
+
+    pub fn query<Q: ReadOnlyQueryData>(&self) -> Result<Query<'_, Q>, Error> {
+        Query::new_ref_since(self.entity_manager, &self.components, self.last_run)
+    }
+
+    // `Added` and `Changed` filters match rows touched since this system last ran
+    pub fn query_filtered<Q: ReadOnlyQueryData, F: QueryFilter>(&self) -> Result<Query<'_, Q, F>, Error> {
+        Query::new_ref_since(self.entity_manager, &self.components, self.last_run)
+    }
+
+    pub fn query_mut<Q: QueryData>(&mut self) -> Result<Query<'_, Q>, Error> {
+        Query::new_since(self.entity_manager, &mut self.components, self.last_run)
+    }
+
+    pub fn query_filtered_mut<Q: QueryData, F: QueryFilter>(&mut self) -> Result<Query<'_, Q, F>, Error> {
+        Query::new_since(self.entity_manager, &mut self.components, self.last_run)
+    }
+
+    pub fn resource<T: 'static>(&self) -> Option<&T> {
+        self.resources.as_ref()?.get::<T>()
+    }
+
+    pub fn resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.resources.as_mut()?.get_mut::<T>()
+    }
+
+    // Events of type `T` sent since `cursor` last read. Sending goes through `commands`.
+    pub fn read_events<T: Send + Sync + 'static>(&self, cursor: &mut EventCursor<T>) -> Vec<&T> {
+        match self.events.and_then(|r| r.get::<T>()) {
+            Some(events) => events.read(cursor).collect(),
+            None => Vec::new()
+        }
+    }
+
+    // Entities that lost `T` since this system last ran
+    pub fn removed<T: Componentable + 'static>(&self) -> Vec<Entity> {
+        match self.components.borrow_ref(ComponentManager::type_hash::<T>()) {
+            Some(c) => c.component().removed_since(self.last_run).collect(),
+            None => Vec::new()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExecutionMode {
+    // Every system runs on the calling thread in schedule order, for debugging
+    Serial,
+    // Non-conflicting systems of a stage run concurrently on a pool of `threads` workers,
+    // started once and reused every frame
+    Parallel { threads: usize },
+}
+
+impl Default for ExecutionMode {
+    fn default() -> Self {
+        let threads = match std::thread::available_parallelism() {
+            Ok(val) => val.get(),
+            Err(_) => 1
+        };
+
+        ExecutionMode::Parallel { threads }
+    }
+}
+
+// <entry index, recorded commands, error>
+type JobResult = (usize, Commands, Option<Error>);
+
+// <entry index, entry, its components, its resources>
+type Job<'e, 'w> = (usize, &'e mut Entry, ComponentView<'w>, ResourceView<'w>);
+
+// Worker threads of parallel mode, started on the first parallel run and kept between frames
+struct WorkerPool {
+    threads: usize,
+    pool: rayon::ThreadPool,
+}
+
+// Everything the systems of a stage borrow from the ECS while it runs
+pub struct StageData<'w> {
+    pub entity_manager: &'w EntityManager,
+    pub component_manager: &'w mut ComponentManager,
+    pub resources: &'w mut Resources,
+    pub events: &'w EventRegistry,
+}
+
+struct Entry {
+    stage: Stage,
+    access: Access,
+    system: Box<dyn System>,
+    last_run: u64,
+}
+
+#[derive(Default)]
+pub struct Schedule {
+    entries: Vec<Entry>,
+    order: HashMap<Stage, Vec<usize>>, // <stage, entry indices in run order>
+    batches: HashMap<Stage, Vec<Vec<usize>>>, // <stage, groups of entries that can run together>
+    mode: ExecutionMode,
+    built: bool,
+    workers: Option<WorkerPool>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            order: HashMap::new(),
+            batches: HashMap::new(),
+            mode: ExecutionMode::default(),
+            built: false,
+            workers: None,
+        }
+    }
+
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) {
+        self.mode = mode;
+    }
+
+    #[cfg(test)]
+    pub fn execution_mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    pub fn add_system(&mut self, stage: Stage, system: Box<dyn System>) -> Result<(), Error> {
+        if self.find(system.name()).is_some() {
+            return Err(Error::DuplicateSystem(String::from(system.name())))
+        }
+
+        let access = system.access();
+        self.entries.push(Entry { stage, access, system, last_run: 0 });
+        self.built = false;
+
+        Ok(())
+    }
+
+    pub fn remove_system(&mut self, name: &str) -> bool {
+        match self.find(name) {
+            Some(index) => {
+                self.entries.remove(index);
+                self.built = false;
+                return true
+            },
+            None => return false
+        }
+    }
+
+    #[cfg(test)]
+    pub fn has_system(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Sorts every stage by its ordering constraints
+    pub fn build(&mut self) -> Result<(), Error> {
+        let mut order = HashMap::new();
+        let mut batches = HashMap::new();
+        for stage in Stage::ALL {
+            let sorted = self.sort_stage(stage)?;
+            batches.insert(stage, self.batch_stage(stage, &sorted)?);
+            order.insert(stage, sorted);
+        }
+
+        self.order = order;
+        self.batches = batches;
+        self.built = true;
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn run_order(&mut self, stage: Stage) -> Result<Vec<String>, Error> {
+        if !self.built {
+            self.build()?;
+        }
+
+        Ok(self.order[&stage].iter()
+            .map(|i| String::from(self.entries[*i].system.name()))
+            .collect())
+    }
+
+    pub fn run_batches(&mut self, stage: Stage) -> Result<Vec<Vec<String>>, Error> {
+        if !self.built {
+            self.build()?;
+        }
+
+        Ok(self.batches[&stage].iter()
+            .map(|batch| batch.iter()
+                .map(|i| String::from(self.entries[*i].system.name()))
+                .collect())
+            .collect())
+    }
+
+    // Systems in the same stage whose access overlaps without an ordering between them
+    pub fn conflicts(&self) -> Result<Vec<Conflict>, Error> {
+        let mut list = Vec::new();
+
+        for stage in Stage::ALL {
+            let indices = self.stage_indices(stage);
+            let edges = self.stage_edges(stage, &indices)?;
+
+            for (a_pos, a) in indices.iter().enumerate() {
+                for b in indices.iter().skip(a_pos + 1) {
+                    let components = self.entries[*a].access.conflicts(&self.entries[*b].access);
+                    let resources = self.entries[*a].access.resource_conflicts(&self.entries[*b].access);
+                    if components.is_empty() && resources.is_empty() {
+                        continue;
+                    }
+
+                    if Schedule::reaches(&edges, *a, *b) || Schedule::reaches(&edges, *b, *a) {
+                        continue;
+                    }
+
+                    list.push(Conflict {
+                        stage,
+                        first: String::from(self.entries[*a].system.name()),
+                        second: String::from(self.entries[*b].system.name()),
+                        components,
+                        resources,
+                    });
+                }
+            }
+        }
+
+        Ok(list)
+    }
+
+    // Builds the schedule if systems changed since the last build
+    pub fn prepare(&mut self) -> Result<(), Error> {
+        if !self.built {
+            self.build()?;
+        }
+
+        Ok(())
+    }
+
+    // Runs every system of a stage, returning the commands they recorded in run order
+    pub fn run_stage(&mut self, stage: Stage, dt: f32, data: StageData) -> (Commands, Vec<Error>) {
+        if let Err(e) = self.prepare() {
+            return (Commands::new(), vec![e])
+        }
+
+        match self.mode {
+            ExecutionMode::Serial => self.run_serial(stage, dt, data),
+            ExecutionMode::Parallel { threads } => self.run_parallel(stage, threads, dt, data),
+        }
+    }
+
+    fn run_serial(&mut self, stage: Stage, dt: f32, mut data: StageData) -> (Commands, Vec<Error>) {
+        let mut commands = Commands::new();
+        let mut errors = Vec::new();
+
+        for index in self.order[&stage].iter() {
+            let tick = Schedule::advance_tick(data.component_manager);
+            let (c, e) = Schedule::run_entry(&mut self.entries[*index], tick, dt, &mut data);
+
+            commands.append(c);
+            errors.extend(e);
+        }
+
+        (commands, errors)
+    }
+
+    fn run_entry(entry: &mut Entry, tick: u64, dt: f32, data: &mut StageData) -> (Commands, Option<Error>) {
+        let view = ComponentView::new(data.component_manager, &entry.access);
+        let mut ctx = SystemContext::new(dt, data.entity_manager, view);
+        ctx.events = Some(data.events);
+        ctx.resources = Some(ResourceView::new(data.resources, &entry.access));
+        ctx.last_run = entry.last_run;
+
+        let result = Schedule::run_system(entry, ctx);
+        entry.last_run = tick;
+
+        result
+    }
+
+    fn run_parallel(&mut self, stage: Stage, threads: usize, dt: f32, mut data: StageData) -> (Commands, Vec<Error>) {
+        let mut commands = Commands::new();
+        let mut errors = Vec::new();
+
+        let threads = threads.max(1);
+        if self.workers.as_ref().map(|w| w.threads) != Some(threads) {
+            match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => self.workers = Some(WorkerPool { threads, pool }),
+                Err(e) => return (commands, vec![Error::ThreadPool(e.to_string())])
+            }
+        }
+
+        let pool = match &self.workers {
+            Some(val) => &val.pool,
+            None => return (commands, errors)
+        };
+
+        for batch in self.batches[&stage].iter() {
+            let mut jobs: Vec<(usize, &mut Entry)> = self.entries.iter_mut()
+                .enumerate()
+                .filter(|(i, _)| batch.contains(i))
+                .collect();
+
+            // Keep the batch's run order so results are merged deterministically
+            jobs.sort_by_key(|(i, _)| batch.iter().position(|b| b == i));
+
+            // Every system gets its own tick in run order, the same as in serial mode.
+            // A written component has a single writer in the batch, so it is stamped with its tick.
+            let start = data.component_manager.tick();
+            let ticks: Vec<u64> = (1..=jobs.len() as u64).map(|i| start + i).collect();
+            data.component_manager.set_tick(start + jobs.len() as u64);
+            for ((_, entry), tick) in jobs.iter().zip(ticks.iter()) {
+                for hash in entry.access.writes() {
+                    if let Some(c) = data.component_manager.get_by_hash_mut(*hash) {
+                        c.component_mut().set_tick(*tick);
+                    }
+                }
+            }
+
+            let accesses: Vec<&Access> = jobs.iter().map(|(_, e)| &e.access).collect();
+            let views = ComponentView::split(data.component_manager, &accesses)
+                .and_then(|c| Ok(c.into_iter().zip(ResourceView::split(data.resources, &accesses)?)));
+            let views = match views {
+                Ok(val) => val,
+                Err(_) => {
+                    // The batch can't be split between workers, run its systems one after another
+                    for ((_, entry), tick) in jobs.into_iter().zip(ticks) {
+                        data.component_manager.set_tick(tick);
+                        let (c, e) = Schedule::run_entry(entry, tick, dt, &mut data);
+
+                        commands.append(c);
+                        errors.extend(e);
+                    }
+
+                    continue;
+                }
+            };
+
+            let queue: Vec<(Job, u64)> = jobs.into_iter()
+                .zip(views)
+                .map(|((order, entry), (view, resources))| (order, entry, view, resources))
+                .zip(ticks)
+                .collect();
+
+            // Collecting a parallel iterator keeps the batch's run order
+            let (entity_manager, events) = (data.entity_manager, data.events);
+            let results: Vec<JobResult> = pool.install(|| {
+                queue.into_par_iter()
+                    .map(|(job, tick)| Schedule::run_job(job, tick, dt, entity_manager, events))
+                    .collect()
+            });
+
+            for (_, c, e) in results {
+                commands.append(c);
+                errors.extend(e);
+            }
+        }
+
+        (commands, errors)
+    }
+
+    fn run_job<'w>(
+        job: Job<'_, 'w>,
+        tick: u64,
+        dt: f32,
+        entity_manager: &'w EntityManager,
+        events: &'w EventRegistry
+    ) -> JobResult {
+
+        let (index, entry, view, resources) = job;
+        let mut ctx = SystemContext::new(dt, entity_manager, view);
+        ctx.events = Some(events);
+        ctx.resources = Some(resources);
+        ctx.last_run = entry.last_run;
+
+        let (commands, error) = Schedule::run_system(entry, ctx);
+        entry.last_run = tick;
+
+        (index, commands, error)
+    }
+
+    // Runs one system the same way in both modes. A panic becomes an error of that
+    // system and drops only the commands it recorded.
+    fn run_system(entry: &mut Entry, mut ctx: SystemContext) -> (Commands, Option<Error>) {
+        let system = &mut entry.system;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| system.run(&mut ctx)));
+
+        match result {
+            Ok(Ok(())) => (ctx.commands, None),
+            Ok(Err(e)) => (ctx.commands, Some(Schedule::system_error(system.name(), e))),
+            Err(payload) => {
+                let msg = match payload.downcast_ref::<&str>() {
+                    Some(val) => String::from(*val),
+                    None => payload.downcast_ref::<String>().cloned().unwrap_or_default()
+                };
+
+                (Commands::new(), Some(Schedule::system_error(system.name(), Error::SystemPanicked(msg))))
+            }
+        }
+    }
+
+    // Oldest tick any system last ran at, `None` without systems
+    pub fn oldest_run(&self) -> Option<u64> {
+        self.entries.iter().map(|e| e.last_run).min()
+    }
+
+    fn advance_tick(component_manager: &mut ComponentManager) -> u64 {
+        let tick = component_manager.tick() + 1;
+        component_manager.set_tick(tick);
+
+        tick
+    }
+
+    fn system_error(name: &str, e: Error) -> Error {
+        Error::System { name: String::from(name), source: Box::new(e) }
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.system.name() == name)
+    }
+
+    fn stage_indices(&self, stage: Stage) -> Vec<usize> {
+        (0..self.entries.len())
+            .filter(|i| self.entries[*i].stage == stage)
+            .collect()
+    }
+
+    // Edges (from, to) between systems of a stage, meaning `from` runs before `to`
+    fn stage_edges(&self, stage: Stage, indices: &[usize]) -> Result<Vec<(usize, usize)>, Error> {
+        let mut edges = Vec::new();
+
+        for i in indices {
+            let entry = &self.entries[*i];
+
+            for name in entry.system.after() {
+                if let Some(other) = self.constraint(stage, &name, entry, true)? {
+                    edges.push((other, *i));
+                }
+            }
+
+            for name in entry.system.before() {
+                if let Some(other) = self.constraint(stage, &name, entry, false)? {
+                    edges.push((*i, other));
+                }
+            }
+        }
+
+        Ok(edges)
+    }
+
+    // Resolves an ordering constraint, returning the other system if it shares the stage.
+    // Constraints across stages must agree with the stage order.
+    fn constraint(
+        &self,
+        stage: Stage,
+        name: &str,
+        entry: &Entry,
+        after: bool
+    ) -> Result<Option<usize>, Error> {
+
+        let other = match self.find(name) {
+            Some(val) => val,
+            None => {
+                return Err(Error::UnknownSystem {
+                    system: String::from(entry.system.name()),
+                    dependency: String::from(name),
+                })
+            }
+        };
+
+        let other_stage = self.entries[other].stage;
+        if other_stage == stage {
+            return Ok(Some(other))
+        }
+
+        if (after && other_stage > stage) || (!after && other_stage < stage) {
+            return Err(Error::CrossStageOrdering {
+                system: String::from(entry.system.name()),
+                other: String::from(name),
+            })
+        }
+
+        Ok(None)
+    }
+
+    // Kahn's algorithm, ties broken by insertion order so runs are deterministic
+    fn sort_stage(&self, stage: Stage) -> Result<Vec<usize>, Error> {
+        let indices = self.stage_indices(stage);
+        let edges = self.stage_edges(stage, &indices)?;
+
+        let mut incoming: HashMap<usize, usize> = indices.iter().map(|i| (*i, 0)).collect();
+        for (_, to) in edges.iter() {
+            *incoming.get_mut(to).unwrap() += 1;
+        }
+
+        let mut sorted = Vec::new();
+        let mut remaining = indices.clone();
+
+        while !remaining.is_empty() {
+            let next = match remaining.iter().position(|i| incoming[i] == 0) {
+                Some(val) => val,
+                None => {
+                    return Err(Error::OrderingCycle(format!("{stage:?}")))
+                }
+            };
+
+            let index = remaining.remove(next);
+            for (_, to) in edges.iter().filter(|(from, _)| *from == index) {
+                *incoming.get_mut(to).unwrap() -= 1;
+            }
+
+            sorted.push(index);
+        }
+
+        Ok(sorted)
+    }
+
+    // Groups a sorted stage into batches of systems that can run at the same time.
+    // A system lands after every system it must follow and after every earlier
+    // system it conflicts with, so batched runs match the serial order's results.
+    fn batch_stage(&self, stage: Stage, sorted: &[usize]) -> Result<Vec<Vec<usize>>, Error> {
+        let edges = self.stage_edges(stage, sorted)?;
+        let mut placed: HashMap<usize, usize> = HashMap::new(); // <entry, batch>
+        let mut batches: Vec<Vec<usize>> = Vec::new();
+
+        for (pos, index) in sorted.iter().enumerate() {
+            let mut batch = 0;
+
+            for (from, _) in edges.iter().filter(|(_, to)| to == index) {
+                batch = batch.max(placed[from] + 1);
+            }
+
+            for earlier in sorted[..pos].iter() {
+                let access = &self.entries[*earlier].access;
+                let other = &self.entries[*index].access;
+                if !access.conflicts(other).is_empty() || !access.resource_conflicts(other).is_empty() {
+                    batch = batch.max(placed[earlier] + 1);
+                }
+            }
+
+            if batch == batches.len() {
+                batches.push(Vec::new());
+            }
+
+            batches[batch].push(*index);
+            placed.insert(*index, batch);
+        }
+
+        Ok(batches)
+    }
+
+    fn reaches(edges: &[(usize, usize)], from: usize, to: usize) -> bool {
+        let mut stack = vec![from];
+        let mut visited = HashSet::new();
+
+        while let Some(current) = stack.pop() {
+            if current == to {
+                return true
+            }
+
+            if !visited.insert(current) {
+                continue;
+            }
+
+            for (_, next) in edges.iter().filter(|(f, _)| *f == current) {
+                stack.push(*next);
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,129 @@
+use crate::game::Game;
+use crate::error::Error;
+use crate::system::ecs::{ECS, entity::Entity};
+use crate::system::ecs::commands::{Commands, Command, Target};
+use crate::system::ecs::schedule::{Access, Stage, System, SystemContext};
+use crate::system::ecs::component_manager::component::{name_component::NameComponent, hierarchy_component::HierarchyComponent};
+use super::fixture::{PositionComponent, create_world};
+
+struct Spawner;
+
+impl System for Spawner {
+    fn name(&self) -> &str {
+        "spawner"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().read::<NameComponent>()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        let parents: Vec<Entity> = ctx.query::<(&NameComponent,)>()?.entities();
+        for p in parents {
+            let child = ctx.commands.spawn();
+            ctx.commands.attach::<PositionComponent>(child);
+            ctx.commands.set_parent(child, p);
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn apply_commands() {
+    let mut ecs = create_world(vec![Box::new(PositionComponent::new())]);
+    let parent = ecs.create_entity().unwrap();
+    let doomed = ecs.create_entity().unwrap();
+
+    let mut commands = Commands::new();
+    let child = commands.spawn();
+    commands.attach::<PositionComponent>(child);
+    commands.set_parent(child, parent);
+    commands.despawn(doomed);
+    commands.detach::<NameComponent>(parent);
+
+    assert!(ecs.apply_commands(commands).is_empty());
+    assert_eq!(2, ecs.count());
+    assert!(!ecs.does_entity_exist(doomed));
+
+    let query = ecs.query::<(&PositionComponent, &HierarchyComponent)>().unwrap();
+    let list: Vec<_> = query.iter().collect();
+    assert_eq!(1, list.len());
+    assert_eq!(Some(parent), list[0].1.1.parent());
+
+    assert!(ecs.get_component::<NameComponent>().unwrap().component.find_index(&parent).is_none());
+}
+
+#[test]
+fn collect_errors() {
+    let mut ecs = ECS::new().unwrap();
+    let stale = ecs.create_entity().unwrap();
+    ecs.remove_entity(stale).unwrap();
+
+    let mut commands = Commands::new();
+    commands.despawn(stale);
+    let e = commands.spawn();
+    commands.attach::<PositionComponent>(e);
+    commands.despawn(Target::Spawned(5));
+    commands.spawn();
+
+    let errors = ecs.apply_commands(commands);
+    assert_eq!(3, errors.len());
+    assert!(matches!(errors[0], Error::EntityNotFound(e) if e == stale));
+    assert!(matches!(errors[1], Error::ComponentNotRegistered(_)));
+    assert!(matches!(errors[2], Error::SpawnedEntityMissing(5)));
+    assert_eq!(2, ecs.count());
+}
+
+#[test]
+fn append_commands() {
+    let mut first = Commands::new();
+    let a = first.spawn();
+    first.clear_parent(a);
+
+    let mut second = Commands::new();
+    let b = second.spawn();
+    second.set_parent(b, Entity::new(7));
+
+    first.append(second);
+    assert_eq!(4, first.len());
+    assert_eq!(vec![
+        Command::Spawn,
+        Command::Reparent { child: Target::Spawned(0), parent: None },
+        Command::Spawn,
+        Command::Reparent { child: Target::Spawned(1), parent: Some(Target::Entity(Entity::new(7))) },
+    ], first.drain());
+}
+
+#[test]
+fn systems_record_commands() {
+    let mut ecs = create_world(vec![Box::new(PositionComponent::new())]);
+    for _ in 0..3 {
+        ecs.create_entity().unwrap();
+    }
+
+    ecs.add_system(Stage::Update, Box::new(Spawner)).unwrap();
+    assert!(ecs.handle_update(0.016, &Game::new()).is_empty());
+    assert_eq!(6, ecs.count());
+
+    let query = ecs.query::<(&PositionComponent, &HierarchyComponent)>().unwrap();
+    for (_, (_, hierarchy)) in query.iter() {
+        assert!(hierarchy.parent().is_some());
+    }
+}
+
+#[test]
+fn despawn_recursive() {
+    let mut ecs = create_world(vec![Box::new(PositionComponent::new())]);
+    let root = ecs.create_entity().unwrap();
+
+    let mut commands = Commands::new();
+    let child = commands.spawn();
+    let grandchild = commands.spawn();
+    commands.set_parent(child, root);
+    commands.set_parent(grandchild, child);
+    commands.despawn_recursive(root);
+
+    assert!(ecs.apply_commands(commands).is_empty());
+    assert_eq!(0, ecs.count());
+}
@@ -1,12 +1,15 @@
 use std::any::TypeId;
 use crate::system::ecs::component_manager::{*, component::{*, name_component, hierarchy_component}};
+use crate::error::Error;
 
 #[test]
 fn add_component() {
     let mut cm = ComponentManager::new();
 
     assert!(cm.add(Box::new(name_component::NameComponent::new())).is_ok());
-    assert!(cm.add(Box::new(name_component::NameComponent::new())).is_err());
+    let hash = ComponentManager::type_hash::<name_component::NameComponent>();
+    let result = cm.add(Box::new(name_component::NameComponent::new()));
+    assert!(matches!(result, Err(Error::ComponentAlreadyRegistered(h)) if h == hash));
 }
 
 #[test]
@@ -1,4 +1,5 @@
 use crate::system::ecs::{component_manager::component::{hierarchy_component, Componentable}, entity::Entity};
+use crate::error::Error;
 
 #[test]
 fn attach_component() {
@@ -100,4 +101,87 @@ fn remove_child() {
 
     let c_list = hc.get_children(p_index).unwrap();
     assert_eq!(0, c_list.len());
+}
+
+#[test]
+fn traversal() {
+    let mut hc = hierarchy_component::HierarchyComponent::new();
+
+    // 1 -> 2 -> 4
+    //   -> 3
+    let mut indices = Vec::new();
+    for i in 1..=4 {
+        indices.push(hc.attach(Entity::new(i)).unwrap());
+    }
+    assert!(hc.add_child(indices[0], indices[1]).is_ok());
+    assert!(hc.add_child(indices[0], indices[2]).is_ok());
+    assert!(hc.add_child(indices[1], indices[3]).is_ok());
+
+    let ids = |list: Vec<Entity>| list.iter().map(|e| e.id).collect::<Vec<u64>>();
+    assert_eq!(vec![1, 2, 4, 3], ids(hc.iter_depth_first(indices[0]).collect()));
+    assert_eq!(vec![1, 2, 3, 4], ids(hc.iter_breadth_first(indices[0]).collect()));
+
+    assert_eq!(vec![Entity::new(2), Entity::new(1)], hc.get_ancestors(indices[3]));
+    assert_eq!(Some(Entity::new(1)), hc.get_root(indices[3]));
+    assert_eq!(Some(Entity::new(1)), hc.get_root(indices[0]));
+    assert_eq!(Some(2), hc.get_depth(indices[3]));
+    assert_eq!(Some(0), hc.get_depth(indices[0]));
+    assert_eq!(None, hc.get_depth(99));
+}
+
+#[test]
+fn siblings_keep_insertion_order() {
+    let mut hc = hierarchy_component::HierarchyComponent::new();
+    let root = hc.attach(Entity::new(1)).unwrap();
+    let mut children = Vec::new();
+    for i in [7, 3, 9, 2, 5, 8, 4, 6] {
+        let index = hc.attach(Entity::new(i)).unwrap();
+        assert!(hc.add_child(root, index).is_ok());
+        children.push(Entity::new(i));
+    }
+
+    // Removing and re-adding moves a child to the end
+    assert!(hc.remove_child(root, hc.component.find_index(&Entity::new(9)).unwrap()).is_ok());
+    assert!(hc.add_child(root, hc.component.find_index(&Entity::new(9)).unwrap()).is_ok());
+    children.retain(|e| e.id != 9);
+    children.push(Entity::new(9));
+
+    assert_eq!(&children, hc.get_children(root).unwrap());
+    assert_eq!(children, hc.iter_depth_first(root).skip(1).collect::<Vec<_>>());
+    assert_eq!(children, hc.iter_breadth_first(root).skip(1).collect::<Vec<_>>());
+}
+
+#[test]
+fn reject_cycles() {
+    let mut hc = hierarchy_component::HierarchyComponent::new();
+
+    let a = hc.attach(Entity::new(1)).unwrap();
+    let b = hc.attach(Entity::new(2)).unwrap();
+    let c = hc.attach(Entity::new(3)).unwrap();
+    assert!(hc.add_child(a, b).is_ok());
+    assert!(hc.add_child(b, c).is_ok());
+
+    assert!(hc.add_child(a, a).is_err());
+    assert!(matches!(hc.add_child(c, a), Err(Error::HierarchyCycle { .. })));
+    assert!(hc.move_child(b, c, b).is_err());
+    assert!(matches!(hc.add_child(a, 10), Err(Error::OutOfBounds { index: 10, len: 3 })));
+
+    // Failed attempts leave the tree untouched
+    assert_eq!(Some(Entity::new(1)), hc.get_parent(b));
+    assert!(hc.get_children(a).unwrap().contains(&Entity::new(2)));
+}
+
+#[test]
+fn add_child_unlinks_old_parent() {
+    let mut hc = hierarchy_component::HierarchyComponent::new();
+
+    let p1 = hc.attach(Entity::new(1)).unwrap();
+    let p2 = hc.attach(Entity::new(2)).unwrap();
+    let c = hc.attach(Entity::new(3)).unwrap();
+
+    assert!(hc.add_child(p1, c).is_ok());
+    assert!(hc.add_child(p2, c).is_ok());
+
+    assert!(hc.get_children(p1).unwrap().is_empty());
+    assert!(hc.get_children(p2).unwrap().contains(&Entity::new(3)));
 }
\ No newline at end of file
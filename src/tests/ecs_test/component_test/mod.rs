@@ -1,2 +1,2 @@
 mod name_component_test;
-mod hierarchy_component_test;
\ No newline at end of file
+mod hierarchy_component_test;mod transform_component_test;
\ No newline at end of file
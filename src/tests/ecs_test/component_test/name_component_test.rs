@@ -119,4 +119,85 @@ fn remove_tag() {
         assert!(nc.remove_tag(index, String::from("player")));
         assert!(!nc.has_tag(index, String::from("player")));
     }
+}
+
+#[test]
+fn find_by_name() {
+    let mut nc = name_component::NameComponent::new();
+
+    let index = nc.attach(Entity::new(1)).unwrap();
+    nc.set_name(index, String::from("player"));
+    let index = nc.attach(Entity::new(2)).unwrap();
+    nc.set_name(index, String::from("player"));
+    assert_eq!(Some(Entity::new(1)), nc.find_by_name("player"));
+    assert_eq!(Some(Entity::new(2)), nc.find_by_name("player_0"));
+
+    // Renaming frees the old name, setting the same name keeps it
+    nc.set_name(0, String::from("hero"));
+    assert_eq!(None, nc.find_by_name("player"));
+    nc.set_name(0, String::from("hero"));
+    assert_eq!(String::from("hero"), nc.get_name(0).unwrap().1);
+    nc.set_name(1, String::from("player"));
+    assert_eq!(Some(Entity::new(2)), nc.find_by_name("player"));
+
+    nc.detach(Entity::new(1)).unwrap();
+    assert_eq!(None, nc.find_by_name("hero"));
+}
+
+#[test]
+fn find_by_tag() {
+    let mut nc = name_component::NameComponent::new();
+
+    for i in 1..=3 {
+        let index = nc.attach(Entity::new(i)).unwrap();
+        nc.add_tag(index, String::from("enemy"));
+    }
+    nc.remove_tag(1, String::from("enemy"));
+    nc.detach(Entity::new(3)).unwrap();
+
+    let found: Vec<Entity> = nc.find_by_tag("enemy").collect();
+    assert_eq!(vec![Entity::new(1)], found);
+    assert_eq!(0, nc.find_by_tag("player").count());
+}
+
+#[test]
+fn search_names() {
+    let mut nc = name_component::NameComponent::new();
+
+    for (i, name) in ["tree", "tree", "trunk", "rock"].iter().enumerate() {
+        let index = nc.attach(Entity::new(i as u64)).unwrap();
+        nc.set_name(index, String::from(*name));
+    }
+
+    let found: Vec<Entity> = nc.find_by_prefix("tr").collect();
+    assert_eq!(vec![Entity::new(0), Entity::new(1), Entity::new(2)], found);
+    let found: Vec<Entity> = nc.find_by_glob("tree_?").collect();
+    assert_eq!(vec![Entity::new(1)], found);
+    let found: Vec<Entity> = nc.find_by_glob("*r*k").collect();
+    assert_eq!(vec![Entity::new(3), Entity::new(2)], found);
+    assert_eq!(4, nc.find_by_glob("*").count());
+}
+
+#[test]
+fn suffixes_after_rename() {
+    let mut nc = name_component::NameComponent::new();
+    let name_at = |nc: &name_component::NameComponent, index: usize| nc.get_name(index).unwrap().1.clone();
+
+    for i in 0..3 {
+        let index = nc.attach(Entity::new(i)).unwrap();
+        nc.set_name(index, String::from("rock"));
+    }
+    assert_eq!(vec!["rock", "rock_0", "rock_1"], (0..3).map(|i| name_at(&nc, i)).collect::<Vec<_>>());
+
+    // A renamed entity gives its suffix back, the next duplicate takes the lowest free one
+    nc.set_name(1, String::from("stone"));
+    let index = nc.attach(Entity::new(3)).unwrap();
+    nc.set_name(index, String::from("rock"));
+    assert_eq!("rock_0", name_at(&nc, index));
+
+    // Setting a name again only competes with the other entities
+    nc.set_name(0, String::from("rock"));
+    assert_eq!("rock", name_at(&nc, 0));
+    nc.set_name(2, String::from("rock"));
+    assert_eq!("rock_1", name_at(&nc, 2));
 }
\ No newline at end of file
@@ -0,0 +1,118 @@
+use cgmath::{Vector3, Vector4, Quaternion, Rad, Rotation3, SquareMatrix, Matrix4};
+use crate::system::ecs::{component_manager::component::{transform_component, hierarchy_component, Componentable}, entity::Entity};
+
+fn position(m: Matrix4<f32>) -> Vector3<f32> {
+    let p = m * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    Vector3::new(p.x, p.y, p.z)
+}
+
+fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+    (a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5 && (a.z - b.z).abs() < 1e-5
+}
+
+#[test]
+fn attach_detach_component() {
+    let mut tc = transform_component::TransformComponent::new();
+
+    let count = 10;
+    for i in 1..=count {
+        let index = tc.attach(Entity::new(i)).unwrap();
+        assert!(tc.is_dirty(index));
+        assert_eq!(Some(Matrix4::identity()), tc.get_world_matrix(index));
+    }
+
+    for i in 1..=count {
+        assert!(tc.detach(Entity::new(i)).is_ok());
+    }
+
+    assert!(tc.is_empty());
+}
+
+#[test]
+fn propagate() {
+    let mut tc = transform_component::TransformComponent::new();
+    let mut hc = hierarchy_component::HierarchyComponent::new();
+
+    // parent -> child -> grandchild, where the child has no transform
+    let parent = Entity::new(1);
+    let child = Entity::new(2);
+    let grandchild = Entity::new(3);
+
+    let p_h = hc.attach(parent).unwrap();
+    let c_h = hc.attach(child).unwrap();
+    let g_h = hc.attach(grandchild).unwrap();
+    hc.add_child(p_h, c_h).unwrap();
+    hc.add_child(c_h, g_h).unwrap();
+
+    let p = tc.attach(parent).unwrap();
+    let g = tc.attach(grandchild).unwrap();
+
+    tc.set_translation(p, Vector3::new(10.0, 0.0, 0.0));
+    tc.set_rotation(p, Quaternion::from_angle_z(Rad(std::f32::consts::FRAC_PI_2)));
+    tc.set_scale(p, Vector3::new(2.0, 2.0, 2.0));
+    tc.set_translation(g, Vector3::new(1.0, 0.0, 0.0));
+
+    tc.propagate(&hc);
+    assert!(!tc.is_dirty(p));
+    assert!(!tc.is_dirty(g));
+    assert!(close(Vector3::new(10.0, 0.0, 0.0), position(tc.get_world_matrix(p).unwrap())));
+    assert!(close(Vector3::new(10.0, 2.0, 0.0), position(tc.get_world_matrix(g).unwrap())));
+    assert!(close(Vector3::new(1.0, 0.0, 0.0), position(tc.get_local_matrix(g).unwrap())));
+
+    tc.set_translation(p, Vector3::new(0.0, 0.0, 5.0));
+    assert!(tc.is_dirty(p));
+    tc.propagate(&hc);
+    assert!(close(Vector3::new(0.0, 2.0, 5.0), position(tc.get_world_matrix(g).unwrap())));
+}
+
+#[test]
+fn propagate_only_dirty() {
+    let mut tc = transform_component::TransformComponent::new();
+    let hc = hierarchy_component::HierarchyComponent::new();
+
+    let a = tc.attach(Entity::new(1)).unwrap();
+    let b = tc.attach(Entity::new(2)).unwrap();
+    tc.propagate(&hc);
+
+    tc.set_translation(a, Vector3::new(1.0, 0.0, 0.0));
+    tc.set_translation(b, Vector3::new(2.0, 0.0, 0.0));
+    tc.propagate(&hc);
+
+    // A clean transform keeps its world matrix until something marks it dirty
+    tc.set_translation(a, Vector3::new(3.0, 0.0, 0.0));
+    assert!(tc.mark_dirty(&Entity::new(2)));
+    tc.propagate(&hc);
+
+    assert!(close(Vector3::new(3.0, 0.0, 0.0), position(tc.get_world_matrix(a).unwrap())));
+    assert!(close(Vector3::new(2.0, 0.0, 0.0), position(tc.get_world_matrix(b).unwrap())));
+    assert_eq!(tc.get_model(b), tc.get_world_matrix(b).map(|m| m.into()));
+}
+
+#[test]
+fn propagate_after_hierarchy_moves() {
+    let mut tc = transform_component::TransformComponent::new();
+    let mut hc = hierarchy_component::HierarchyComponent::new();
+
+    // a and b are roots, c moves between them through the hierarchy alone
+    let (a, b, c) = (Entity::new(1), Entity::new(2), Entity::new(3));
+    let a_h = hc.attach(a).unwrap();
+    let b_h = hc.attach(b).unwrap();
+    let c_h = hc.attach(c).unwrap();
+    hc.add_child(a_h, c_h).unwrap();
+
+    let a_t = tc.attach(a).unwrap();
+    let b_t = tc.attach(b).unwrap();
+    let c_t = tc.attach(c).unwrap();
+    tc.set_translation(a_t, Vector3::new(1.0, 0.0, 0.0));
+    tc.set_translation(b_t, Vector3::new(0.0, 5.0, 0.0));
+    tc.propagate(&hc);
+    assert!(close(Vector3::new(1.0, 0.0, 0.0), position(tc.get_world_matrix(c_t).unwrap())));
+
+    hc.move_child(a_h, b_h, c_h).unwrap();
+    tc.propagate(&hc);
+    assert!(close(Vector3::new(0.0, 5.0, 0.0), position(tc.get_world_matrix(c_t).unwrap())));
+
+    hc.remove_child(b_h, c_h).unwrap();
+    tc.propagate(&hc);
+    assert!(close(Vector3::new(0.0, 0.0, 0.0), position(tc.get_world_matrix(c_t).unwrap())));
+}
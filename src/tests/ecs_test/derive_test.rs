@@ -0,0 +1,230 @@
+use serde::{Serialize, Deserialize};
+
+use crate::system::ecs::{ECS, entity::Entity};
+use crate::error::Error;
+use crate::system::ecs::component_manager::{ComponentManager, component::{Component, Componentable}};
+use crate::system::ecs::component_manager::component::{name_component::NameComponent, reflect::Reflect};
+use crate::util::hash;
+
+#[derive(Serialize, Deserialize, Componentable)]
+#[component(on_attach = attached, on_detach = detached)]
+pub struct StatsComponent {
+    pub component: Component,
+    #[entity]
+    entity: Vec<Entity>,
+    #[column(default = 100)]
+    health: Vec<i64>,
+    #[column(readonly, default = String::from("none"))]
+    label: Vec<String>,
+    #[column(manual)]
+    armor: Vec<u32>,
+    log: Vec<String>,
+}
+
+// Two types claiming the same id, nothing deserializes "collider"
+#[derive(Serialize, Deserialize, Componentable)]
+#[component(id = "collider")]
+pub struct BoxColliderComponent {
+    pub component: Component,
+    #[entity]
+    entity: Vec<Entity>,
+}
+
+#[derive(Serialize, Deserialize, Componentable)]
+#[component(id = "collider")]
+pub struct SphereColliderComponent {
+    pub component: Component,
+    #[entity]
+    entity: Vec<Entity>,
+}
+
+impl StatsComponent {
+    pub fn new() -> Self {
+        Self {
+            component: Component::new(),
+            entity: Vec::new(),
+            health: Vec::new(),
+            label: Vec::new(),
+            armor: Vec::new(),
+            log: Vec::new(),
+        }
+    }
+
+    fn attached(&mut self, index: usize) {
+        self.log.push(format!("attach {}", self.entity[index].id));
+    }
+
+    fn detached(&mut self, index: usize) -> Result<(), Error> {
+        if self.armor[index] > 0 {
+            return Err(Error::InvalidData(String::from("armored")))
+        }
+
+        self.log.push(format!("detach {}", self.entity[index].id));
+        Ok(())
+    }
+}
+
+#[test]
+fn attach_defaults() {
+    let mut sc = StatsComponent::new();
+
+    let index = sc.attach(Entity::new(1)).unwrap();
+    assert_eq!(Some(&100), sc.get_health(index));
+    assert_eq!(Some(&String::from("none")), sc.get_label(index));
+    assert_eq!(Some(Entity::new(1)), sc.get_entity(index));
+    assert_eq!(0, sc.armor[index]);
+    assert!(sc.attach(Entity::new(1)).is_err());
+}
+
+#[test]
+fn detach_swaps_rows() {
+    let mut sc = StatsComponent::new();
+
+    for i in 1..=4 {
+        let index = sc.attach(Entity::new(i)).unwrap();
+        sc.set_health(index, i as i64);
+    }
+
+    assert!(sc.detach(Entity::new(2)).is_ok());
+    assert!(sc.detach(Entity::new(2)).is_err());
+
+    // The last row fills the hole and the index map follows it
+    let index = sc.component.find_index(&Entity::new(4)).unwrap();
+    assert_eq!(1, index);
+    assert_eq!(Some(&4), sc.get_health(index));
+    assert_eq!(3, sc.entity.len());
+    assert_eq!(3, sc.health.len());
+
+    for i in [1, 3, 4] {
+        assert!(sc.detach(Entity::new(i)).is_ok());
+    }
+    assert!(sc.is_empty());
+    assert_eq!(vec!["attach 1", "attach 2", "attach 3", "attach 4",
+        "detach 2", "detach 1", "detach 3", "detach 4"], sc.log);
+}
+
+#[test]
+fn detach_hook_can_refuse() {
+    let mut sc = StatsComponent::new();
+
+    let index = sc.attach(Entity::new(1)).unwrap();
+    sc.armor[index] = 1;
+
+    assert!(sc.detach(Entity::new(1)).is_err());
+    assert!(sc.component.does_exist(&Entity::new(1)));
+}
+
+#[test]
+fn derived_component_in_ecs() {
+    let mut ecs = ECS::new().unwrap();
+    ecs.add_component(Box::new(StatsComponent::new())).unwrap();
+
+    let e = ecs.create_entity().unwrap();
+    ecs.attach_component::<StatsComponent>(e).unwrap();
+    let since = ecs.tick();
+    ecs.advance_tick();
+
+    let mut query = ecs.query_mut::<(&mut StatsComponent,)>().unwrap();
+    let (mut stats,) = query.get(e).unwrap();
+    assert_eq!(100, *stats.health());
+    assert!(stats.set_health(42));
+
+    let sc = ecs.get_component::<StatsComponent>().unwrap();
+    assert!(sc.component.is_changed_since(&e, since));
+
+    let json = serde_json::to_string(&ecs).unwrap();
+    let ecs: ECS = serde_json::from_str(&json).unwrap();
+    let query = ecs.query::<(&StatsComponent,)>().unwrap();
+    let (stats,) = query.get_ref(e).unwrap();
+    assert_eq!(42, *stats.health());
+    assert_eq!("none", stats.label());
+}
+
+#[test]
+fn ids_are_stable() {
+    assert_eq!("StatsComponent", StatsComponent::id());
+    assert_eq!("collider", BoxColliderComponent::id());
+    assert_eq!(hash::fnv1a(b"StatsComponent"), ComponentManager::type_hash::<StatsComponent>());
+    assert_eq!(hash::fnv1a(b"collider"), ComponentManager::type_hash::<SphereColliderComponent>());
+    assert_eq!("StatsComponent", StatsComponent::new().get_id());
+}
+
+#[test]
+fn shared_ids_collide() {
+    let mut cm = ComponentManager::new();
+    cm.add(Box::new(BoxColliderComponent { component: Component::new(), entity: Vec::new() })).unwrap();
+
+    let result = cm.add(Box::new(SphereColliderComponent { component: Component::new(), entity: Vec::new() }));
+    assert!(matches!(result, Err(Error::ComponentIdCollision { id: "collider", other: "collider", .. })));
+
+    let result = cm.add(Box::new(BoxColliderComponent { component: Component::new(), entity: Vec::new() }));
+    assert!(matches!(result, Err(Error::ComponentAlreadyRegistered(_))));
+}
+
+#[test]
+fn reflect_lists_and_sets_columns() {
+    let mut sc = StatsComponent::new();
+    let e = Entity::new(1);
+    sc.attach(e).unwrap();
+
+    let fields = sc.fields(e).unwrap();
+    let names: Vec<&str> = fields.iter().map(|f| f.info.name).collect();
+    assert_eq!(vec!["health", "label", "armor"], names);
+    assert_eq!("i64", fields[0].info.ty);
+    assert_eq!(serde_json::json!(100), fields[0].value);
+    assert_eq!(serde_json::json!("none"), fields[1].value);
+
+    assert!(!fields[0].info.readonly);
+    assert!(fields[1].info.readonly);
+
+    sc.set_field(e, "health", serde_json::json!(7)).unwrap();
+    assert_eq!(Some(&7), sc.get_health(0));
+    assert!(matches!(sc.set_field(e, "label", serde_json::json!("boss")), Err(Error::ReadOnlyColumn { .. })));
+    assert_eq!(serde_json::json!("none"), sc.get_field(e, "label").unwrap());
+
+    // Loading a row still writes readonly columns
+    sc.load_row(0, &serde_json::json!({ "label": "boss" })).unwrap();
+    assert_eq!(serde_json::json!("boss"), sc.get_field(e, "label").unwrap());
+
+    assert!(matches!(sc.set_field(e, "health", serde_json::json!("lots")), Err(Error::InvalidRow { .. })));
+    assert!(matches!(sc.get_field(e, "log"), Err(Error::UnknownColumn { .. })));
+    assert!(matches!(sc.get_field(Entity::new(2), "health"), Err(Error::ComponentNotAttached { .. })));
+}
+
+#[test]
+fn reflect_whole_entity() {
+    let mut ecs = ECS::new().unwrap();
+    let e = ecs.create_entity().unwrap();
+
+    let components = ecs.reflect_entity(e).unwrap();
+    let ids: Vec<&str> = components.iter().map(|(id, _)| *id).collect();
+    assert_eq!(vec!["HierarchyComponent", "NameComponent", "TransformComponent"], ids);
+
+    // Names are only changed through `set_name`, which keeps them unique
+    let hash = ComponentManager::type_hash::<NameComponent>();
+    let nc = ecs.get_component_by_hash_mut(hash).unwrap();
+    let result = nc.set_field(e, "name", serde_json::json!([0, "player"]));
+    assert!(matches!(result, Err(Error::ReadOnlyColumn { .. })));
+    assert_eq!(None, ecs.get_component::<NameComponent>().unwrap().find_by_name("player"));
+
+    assert!(matches!(ecs.reflect_entity(Entity::new(99)), Err(Error::EntityNotFound(_))));
+}
+
+#[test]
+fn failed_loads_leave_the_row_untouched() {
+    let mut nc = NameComponent::new();
+    let e = Entity::new(1);
+    let index = nc.attach(e).unwrap();
+    nc.set_name(index, String::from("player"));
+    nc.add_tag(index, String::from("hero"));
+
+    // "name" parses, "tags" doesn't
+    let row = serde_json::json!({ "name": [0, "renamed"], "tags": 42 });
+    assert!(matches!(nc.load_row(index, &row), Err(Error::InvalidRow { .. })));
+    assert!(nc.set_field(e, "name", serde_json::json!(42)).is_err());
+
+    assert_eq!("player", nc.get_name(index).unwrap().1);
+    assert_eq!(Some(e), nc.find_by_name("player"));
+    assert_eq!(None, nc.find_by_name("renamed"));
+    assert_eq!(vec![e], nc.find_by_tag("hero").collect::<Vec<_>>());
+}
@@ -1,4 +1,6 @@
-use crate::system::ecs::{ECS, entity::Entity};
+use cgmath::{Vector3, Vector4};
+use crate::error::Error;
+use crate::system::ecs::{ECS, entity::Entity, component_manager::component::{Componentable, name_component::NameComponent, hierarchy_component::HierarchyComponent, transform_component::TransformComponent}};
 
 #[test]
 fn create_entity() {
@@ -24,4 +26,142 @@ fn remove_entity() {
     }
 
     assert_eq!(0, ecs.count());
+}
+
+#[test]
+fn stale_entity() {
+    let mut ecs = ECS::new().unwrap();
+
+    let stale = ecs.create_entity().unwrap();
+    assert!(ecs.remove_entity(stale).is_ok());
+    let e = ecs.create_entity().unwrap();
+    assert_eq!(stale.id, e.id);
+
+    assert!(!ecs.does_entity_exist(stale));
+    assert!(matches!(ecs.remove_entity(stale), Err(Error::EntityNotFound(e)) if e == stale));
+    assert!(ecs.detach_component::<NameComponent>(stale).is_err());
+    assert!(ecs.attach_component::<NameComponent>(stale).is_err());
+
+    let nc = ecs.get_component::<NameComponent>().unwrap();
+    assert!(nc.component.find_index(&stale).is_none());
+    assert!(nc.component.find_index(&e).is_some());
+}
+
+#[test]
+fn serde_keeps_generations() {
+    let mut ecs = ECS::new().unwrap();
+
+    let stale = ecs.create_entity().unwrap();
+    _ = ecs.remove_entity(stale);
+    let e = ecs.create_entity().unwrap();
+
+    let json = serde_json::to_string(&ecs).unwrap();
+    let mut ecs: ECS = serde_json::from_str(&json).unwrap();
+
+    assert!(ecs.does_entity_exist(e));
+    assert!(!ecs.does_entity_exist(stale));
+    assert!(ecs.get_component::<NameComponent>().unwrap().component.does_exist(&e));
+
+    assert!(ecs.remove_entity(e).is_ok());
+    let next = ecs.create_entity().unwrap();
+    assert_eq!(e.id, next.id);
+    assert_eq!(e.generation + 1, next.generation);
+}
+
+#[test]
+fn remove_entity_recursive() {
+    let mut ecs = ECS::new().unwrap();
+
+    let root = ecs.create_entity().unwrap();
+    let child = ecs.create_entity().unwrap();
+    let grandchild = ecs.create_entity().unwrap();
+    let other = ecs.create_entity().unwrap();
+    assert!(ecs.reparent_subtree(child, Some(root)).is_ok());
+    assert!(ecs.reparent_subtree(grandchild, Some(child)).is_ok());
+
+    // Plain removal still refuses entities with children
+    assert!(ecs.remove_entity(root).is_err());
+
+    assert!(ecs.remove_entity_recursive(root).is_ok());
+    assert!(!ecs.does_entity_exist(root));
+    assert!(!ecs.does_entity_exist(child));
+    assert!(!ecs.does_entity_exist(grandchild));
+    assert!(ecs.does_entity_exist(other));
+    assert_eq!(1, ecs.count());
+}
+
+#[test]
+fn remove_entity_recursive_is_all_or_nothing() {
+    let mut ecs = ECS::new().unwrap();
+
+    let root = ecs.create_entity().unwrap();
+    let child = ecs.create_entity().unwrap();
+    let grandchild = ecs.create_entity().unwrap();
+    assert!(ecs.reparent_subtree(child, Some(root)).is_ok());
+    assert!(ecs.reparent_subtree(grandchild, Some(child)).is_ok());
+
+    // The root claims a name row it no longer has, so removing it would fail last
+    assert!(ecs.get_component_mut::<NameComponent>().unwrap().detach(root).is_ok());
+
+    assert!(matches!(ecs.remove_entity_recursive(root), Err(Error::ComponentNotAttached { entity, .. }) if entity == root));
+    assert!(ecs.does_entity_exist(child));
+    assert!(ecs.does_entity_exist(grandchild));
+    assert!(ecs.get_component::<NameComponent>().unwrap().component.does_exist(&grandchild));
+    assert_eq!(3, ecs.count());
+}
+
+#[test]
+fn reparent_subtree() {
+    let mut ecs = ECS::new().unwrap();
+
+    let a = ecs.create_entity().unwrap();
+    let b = ecs.create_entity().unwrap();
+    let c = ecs.create_entity().unwrap();
+    let d = ecs.create_entity().unwrap();
+    assert!(ecs.reparent_subtree(b, Some(a)).is_ok());
+    assert!(ecs.reparent_subtree(c, Some(b)).is_ok());
+
+    assert!(ecs.reparent_subtree(a, Some(c)).is_err());
+    assert!(ecs.reparent_subtree(a, Some(a)).is_err());
+
+    assert!(ecs.reparent_subtree(b, Some(d)).is_ok());
+    {
+        let hc = ecs.get_component::<HierarchyComponent>().unwrap();
+        let a_index = hc.component.find_index(&a).unwrap();
+        let c_index = hc.component.find_index(&c).unwrap();
+        assert!(hc.get_children(a_index).unwrap().is_empty());
+        assert_eq!(Some(d), hc.get_root(c_index));
+        assert_eq!(Some(2), hc.get_depth(c_index));
+    }
+
+    assert!(ecs.reparent_subtree(b, None).is_ok());
+    let hc = ecs.get_component::<HierarchyComponent>().unwrap();
+    let c_index = hc.component.find_index(&c).unwrap();
+    assert_eq!(Some(b), hc.get_root(c_index));
+}
+
+#[test]
+fn world_matrix_follows_reparent() {
+    let mut ecs = ECS::new().unwrap();
+
+    let a = ecs.create_entity().unwrap();
+    let b = ecs.create_entity().unwrap();
+    let child = ecs.create_entity().unwrap();
+
+    {
+        let tc = ecs.get_component_mut::<TransformComponent>().unwrap();
+        let a_index = tc.component.find_index(&a).unwrap();
+        let b_index = tc.component.find_index(&b).unwrap();
+        tc.set_translation(a_index, Vector3::new(1.0, 0.0, 0.0));
+        tc.set_translation(b_index, Vector3::new(0.0, 5.0, 0.0));
+    }
+
+    assert!(ecs.reparent_subtree(child, Some(a)).is_ok());
+    ecs.propagate_transforms();
+    let origin = Vector4::new(0.0, 0.0, 0.0, 1.0);
+    assert_eq!(Vector4::new(1.0, 0.0, 0.0, 1.0), ecs.world_matrix(child).unwrap() * origin);
+
+    assert!(ecs.reparent_subtree(child, Some(b)).is_ok());
+    assert!(ecs.run_systems(0.0).is_empty());
+    assert_eq!(Vector4::new(0.0, 5.0, 0.0, 1.0), ecs.world_matrix(child).unwrap() * origin);
 }
\ No newline at end of file
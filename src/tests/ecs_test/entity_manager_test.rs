@@ -74,4 +74,24 @@ fn detach_components() {
 
     let attached = em.get_attached(e).unwrap();
     assert_eq!(0, attached.len());
+}
+
+#[test]
+fn stale_handle() {
+    let mut em = EntityManager::new();
+
+    let e1 = em.create();
+    assert!(em.attach_component(e1, 1));
+    assert!(em.remove(e1));
+
+    let e2 = em.create();
+    assert_eq!(e1.id, e2.id);
+    assert_eq!(e1.generation + 1, e2.generation);
+    assert_ne!(e1, e2);
+
+    assert!(!em.does_exist(e1));
+    assert!(!em.attach_component(e1, 2));
+    assert!(!em.has_component(e1, 1));
+    assert!(!em.remove(e1));
+    assert!(em.does_exist(e2));
 }
\ No newline at end of file
@@ -0,0 +1,153 @@
+use std::sync::{Arc, Mutex};
+
+use crate::system::ecs::{ECS, entity::Entity};
+use crate::error::Error;
+use crate::system::ecs::commands::Commands;
+use crate::system::ecs::events::{Events, EventCursor, EntityEvent, ComponentEvent, HierarchyEvent};
+use crate::system::ecs::schedule::{Access, Stage, System, SystemContext};
+use crate::system::ecs::component_manager::ComponentManager;
+use crate::system::ecs::component_manager::component::name_component::NameComponent;
+use crate::system::ecs::component_manager::component::hierarchy_component::HierarchyComponent;
+use crate::system::ecs::component_manager::component::transform_component::TransformComponent;
+
+#[derive(Debug, PartialEq)]
+struct Ping(u32);
+
+#[test]
+fn cursor_reads_once() {
+    let mut events = Events::<Ping>::new();
+    let mut first = EventCursor::new();
+    let mut second = EventCursor::new();
+
+    events.send(Ping(1));
+    events.send(Ping(2));
+    assert_eq!(vec![&Ping(1), &Ping(2)], events.read(&mut first).collect::<Vec<_>>());
+    assert_eq!(0, events.read(&mut first).count());
+
+    events.send(Ping(3));
+    assert_eq!(vec![&Ping(3)], events.read(&mut first).collect::<Vec<_>>());
+    assert_eq!(3, events.read(&mut second).count());
+}
+
+#[test]
+fn double_buffered_update() {
+    let mut events = Events::<Ping>::new();
+    let mut cursor = EventCursor::new();
+
+    events.send(Ping(1));
+    events.update();
+    events.send(Ping(2));
+    assert_eq!(2, events.len());
+
+    // The first event survives one update, then it is dropped unread
+    events.update();
+    assert_eq!(1, events.len());
+    assert_eq!(vec![&Ping(2)], events.read(&mut cursor).collect::<Vec<_>>());
+
+    events.update();
+    assert!(events.is_empty());
+}
+
+#[test]
+fn builtin_events() {
+    let mut ecs = ECS::new().unwrap();
+    let mut entity_cursor = EventCursor::new();
+    let mut component_cursor = EventCursor::new();
+    let mut hierarchy_cursor = EventCursor::new();
+
+    let parent = ecs.create_entity().unwrap();
+    let child = ecs.create_entity().unwrap();
+    assert!(ecs.reparent_subtree(child, Some(parent)).is_ok());
+    assert!(ecs.detach_component::<NameComponent>(child).is_ok());
+    assert!(ecs.remove_entity(child).is_ok());
+
+    assert_eq!(vec![&EntityEvent::Created(parent), &EntityEvent::Created(child), &EntityEvent::Removed(child)],
+        ecs.read_events(&mut entity_cursor));
+
+    let name = ComponentManager::type_hash::<NameComponent>();
+    let list = ecs.read_events::<ComponentEvent>(&mut component_cursor);
+    assert!(list.contains(&&ComponentEvent::Attached { entity: parent, hash: name }));
+    assert!(list.contains(&&ComponentEvent::Detached { entity: child, hash: name }));
+    // Removing the child detaches its remaining hierarchy and transform rows
+    let detached = list.iter().filter(|e| matches!(e, ComponentEvent::Detached { entity, .. } if *entity == child)).count();
+    assert_eq!(3, detached);
+
+    assert_eq!(vec![&HierarchyEvent::Reparented { child, old_parent: None, new_parent: Some(parent) }],
+        ecs.read_events(&mut hierarchy_cursor));
+}
+
+#[test]
+fn unregistered_event() {
+    let mut ecs = ECS::new().unwrap();
+    assert!(ecs.send_event(Ping(1)).is_err());
+
+    ecs.add_event::<Ping>();
+    assert!(ecs.send_event(Ping(1)).is_ok());
+    ecs.add_event::<Ping>();
+    assert_eq!(1, ecs.get_events::<Ping>().unwrap().len());
+
+    let mut commands = Commands::new();
+    commands.send_event(Entity::new(1));
+    assert_eq!(1, ecs.apply_commands(commands).len());
+}
+
+// Answers every ping with a ping one higher, up to 3
+struct Echo {
+    cursor: EventCursor<Ping>,
+    seen: Arc<Mutex<Vec<u32>>>,
+}
+
+impl System for Echo {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn access(&self) -> Access {
+        Access::new()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        let pings: Vec<u32> = ctx.read_events(&mut self.cursor).iter().map(|p| p.0).collect();
+        for n in pings {
+            self.seen.lock().unwrap().push(n);
+            if n < 3 {
+                ctx.commands.send_event(Ping(n + 1));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn systems_send_and_read() {
+    let mut ecs = ECS::new().unwrap();
+    ecs.add_event::<Ping>();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    ecs.add_system(Stage::Update, Box::new(Echo { cursor: EventCursor::new(), seen: seen.clone() })).unwrap();
+
+    assert!(ecs.send_event(Ping(1)).is_ok());
+    for _ in 0..4 {
+        assert!(ecs.run_systems(0.0).is_empty());
+        ecs.get_events_mut::<Ping>().unwrap().update();
+    }
+
+    assert_eq!(vec![1, 2, 3], *seen.lock().unwrap());
+}
+
+#[test]
+fn failed_creation_sends_nothing() {
+    let mut ecs = ECS::new().unwrap();
+    let mut entity_cursor = EventCursor::new();
+    let mut component_cursor = EventCursor::new();
+    assert!(ecs.remove_component::<TransformComponent>().is_ok());
+
+    assert!(ecs.create_entity().is_err());
+    assert_eq!(0, ecs.read_events::<EntityEvent>(&mut entity_cursor).len());
+    assert_eq!(0, ecs.read_events::<ComponentEvent>(&mut component_cursor).len());
+
+    // The name and hierarchy rows attached before the failure are gone too
+    assert_eq!(0, ecs.query::<(&NameComponent,)>().unwrap().count());
+    assert_eq!(0, ecs.query::<(&HierarchyComponent,)>().unwrap().count());
+}
@@ -0,0 +1,47 @@
+use serde::{Serialize, Deserialize};
+
+use crate::system::ecs::{ECS, entity::Entity};
+use crate::system::ecs::component_manager::component::{Component, Componentable};
+
+// Minimal single column components used by the ecs tests
+macro_rules! value_component {
+    ($name:ident) => {
+        #[derive(Serialize, Deserialize, Componentable)]
+        pub struct $name {
+            pub component: Component,
+            #[entity]
+            entity: Vec<Entity>,
+            #[column]
+            value: Vec<i64>,
+        }
+
+        impl $name {
+            pub fn new() -> Self {
+                Self {
+                    component: Component::new(),
+                    entity: Vec::new(),
+                    value: Vec::new(),
+                }
+            }
+        }
+    };
+}
+
+value_component!(PositionComponent);
+value_component!(VelocityComponent);
+value_component!(HealthComponent);
+
+// A world with `components` registered next to the built-in ones
+pub fn create_world(components: Vec<Box<dyn Componentable>>) -> ECS {
+    let mut ecs = ECS::new().unwrap();
+    for c in components {
+        ecs.add_component(c).unwrap();
+    }
+
+    ecs
+}
+
+pub fn health_of(ecs: &ECS, e: Entity) -> i64 {
+    let c = ecs.get_component::<HealthComponent>().unwrap();
+    *c.get_value(c.component.find_index(&e).unwrap()).unwrap()
+}
@@ -1,4 +1,14 @@
+mod fixture;
 mod component_test;
 mod component_manager_test;
 mod entity_manager_test;
-mod ecs_test;
\ No newline at end of file
+mod ecs_test;
+mod query_test;
+mod schedule_test;
+mod commands_test;
+mod derive_test;
+mod events_test;
+mod resources_test;
+mod signature_index_test;
+mod prefab_test;
+mod scene_test;
\ No newline at end of file
@@ -0,0 +1,192 @@
+use cgmath::Vector3;
+
+use crate::system::ecs::{ECS, entity::Entity};
+use crate::system::ecs::prefab::Overrides;
+use crate::system::ecs::component_manager::ComponentManager;
+use crate::util::hash;
+use crate::system::ecs::component_manager::component::{
+    name_component::NameComponent,
+    hierarchy_component::HierarchyComponent,
+    transform_component::TransformComponent};
+use crate::tests::fixture::temp_path;
+use super::fixture::{HealthComponent, health_of};
+
+fn name_of(ecs: &ECS, e: Entity) -> String {
+    let nc = ecs.get_component::<NameComponent>().unwrap();
+    nc.get_name(nc.component.find_index(&e).unwrap()).unwrap().1.clone()
+}
+
+fn children_of(ecs: &ECS, e: Entity) -> Vec<Entity> {
+    let hc = ecs.get_component::<HierarchyComponent>().unwrap();
+    hc.get_children(hc.component.find_index(&e).unwrap()).unwrap().iter().copied().collect()
+}
+
+// A "goblin" root holding a "sword" child, the goblin has 30 health
+fn create_goblin(ecs: &mut ECS) -> (Entity, Entity) {
+    ecs.add_component(Box::new(HealthComponent::new())).unwrap();
+
+    let goblin = ecs.create_entity().unwrap();
+    let sword = ecs.create_entity().unwrap();
+    ecs.reparent_subtree(sword, Some(goblin)).unwrap();
+
+    let nc = ecs.get_component_mut::<NameComponent>().unwrap();
+    let index = nc.component.find_index(&goblin).unwrap();
+    nc.set_name(index, String::from("goblin"));
+    nc.add_tag(index, String::from("enemy"));
+    let index = nc.component.find_index(&sword).unwrap();
+    nc.set_name(index, String::from("sword"));
+
+    let index = ecs.attach_component::<HealthComponent>(goblin).unwrap();
+    ecs.get_component_mut::<HealthComponent>().unwrap().set_value(index, 30);
+
+    let tc = ecs.get_component_mut::<TransformComponent>().unwrap();
+    let index = tc.component.find_index(&sword).unwrap();
+    tc.set_translation(index, Vector3::new(1.0, 0.0, 0.0));
+
+    (goblin, sword)
+}
+
+#[test]
+fn spawn_copies_subtree() {
+    let mut ecs = ECS::new().unwrap();
+    let (goblin, _) = create_goblin(&mut ecs);
+
+    let prefab = ecs.create_prefab(goblin).unwrap();
+    assert_eq!(2, prefab.len());
+    assert_eq!(None, prefab.entities[0].parent);
+    assert_eq!(Some(0), prefab.entities[1].parent);
+
+    let copy = ecs.spawn_prefab(&prefab, &Overrides::new()).unwrap();
+    assert_ne!(goblin, copy);
+    assert_eq!(4, ecs.count());
+    assert_eq!(30, health_of(&ecs, copy));
+
+    // Names stay unique, tags are copied as is
+    assert_eq!("goblin_0", name_of(&ecs, copy));
+    let nc = ecs.get_component::<NameComponent>().unwrap();
+    assert!(nc.has_tag(nc.component.find_index(&copy).unwrap(), String::from("enemy")));
+
+    let children = children_of(&ecs, copy);
+    assert_eq!(1, children.len());
+    assert_eq!("sword_0", name_of(&ecs, children[0]));
+
+    ecs.propagate_transforms();
+    let world = ecs.world_matrix(children[0]).unwrap();
+    assert_eq!(1.0, world.w.x);
+}
+
+#[test]
+fn missing_components_are_detached() {
+    let mut ecs = ECS::new().unwrap();
+    let (goblin, sword) = create_goblin(&mut ecs);
+    ecs.detach_component::<TransformComponent>(sword).unwrap();
+
+    let prefab = ecs.create_prefab(goblin).unwrap();
+    let copy = ecs.spawn_prefab(&prefab, &Overrides::new()).unwrap();
+    let child = children_of(&ecs, copy)[0];
+
+    assert!(ecs.get_component::<TransformComponent>().unwrap().component.find_index(&child).is_none());
+    assert!(ecs.get_component::<HealthComponent>().unwrap().component.find_index(&child).is_none());
+}
+
+#[test]
+fn overrides_apply_per_instance() {
+    let mut ecs = ECS::new().unwrap();
+    let (goblin, _) = create_goblin(&mut ecs);
+    let prefab = ecs.create_prefab(goblin).unwrap();
+
+    let mut overrides = Overrides::new();
+    overrides.set::<HealthComponent>(0, "value", 99).unwrap();
+    overrides.set::<HealthComponent>(0, "value", 50).unwrap();
+    assert_eq!(1, overrides.len());
+
+    let strong = ecs.spawn_prefab(&prefab, &overrides).unwrap();
+    let plain = ecs.spawn_prefab(&prefab, &Overrides::new()).unwrap();
+    assert_eq!(50, health_of(&ecs, strong));
+    assert_eq!(30, health_of(&ecs, plain));
+
+    // Unknown columns leave nothing behind
+    let mut overrides = Overrides::new();
+    overrides.set::<HealthComponent>(0, "armor", 1).unwrap();
+    let count = ecs.count();
+    assert!(ecs.spawn_prefab(&prefab, &overrides).is_err());
+    assert_eq!(count, ecs.count());
+}
+
+#[test]
+fn reload_propagates_to_instances() {
+    let path = temp_path("reload_propagates_to_instances.json");
+    let mut ecs = ECS::new().unwrap();
+    let (goblin, _) = create_goblin(&mut ecs);
+    ecs.save_prefab(goblin, &path).unwrap();
+
+    let mut overrides = Overrides::new();
+    overrides.set::<NameComponent>(0, "name", (0, "boss")).unwrap();
+    let boss = ecs.instantiate_prefab_with(&path, overrides).unwrap();
+    let grunt = ecs.instantiate_prefab(&path).unwrap();
+    assert_eq!("boss", name_of(&ecs, boss));
+    assert_eq!("goblin_0", name_of(&ecs, grunt));
+    assert_eq!(2, ecs.prefab_instances(&path).len());
+
+    let index = ecs.get_component::<HealthComponent>().unwrap().component.find_index(&goblin).unwrap();
+    ecs.get_component_mut::<HealthComponent>().unwrap().set_value(index, 80);
+    ecs.save_prefab(goblin, &path).unwrap();
+
+    assert_eq!(2, ecs.reload_prefab(&path).unwrap());
+    assert_eq!(80, health_of(&ecs, boss));
+    assert_eq!(80, health_of(&ecs, grunt));
+    assert_eq!("boss", name_of(&ecs, boss));
+    assert_eq!("goblin_0", name_of(&ecs, grunt));
+
+    // Removed instances are forgotten
+    ecs.remove_entity_recursive(grunt).unwrap();
+    assert_eq!(1, ecs.reload_prefab(&path).unwrap());
+    assert_eq!(vec![boss], ecs.prefab_instances(&path));
+
+    // Instances can't follow a change in the prefab's entities
+    ecs.save_prefab(children_of(&ecs, goblin)[0], &path).unwrap();
+    assert!(ecs.reload_prefab(&path).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn old_prefabs_rename_builtin_hashes() {
+    let path = temp_path("old_prefabs_rename_builtin_hashes.json");
+    let mut ecs = ECS::new().unwrap();
+    let (goblin, sword) = create_goblin(&mut ecs);
+
+    // Rewritten the way prefabs were saved before hashes came from component ids
+    let legacy = |path: &str| {
+        let mut value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        value["version"] = serde_json::Value::from(1);
+        let renamed = [
+            (ComponentManager::type_hash::<NameComponent>(), hash::get(&String::from(std::any::type_name::<NameComponent>()))),
+            (ComponentManager::type_hash::<HierarchyComponent>(), hash::get(&String::from(std::any::type_name::<HierarchyComponent>()))),
+            (ComponentManager::type_hash::<TransformComponent>(), hash::get(&String::from(std::any::type_name::<TransformComponent>()))),
+            (ComponentManager::type_hash::<HealthComponent>(), hash::get(&String::from(std::any::type_name::<HealthComponent>()))),
+        ];
+
+        for e in value["data"]["entities"].as_array_mut().unwrap() {
+            let components = e["components"].as_object_mut().unwrap();
+            for (new, old) in renamed {
+                if let Some(row) = components.remove(&new.to_string()) {
+                    components.insert(old.to_string(), row);
+                }
+            }
+        }
+        std::fs::write(path, value.to_string()).unwrap();
+    };
+
+    ecs.save_prefab(sword, &path).unwrap();
+    legacy(&path);
+    let copy = ecs.instantiate_prefab(&path).unwrap();
+    assert_eq!("sword_0", name_of(&ecs, copy));
+
+    // Only the built in components can be renamed
+    ecs.save_prefab(goblin, &path).unwrap();
+    legacy(&path);
+    assert!(ecs.instantiate_prefab(&path).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
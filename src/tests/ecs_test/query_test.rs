@@ -0,0 +1,147 @@
+use crate::system::ecs::{ECS, entity::Entity};
+use crate::system::ecs::query::{With, Without, Added, Changed};
+use crate::system::ecs::component_manager::component::{name_component::NameComponent, hierarchy_component::HierarchyComponent};
+use super::fixture::create_world;
+
+fn create_named(count: u64) -> (ECS, Vec<Entity>) {
+    let mut ecs = create_world(Vec::new());
+    let mut list = Vec::new();
+
+    for i in 0..count {
+        let e = ecs.create_entity().unwrap();
+        let index = ecs.get_component::<NameComponent>().unwrap().component.find_index(&e).unwrap();
+        ecs.get_component_mut::<NameComponent>().unwrap().set_name(index, format!("entity{i}"));
+        list.push(e);
+    }
+
+    (ecs, list)
+}
+
+#[test]
+fn query_rows() {
+    let (ecs, list) = create_named(10);
+
+    let query = ecs.query::<(&NameComponent, &HierarchyComponent)>().unwrap();
+    assert_eq!(10, query.count());
+
+    for (e, (name, hierarchy)) in query.iter() {
+        assert!(list.contains(&e));
+        assert_eq!(e, name.entity);
+        assert_eq!(e, hierarchy.entity);
+        assert!(name.name().1.starts_with("entity"));
+        assert!(hierarchy.parent().is_none());
+    }
+}
+
+#[test]
+fn query_mut_rows() {
+    let (mut ecs, list) = create_named(10);
+
+    let mut query = ecs.query_mut::<(&mut NameComponent, &HierarchyComponent)>().unwrap();
+    query.for_each(|_, (mut name, _)| {
+        assert!(name.add_tag(String::from("player")));
+    });
+
+    let query = ecs.query::<(&NameComponent,)>().unwrap();
+    for e in list {
+        let (name,) = query.get_ref(e).unwrap();
+        assert!(name.tags().contains(&(crate::util::hash::get(&String::from("player")), String::from("player"))));
+    }
+}
+
+#[test]
+fn query_filters() {
+    let (mut ecs, list) = create_named(10);
+
+    for e in list.iter().take(4) {
+        assert!(ecs.detach_component::<HierarchyComponent>(*e).is_ok());
+    }
+
+    let with = ecs.query_filtered::<(&NameComponent,), With<HierarchyComponent>>().unwrap();
+    assert_eq!(6, with.count());
+
+    let without = ecs.query_filtered::<(&NameComponent,), Without<HierarchyComponent>>().unwrap();
+    assert_eq!(4, without.count());
+    for (e, _) in without.iter() {
+        assert!(list[..4].contains(&e));
+    }
+
+    let optional = ecs.query::<(&NameComponent, Option<&HierarchyComponent>)>().unwrap();
+    let mut missing = 0;
+    for (_, (_, hierarchy)) in optional.iter() {
+        if hierarchy.is_none() {
+            missing += 1;
+        }
+    }
+    assert_eq!(4, missing);
+}
+
+#[test]
+fn query_conflict() {
+    let (mut ecs, _) = create_named(1);
+
+    assert!(ecs.query_mut::<(&mut NameComponent, &NameComponent)>().is_err());
+    assert!(ecs.query_mut::<(&NameComponent, &NameComponent)>().is_ok());
+}
+
+#[test]
+fn query_stale_entity() {
+    let (mut ecs, list) = create_named(2);
+
+    assert!(ecs.remove_entity(list[0]).is_ok());
+    let query = ecs.query::<(&NameComponent,)>().unwrap();
+    assert!(query.get_ref(list[0]).is_none());
+    assert!(query.get_ref(list[1]).is_some());
+}
+
+#[test]
+fn change_filters() {
+    let (mut ecs, list) = create_named(4);
+
+    let since = ecs.advance_tick();
+    let late = ecs.create_entity().unwrap();
+    {
+        let mut query = ecs.query_mut::<(&mut NameComponent,)>().unwrap();
+        let (mut name,) = query.get(list[1]).unwrap();
+        name.set_name(String::from("renamed"));
+    }
+
+    let added = ecs.query_filtered_since::<(&NameComponent,), Added<NameComponent>>(since - 1).unwrap();
+    let entities = added.entities();
+    assert_eq!(vec![late], entities);
+
+    let changed = ecs.query_filtered_since::<(&NameComponent,), Changed<NameComponent>>(since - 1).unwrap();
+    let mut entities = changed.entities();
+    entities.sort_by_key(|e| e.id);
+    assert_eq!(vec![list[1], late], entities);
+
+    // Nothing changed after the current tick yet
+    let tick = ecs.tick();
+    let changed = ecs.query_filtered_since::<(&NameComponent,), Changed<NameComponent>>(tick).unwrap();
+    assert_eq!(0, changed.count());
+
+    // Reading a row through a mutable query doesn't count as a change
+    ecs.advance_tick();
+    let tick = ecs.tick();
+    ecs.query_mut::<(&mut NameComponent,)>().unwrap().for_each(|_, (name,)| {
+        assert!(name.tags().is_empty());
+    });
+    let changed = ecs.query_filtered_since::<(&NameComponent,), Changed<NameComponent>>(tick - 1).unwrap();
+    assert_eq!(0, changed.count());
+}
+
+#[test]
+fn removed_rows() {
+    let (mut ecs, list) = create_named(3);
+
+    let since = ecs.tick();
+    ecs.advance_tick();
+    assert!(ecs.remove_entity(list[0]).is_ok());
+
+    assert_eq!(vec![list[0]], ecs.removed_since::<NameComponent>(since));
+    assert!(ecs.removed_since::<NameComponent>(ecs.tick()).is_empty());
+
+    let nc = ecs.get_component::<NameComponent>().unwrap();
+    assert!(nc.component.get_ticks(&list[0]).is_none());
+    assert!(nc.component.get_ticks(&list[1]).is_some());
+}
@@ -0,0 +1,156 @@
+use std::any::Any;
+use std::sync::{Arc, Mutex};
+use serde::{Serialize, Deserialize};
+
+use crate::system::ecs::ECS;
+use crate::error::Error;
+use crate::system::ecs::resources::{Resources, SavedResource};
+use crate::system::ecs::schedule::{Access, Schedule, Stage, System, SystemContext, ExecutionMode};
+
+#[derive(Serialize, Deserialize)]
+struct Time {
+    elapsed: f32,
+    frames: u32,
+}
+
+#[typetag::serde]
+impl SavedResource for Time {
+    fn get_hash(&self) -> u64 {
+        Resources::type_hash::<Time>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+struct Gravity(f32);
+
+#[test]
+fn insert_and_get() {
+    let mut ecs = ECS::new().unwrap();
+    assert!(ecs.resource::<Gravity>().is_none());
+
+    ecs.insert_resource(Gravity(9.8));
+    ecs.insert_saved_resource(Time { elapsed: 0.0, frames: 0 });
+    assert!(ecs.has_resource::<Gravity>());
+    assert_eq!(9.8, ecs.resource::<Gravity>().unwrap().0);
+
+    ecs.resource_mut::<Time>().unwrap().frames += 1;
+    assert_eq!(1, ecs.resource::<Time>().unwrap().frames);
+
+    ecs.insert_resource(Gravity(1.6));
+    assert_eq!(1.6, ecs.resource::<Gravity>().unwrap().0);
+
+    assert!(ecs.remove_resource::<Gravity>());
+    assert!(!ecs.remove_resource::<Gravity>());
+    assert!(ecs.resource::<Gravity>().is_none());
+}
+
+#[test]
+fn only_saved_resources_serialize() {
+    let mut ecs = ECS::new().unwrap();
+    ecs.insert_resource(Gravity(9.8));
+    ecs.insert_saved_resource(Time { elapsed: 2.5, frames: 3 });
+
+    let json = serde_json::to_string(&ecs).unwrap();
+    let ecs: ECS = serde_json::from_str(&json).unwrap();
+
+    assert!(ecs.resource::<Gravity>().is_none());
+    let time = ecs.resource::<Time>().unwrap();
+    assert_eq!(2.5, time.elapsed);
+    assert_eq!(3, time.frames);
+}
+
+struct Tick {
+    name: &'static str,
+    seen: Arc<Mutex<Vec<u32>>>,
+}
+
+impl System for Tick {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn access(&self) -> Access {
+        Access::new().write_resource::<Time>().read_resource::<Gravity>()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        let gravity = ctx.resource::<Gravity>().map(|g| g.0).unwrap_or(0.0);
+        let time = match ctx.resource_mut::<Time>() {
+            Some(val) => val,
+            None => return Err(Error::InvalidData(String::from("no time")))
+        };
+
+        time.frames += 1;
+        time.elapsed += gravity;
+        self.seen.lock().unwrap().push(time.frames);
+
+        Ok(())
+    }
+}
+
+// Declares nothing, so it must not see any resource
+struct Peek;
+
+impl System for Peek {
+    fn name(&self) -> &str {
+        "peek"
+    }
+
+    fn access(&self) -> Access {
+        Access::new()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        if ctx.resource::<Time>().is_some() || ctx.resource_mut::<Gravity>().is_some() {
+            return Err(Error::InvalidData(String::from("undeclared resource")))
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn systems_access_resources() {
+    for mode in [ExecutionMode::Serial, ExecutionMode::Parallel { threads: 2 }] {
+        let mut ecs = ECS::new().unwrap();
+        ecs.set_execution_mode(mode);
+        ecs.insert_resource(Gravity(2.0));
+        ecs.insert_saved_resource(Time { elapsed: 0.0, frames: 0 });
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        ecs.add_system(Stage::Update, Box::new(Tick { name: "first", seen: seen.clone() })).unwrap();
+        ecs.add_system(Stage::Update, Box::new(Tick { name: "second", seen: seen.clone() })).unwrap();
+        ecs.add_system(Stage::Update, Box::new(Peek)).unwrap();
+
+        assert!(ecs.run_systems(0.0).is_empty());
+        assert_eq!(vec![1, 2], *seen.lock().unwrap());
+        assert_eq!(4.0, ecs.resource::<Time>().unwrap().elapsed);
+    }
+}
+
+#[test]
+fn resource_conflicts() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut schedule = Schedule::new();
+    schedule.add_system(Stage::Update, Box::new(Tick { name: "first", seen: seen.clone() })).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Tick { name: "second", seen: seen.clone() })).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Peek)).unwrap();
+
+    let conflicts = schedule.conflicts().unwrap();
+    assert_eq!(1, conflicts.len());
+    assert!(conflicts[0].components.is_empty());
+    assert_eq!(vec![Resources::type_hash::<Time>()], conflicts[0].resources);
+
+    // Writers of the same resource never share a batch
+    assert_eq!(vec![
+        vec![String::from("first"), String::from("peek")],
+        vec![String::from("second")],
+    ], schedule.run_batches(Stage::Update).unwrap());
+}
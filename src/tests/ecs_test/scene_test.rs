@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use crate::system::ecs::{ECS, entity::Entity};
+use crate::system::ecs::component_manager::signature_index::StorageMode;
+use crate::util::serialize::Format;
+use crate::system::ecs::component_manager::component::{
+    name_component::NameComponent,
+    hierarchy_component::HierarchyComponent};
+use crate::tests::fixture::temp_path;
+use super::fixture::{HealthComponent, create_world, health_of};
+
+// A "root" with two children, the first of which has a child of its own
+fn create_tree(ecs: &mut ECS) -> Vec<Entity> {
+    let list: Vec<Entity> = (0..4).map(|_| ecs.create_entity().unwrap()).collect();
+    ecs.reparent_subtree(list[1], Some(list[0])).unwrap();
+    ecs.reparent_subtree(list[2], Some(list[0])).unwrap();
+    ecs.reparent_subtree(list[3], Some(list[1])).unwrap();
+
+    for (i, e) in list.iter().enumerate() {
+        let nc = ecs.get_component_mut::<NameComponent>().unwrap();
+        let index = nc.component.find_index(e).unwrap();
+        nc.set_name(index, format!("node{i}"));
+
+        let index = ecs.attach_component::<HealthComponent>(*e).unwrap();
+        ecs.get_component_mut::<HealthComponent>().unwrap().set_value(index, i as i64);
+    }
+
+    list
+}
+
+fn parent_of(ecs: &ECS, e: Entity) -> Option<Entity> {
+    let hc = ecs.get_component::<HierarchyComponent>().unwrap();
+    hc.get_parent(hc.component.find_index(&e).unwrap())
+}
+
+#[test]
+fn load_restores_ids() {
+    let path = temp_path("load_restores_ids.json");
+    let mut ecs = create_world(vec![Box::new(HealthComponent::new())]);
+    let list = create_tree(&mut ecs);
+    ecs.save_scene(&path).unwrap();
+
+    let mut loaded = create_world(vec![Box::new(HealthComponent::new())]);
+    loaded.create_entity().unwrap();
+    loaded.create_entity().unwrap();
+    loaded.create_entity().unwrap();
+    loaded.create_entity().unwrap();
+    loaded.create_entity().unwrap();
+    loaded.load_scene(&path).unwrap();
+
+    assert_eq!(4, loaded.count());
+    for (i, e) in list.iter().enumerate() {
+        assert!(loaded.does_entity_exist(*e));
+        assert_eq!(i as i64, health_of(&loaded, *e));
+    }
+    assert_eq!(Some(list[1]), parent_of(&loaded, list[3]));
+    assert_eq!(Some(list[3]), loaded.get_component::<NameComponent>().unwrap().find_by_name("node3"));
+
+    // Entities that were there before are gone, new ones don't reuse loaded ids
+    let e = loaded.create_entity().unwrap();
+    assert!(!list.iter().any(|l| l.id == e.id));
+    assert_eq!(5, loaded.count());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn merge_remaps_references() {
+    let path = temp_path("merge_remaps_references.json");
+    let mut ecs = create_world(vec![Box::new(HealthComponent::new())]);
+    let list = create_tree(&mut ecs);
+    ecs.save_scene(&path).unwrap();
+
+    let first = ecs.merge_scene(&path).unwrap();
+    let second = ecs.merge_scene(&path).unwrap();
+    assert_eq!(12, ecs.count());
+
+    for map in [&first, &second] {
+        assert_eq!(4, map.len());
+        for (i, e) in list.iter().enumerate() {
+            let mapped = map[e];
+            assert!(!list.contains(&mapped));
+            assert_eq!(i as i64, health_of(&ecs, mapped));
+        }
+
+        assert_eq!(None, parent_of(&ecs, map[&list[0]]));
+        assert_eq!(Some(map[&list[0]]), parent_of(&ecs, map[&list[1]]));
+        assert_eq!(Some(map[&list[1]]), parent_of(&ecs, map[&list[3]]));
+
+        let nc = ecs.get_component::<NameComponent>().unwrap();
+        assert_eq!(Some(list[0]), nc.find_by_name("node0"));
+
+        let hc = ecs.get_component::<HierarchyComponent>().unwrap();
+        let children = hc.get_children(hc.component.find_index(&map[&list[0]]).unwrap()).unwrap();
+        assert!(children.contains(&map[&list[1]]) && children.contains(&map[&list[2]]));
+    }
+
+    // The original tree is untouched
+    assert_eq!(Some(list[1]), parent_of(&ecs, list[3]));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn merge_keeps_signatures_current() {
+    let path = temp_path("merge_keeps_signatures_current.json");
+    let mut ecs = create_world(vec![Box::new(HealthComponent::new())]);
+    create_tree(&mut ecs);
+    ecs.save_scene(&path).unwrap();
+
+    let mut merged = create_world(vec![Box::new(HealthComponent::new())]);
+    merged.set_storage_mode(StorageMode::Indexed);
+    let map: HashMap<Entity, Entity> = merged.merge_scene(&path).unwrap();
+
+    let query = merged.query::<(&HealthComponent, &HierarchyComponent)>().unwrap();
+    assert_eq!(4, query.count());
+    for (e, (health, _)) in query.iter() {
+        assert!(map.values().any(|m| *m == e));
+        assert_eq!(e, health.entity);
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn unknown_components_are_rejected() {
+    let path = temp_path("unknown_components_are_rejected.json");
+    let mut ecs = create_world(vec![Box::new(HealthComponent::new())]);
+    create_tree(&mut ecs);
+    ecs.save_scene(&path).unwrap();
+
+    let mut other = ECS::new().unwrap();
+    let e = other.create_entity().unwrap();
+    assert!(other.load_scene(&path).is_err());
+    assert!(other.merge_scene(&path).is_err());
+    assert!(other.does_entity_exist(e));
+    assert_eq!(1, other.count());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn broken_scenes_leave_the_world_untouched() {
+    let path = temp_path("broken_scenes_leave_the_world_untouched.json");
+    let mut ecs = create_world(vec![Box::new(HealthComponent::new())]);
+    create_tree(&mut ecs);
+    ecs.save_scene(&path).unwrap();
+
+    // The last entity keeps its rows but is no longer part of the scene
+    let mut value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    value.pointer_mut("/data/entity_manager/entities").unwrap().as_array_mut().unwrap().pop();
+    std::fs::write(&path, value.to_string()).unwrap();
+
+    let mut other = create_world(vec![Box::new(HealthComponent::new())]);
+    let e = other.create_entity().unwrap();
+    assert!(other.merge_scene(&path).is_err());
+    assert!(other.load_scene(&path).is_err());
+    assert!(other.does_entity_exist(e));
+    assert_eq!(1, other.count());
+    assert_eq!(0, other.get_component::<HealthComponent>().unwrap().component.entities.len());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn binary_scenes_round_trip() {
+    let json_path = temp_path("binary_scenes_round_trip.json");
+    let binary_path = temp_path("binary_scenes_round_trip.bin");
+    let mut ecs = create_world(vec![Box::new(HealthComponent::new())]);
+    let list = create_tree(&mut ecs);
+    ecs.save_scene(&json_path).unwrap();
+    ecs.save_scene_as(&binary_path, Format::Binary).unwrap();
+
+    let json_len = std::fs::metadata(&json_path).unwrap().len();
+    let binary_len = std::fs::metadata(&binary_path).unwrap().len();
+    assert!(binary_len < json_len);
+
+    let mut loaded = create_world(vec![Box::new(HealthComponent::new())]);
+    loaded.load_scene(&binary_path).unwrap();
+    assert_eq!(4, loaded.count());
+    assert_eq!(3, health_of(&loaded, list[3]));
+    assert_eq!(Some(list[1]), parent_of(&loaded, list[3]));
+
+    let map = loaded.merge_scene(&json_path).unwrap();
+    assert_eq!(8, loaded.count());
+    assert_eq!(Some(map[&list[1]]), parent_of(&loaded, map[&list[3]]));
+
+    std::fs::remove_file(&json_path).unwrap();
+    std::fs::remove_file(&binary_path).unwrap();
+}
+
+#[test]
+fn old_scenes_rename_component_hashes() {
+    // Written before hashes came from component ids, in indexed mode
+    let mut loaded = create_world(vec![Box::new(HealthComponent::new())]);
+    loaded.set_storage_mode(StorageMode::Indexed);
+    let map = loaded.merge_scene("./src/tests/fixtures/scene_v1.json").unwrap();
+    assert_eq!(3, map.len());
+
+    let query = loaded.query::<(&HealthComponent, &NameComponent)>().unwrap();
+    let mut health: Vec<i64> = query.iter().map(|(_, (h, _))| *h.value()).collect();
+    health.sort();
+    assert_eq!(vec![10, 20], health);
+
+    let mut loaded = create_world(vec![Box::new(HealthComponent::new())]);
+    loaded.load_scene("./src/tests/fixtures/scene_v1.json").unwrap();
+    let e = Entity::new(1);
+    assert_eq!(10, health_of(&loaded, e));
+    assert!(loaded.query::<(&HealthComponent, &HierarchyComponent)>().unwrap().get_ref(e).is_some());
+}
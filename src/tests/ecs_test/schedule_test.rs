@@ -0,0 +1,538 @@
+use std::sync::{Arc, Mutex};
+
+use crate::system::ecs::ECS;
+use crate::error::Error;
+use crate::system::ecs::query::Changed;
+use crate::system::ecs::events::EventRegistry;
+use crate::system::ecs::resources::Resources;
+use crate::system::ecs::schedule::{Access, Schedule, Stage, StageData, System, SystemContext, ExecutionMode};
+use crate::system::ecs::component_manager::ComponentManager;
+use crate::system::ecs::component_manager::component::{name_component::NameComponent, hierarchy_component::HierarchyComponent};
+use super::fixture::{PositionComponent, VelocityComponent, HealthComponent};
+
+struct Recorder {
+    name: String,
+    access: Access,
+    after: Vec<String>,
+    before: Vec<String>,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl Recorder {
+    fn new(name: &str, access: Access, log: &Arc<Mutex<Vec<String>>>) -> Self {
+        Self {
+            name: String::from(name),
+            access,
+            after: Vec::new(),
+            before: Vec::new(),
+            log: log.clone(),
+        }
+    }
+
+    fn after(mut self, name: &str) -> Self {
+        self.after.push(String::from(name));
+        self
+    }
+
+    fn before(mut self, name: &str) -> Self {
+        self.before.push(String::from(name));
+        self
+    }
+}
+
+impl System for Recorder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn access(&self) -> Access {
+        self.access.clone()
+    }
+
+    fn after(&self) -> Vec<String> {
+        self.after.clone()
+    }
+
+    fn before(&self) -> Vec<String> {
+        self.before.clone()
+    }
+
+    fn run(&mut self, _ctx: &mut SystemContext) -> Result<(), Error> {
+        self.log.lock().unwrap().push(self.name.clone());
+        Ok(())
+    }
+}
+
+struct TagSystem;
+
+impl System for TagSystem {
+    fn name(&self) -> &str {
+        "tag"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().write::<NameComponent>().read::<HierarchyComponent>()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        let mut query = ctx.query_mut::<(&mut NameComponent, &HierarchyComponent)>()?;
+        query.for_each(|_, (mut name, _)| {
+            name.add_tag(String::from("tagged"));
+        });
+
+        Ok(())
+    }
+}
+
+struct Illegal;
+
+impl System for Illegal {
+    fn name(&self) -> &str {
+        "illegal"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().read::<NameComponent>()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        ctx.query_mut::<(&mut NameComponent,)>()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn run_order() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut schedule = Schedule::new();
+
+    let access = Access::new();
+    schedule.add_system(Stage::PostUpdate, Box::new(Recorder::new("post", access.clone(), &log))).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("b", access.clone(), &log).after("a"))).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("a", access.clone(), &log))).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("c", access.clone(), &log).before("a"))).unwrap();
+    schedule.add_system(Stage::PreUpdate, Box::new(Recorder::new("pre", access.clone(), &log))).unwrap();
+
+    let mut cm = ComponentManager::new();
+    let mut resources = Resources::new();
+    let events = EventRegistry::new();
+    let em = crate::system::ecs::entity_manager::EntityManager::new();
+    for stage in Stage::ALL {
+        let data = StageData {
+            entity_manager: &em,
+            component_manager: &mut cm,
+            resources: &mut resources,
+            events: &events,
+        };
+        let (commands, errors) = schedule.run_stage(stage, 0.0, data);
+        assert!(commands.is_empty());
+        assert!(errors.is_empty());
+    }
+
+    let log = log.lock().unwrap();
+    assert_eq!(vec!["pre", "c", "a", "b", "post"], *log);
+}
+
+#[test]
+fn duplicate_system() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut schedule = Schedule::new();
+
+    assert!(schedule.add_system(Stage::Update, Box::new(Recorder::new("a", Access::new(), &log))).is_ok());
+    let result = schedule.add_system(Stage::Update, Box::new(Recorder::new("a", Access::new(), &log)));
+    assert!(matches!(result, Err(Error::DuplicateSystem(name)) if name == "a"));
+    assert!(schedule.remove_system("a"));
+    assert_eq!(0, schedule.count());
+}
+
+#[test]
+fn ordering_errors() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("a", Access::new(), &log).after("b"))).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("b", Access::new(), &log).after("a"))).unwrap();
+    assert!(matches!(schedule.build(), Err(Error::OrderingCycle(_))));
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("a", Access::new(), &log).after("missing"))).unwrap();
+    assert!(matches!(schedule.build(), Err(Error::UnknownSystem { .. })));
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("a", Access::new(), &log).after("b"))).unwrap();
+    schedule.add_system(Stage::PostUpdate, Box::new(Recorder::new("b", Access::new(), &log))).unwrap();
+    assert!(matches!(schedule.build(), Err(Error::CrossStageOrdering { .. })));
+}
+
+#[test]
+fn report_conflicts() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let write = Access::new().write::<NameComponent>();
+    let read = Access::new().read::<NameComponent>();
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("w1", write.clone(), &log))).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("w2", write.clone(), &log))).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Recorder::new("r1", read.clone(), &log).after("w2"))).unwrap();
+    schedule.add_system(Stage::PostUpdate, Box::new(Recorder::new("w3", write.clone(), &log))).unwrap();
+
+    let conflicts = schedule.conflicts().unwrap();
+    assert_eq!(2, conflicts.len());
+
+    let hash = ComponentManager::type_hash::<NameComponent>();
+    assert!(conflicts.iter().any(|c| c.first == "w1" && c.second == "w2" && c.components == vec![hash]));
+    assert!(conflicts.iter().any(|c| c.first == "w1" && c.second == "r1"));
+}
+
+#[test]
+fn ecs_drives_systems() {
+    let mut ecs = ECS::new().unwrap();
+    for _ in 0..5 {
+        ecs.create_entity().unwrap();
+    }
+
+    ecs.add_system(Stage::Update, Box::new(TagSystem)).unwrap();
+    assert!(ecs.run_systems(0.0).is_empty());
+
+    let query = ecs.query::<(&NameComponent,)>().unwrap();
+    for (_, (name,)) in query.iter() {
+        assert_eq!(1, name.tags().len());
+    }
+}
+
+#[test]
+fn undeclared_access() {
+    let mut ecs = ECS::new().unwrap();
+    ecs.create_entity().unwrap();
+
+    ecs.add_system(Stage::Update, Box::new(Illegal)).unwrap();
+    let errors = ecs.run_systems(0.0);
+    assert_eq!(1, errors.len());
+    assert!(matches!(&errors[0], Error::System { source, .. } if matches!(**source, Error::AccessNotDeclared(_))));
+}
+
+struct Accelerate;
+
+impl System for Accelerate {
+    fn name(&self) -> &str {
+        "accelerate"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().write::<VelocityComponent>()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        ctx.query_mut::<(&mut VelocityComponent,)>()?.for_each(|e, (mut v,)| {
+            let value = v.value() + 1 + (e.id % 3) as i64;
+            v.set_value(value);
+        });
+
+        Ok(())
+    }
+}
+
+struct Integrate;
+
+impl System for Integrate {
+    fn name(&self) -> &str {
+        "integrate"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().read::<VelocityComponent>().write::<PositionComponent>()
+    }
+
+    fn after(&self) -> Vec<String> {
+        vec![String::from("accelerate")]
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        ctx.query_mut::<(&mut PositionComponent, &VelocityComponent)>()?.for_each(|_, (mut p, v)| {
+            let value = p.value() + v.value();
+            p.set_value(value);
+        });
+
+        Ok(())
+    }
+}
+
+struct Heal {
+    threads: Arc<Mutex<Vec<std::thread::ThreadId>>>,
+}
+
+impl System for Heal {
+    fn name(&self) -> &str {
+        "heal"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().write::<HealthComponent>().read::<PositionComponent>()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        self.threads.lock().unwrap().push(std::thread::current().id());
+
+        ctx.query_mut::<(&mut HealthComponent, &PositionComponent)>()?.for_each(|_, (mut h, p)| {
+            let value = h.value() * 2 - p.value();
+            h.set_value(value);
+        });
+
+        Ok(())
+    }
+}
+
+struct Spin {
+    threads: Arc<Mutex<Vec<std::thread::ThreadId>>>,
+}
+
+impl System for Spin {
+    fn name(&self) -> &str {
+        "spin"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().read::<NameComponent>()
+    }
+
+    fn run(&mut self, _ctx: &mut SystemContext) -> Result<(), Error> {
+        self.threads.lock().unwrap().push(std::thread::current().id());
+        Ok(())
+    }
+}
+
+fn physics_world(mode: ExecutionMode, threads: &Arc<Mutex<Vec<std::thread::ThreadId>>>) -> ECS {
+    let mut ecs = ECS::new().unwrap();
+    ecs.add_component(Box::new(PositionComponent::new())).unwrap();
+    ecs.add_component(Box::new(VelocityComponent::new())).unwrap();
+    ecs.add_component(Box::new(HealthComponent::new())).unwrap();
+
+    for i in 0..200 {
+        let e = ecs.create_entity().unwrap();
+        ecs.attach_component::<PositionComponent>(e).unwrap();
+        ecs.attach_component::<HealthComponent>(e).unwrap();
+        if i % 4 != 0 {
+            ecs.attach_component::<VelocityComponent>(e).unwrap();
+        }
+    }
+
+    ecs.set_execution_mode(mode);
+    ecs.add_system(Stage::Update, Box::new(Heal { threads: threads.clone() })).unwrap();
+    ecs.add_system(Stage::Update, Box::new(Integrate)).unwrap();
+    ecs.add_system(Stage::Update, Box::new(Accelerate)).unwrap();
+    ecs.add_system(Stage::Update, Box::new(Spin { threads: threads.clone() })).unwrap();
+
+    ecs
+}
+
+fn snapshot(ecs: &ECS) -> Vec<(u64, i64, i64)> {
+    let query = ecs.query::<(&PositionComponent, &HealthComponent)>().unwrap();
+    let mut list: Vec<(u64, i64, i64)> = query.iter()
+        .map(|(e, (p, h))| (e.id, *p.value(), *h.value()))
+        .collect();
+
+    list.sort();
+    list
+}
+
+#[test]
+fn parallel_batches() {
+    let threads = Arc::new(Mutex::new(Vec::new()));
+    let mut schedule = Schedule::new();
+    schedule.add_system(Stage::Update, Box::new(Heal { threads: threads.clone() })).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Integrate)).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Accelerate)).unwrap();
+    schedule.add_system(Stage::Update, Box::new(Spin { threads: threads.clone() })).unwrap();
+
+    let batches = schedule.run_batches(Stage::Update).unwrap();
+    assert_eq!(vec![
+        vec![String::from("heal"), String::from("accelerate"), String::from("spin")],
+        vec![String::from("integrate")],
+    ], batches);
+}
+
+#[test]
+fn parallel_matches_serial() {
+    let threads = Arc::new(Mutex::new(Vec::new()));
+    let mut serial = physics_world(ExecutionMode::Serial, &threads);
+    let mut parallel = physics_world(ExecutionMode::Parallel { threads: 4 }, &threads);
+
+    for _ in 0..5 {
+        assert!(serial.run_systems(0.016).is_empty());
+        assert!(parallel.run_systems(0.016).is_empty());
+    }
+
+    assert_eq!(snapshot(&serial), snapshot(&parallel));
+}
+
+#[test]
+fn parallel_uses_workers() {
+    let threads = Arc::new(Mutex::new(Vec::new()));
+    let mut ecs = physics_world(ExecutionMode::Parallel { threads: 3 }, &threads);
+    for _ in 0..10 {
+        assert!(ecs.run_systems(0.016).is_empty());
+    }
+
+    // The same few workers run every frame, none of them is the caller
+    let list = threads.lock().unwrap();
+    let workers: std::collections::HashSet<_> = list.iter().collect();
+    assert_eq!(20, list.len());
+    assert!(workers.len() <= 3);
+    assert!(!list.contains(&std::thread::current().id()));
+}
+
+#[test]
+fn serial_uses_caller() {
+    let threads = Arc::new(Mutex::new(Vec::new()));
+    let mut ecs = physics_world(ExecutionMode::Serial, &threads);
+    assert!(ecs.run_systems(0.016).is_empty());
+
+    let list = threads.lock().unwrap();
+    assert!(list.iter().all(|id| *id == std::thread::current().id()));
+}
+
+// Spawns an entity, then panics when `panic` is set
+struct Spawner {
+    name: &'static str,
+    panic: bool,
+}
+
+impl System for Spawner {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn access(&self) -> Access {
+        Access::new().read::<NameComponent>()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        ctx.commands.spawn();
+        if self.panic {
+            panic!("{} gave up", self.name);
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn panics_drop_only_their_commands() {
+    for mode in [ExecutionMode::Serial, ExecutionMode::Parallel { threads: 2 }] {
+        let mut ecs = ECS::new().unwrap();
+        ecs.set_execution_mode(mode);
+        ecs.add_system(Stage::Update, Box::new(Spawner { name: "first", panic: false })).unwrap();
+        ecs.add_system(Stage::Update, Box::new(Spawner { name: "boom", panic: true })).unwrap();
+        ecs.add_system(Stage::Update, Box::new(Spawner { name: "last", panic: false })).unwrap();
+
+        let errors = ecs.run_systems(0.0);
+        assert_eq!(1, errors.len());
+        match &errors[0] {
+            Error::System { name, source } => {
+                assert_eq!("boom", name);
+                assert!(matches!(source.as_ref(), Error::SystemPanicked(msg) if msg == "boom gave up"));
+            },
+            e => panic!("unexpected error {e}")
+        }
+
+        assert_eq!(2, ecs.query::<(&NameComponent,)>().unwrap().count());
+    }
+}
+
+// Counts rows of `PositionComponent` changed since its previous run
+struct Watch {
+    seen: Arc<Mutex<Vec<usize>>>,
+}
+
+impl System for Watch {
+    fn name(&self) -> &str {
+        "watch"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().read::<PositionComponent>()
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        let count = ctx.query_filtered::<(&PositionComponent,), Changed<PositionComponent>>()?.count();
+        self.seen.lock().unwrap().push(count);
+        Ok(())
+    }
+}
+
+// Moves only the first entity, after `watch` has already run
+struct Nudge;
+
+impl System for Nudge {
+    fn name(&self) -> &str {
+        "nudge"
+    }
+
+    fn access(&self) -> Access {
+        Access::new().write::<PositionComponent>()
+    }
+
+    fn after(&self) -> Vec<String> {
+        vec![String::from("watch")]
+    }
+
+    fn run(&mut self, ctx: &mut SystemContext) -> Result<(), Error> {
+        let first = ctx.query::<(&PositionComponent,)>()?.entities().into_iter().min_by_key(|e| e.id);
+        if let Some(e) = first {
+            if let Some((mut p,)) = ctx.query_mut::<(&mut PositionComponent,)>()?.get(e) {
+                let value = p.value() + 1;
+                p.set_value(value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn systems_see_changes_since_last_run() {
+    for mode in [ExecutionMode::Serial, ExecutionMode::Parallel { threads: 2 }] {
+        let mut ecs = ECS::new().unwrap();
+        ecs.add_component(Box::new(PositionComponent::new())).unwrap();
+        ecs.set_execution_mode(mode);
+
+        for _ in 0..5 {
+            let e = ecs.create_entity().unwrap();
+            ecs.attach_component::<PositionComponent>(e).unwrap();
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        ecs.add_system(Stage::Update, Box::new(Watch { seen: seen.clone() })).unwrap();
+        ecs.add_system(Stage::Update, Box::new(Nudge)).unwrap();
+
+        for _ in 0..3 {
+            assert!(ecs.run_systems(0.0).is_empty());
+        }
+
+        // Every row is new on the first run, later runs only see the nudged row
+        assert_eq!(vec![5, 1, 1], *seen.lock().unwrap());
+    }
+}
+
+
+#[test]
+fn changed_matches_between_modes() {
+    let threads = Arc::new(Mutex::new(Vec::new()));
+    let mut runs = Vec::new();
+
+    for mode in [ExecutionMode::Serial, ExecutionMode::Parallel { threads: 4 }] {
+        let mut ecs = physics_world(mode, &threads);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        ecs.add_system(Stage::Update, Box::new(Watch { seen: seen.clone() })).unwrap();
+        ecs.add_system(Stage::Update, Box::new(Nudge)).unwrap();
+
+        for _ in 0..4 {
+            assert!(ecs.run_systems(0.016).is_empty());
+        }
+
+        let seen = seen.lock().unwrap().clone();
+        runs.push(seen);
+    }
+
+    assert_eq!(runs[0], runs[1]);
+}
@@ -0,0 +1,172 @@
+use std::time::Instant;
+
+use crate::system::ecs::{ECS, entity::Entity};
+use crate::system::ecs::query::Without;
+use crate::system::ecs::component_manager::ComponentManager;
+use crate::system::ecs::component_manager::signature_index::StorageMode;
+use super::fixture::{PositionComponent, VelocityComponent, HealthComponent, create_world};
+
+// Every entity has a position, every 2nd a velocity and every 3rd a health
+fn create_mixed(mode: StorageMode, count: usize) -> (ECS, Vec<Entity>) {
+    let mut ecs = create_world(vec![
+        Box::new(PositionComponent::new()),
+        Box::new(VelocityComponent::new()),
+        Box::new(HealthComponent::new())]);
+    ecs.set_storage_mode(mode);
+
+    let mut list = Vec::new();
+    for i in 0..count {
+        let e = ecs.create_entity().unwrap();
+        let index = ecs.attach_component::<PositionComponent>(e).unwrap();
+        ecs.get_component_mut::<PositionComponent>().unwrap().set_value(index, i as i64);
+
+        if i % 2 == 0 {
+            let index = ecs.attach_component::<VelocityComponent>(e).unwrap();
+            ecs.get_component_mut::<VelocityComponent>().unwrap().set_value(index, 10 * i as i64);
+        }
+        if i % 3 == 0 {
+            ecs.attach_component::<HealthComponent>(e).unwrap();
+        }
+
+        list.push(e);
+    }
+
+    (ecs, list)
+}
+
+fn snapshot(ecs: &ECS) -> Vec<(u64, i64, Option<i64>)> {
+    let query = ecs.query::<(&PositionComponent, Option<&VelocityComponent>)>().unwrap();
+    let mut list: Vec<(u64, i64, Option<i64>)> = query.iter()
+        .map(|(e, (p, v))| (e.id, *p.value(), v.map(|v| *v.value())))
+        .collect();
+    list.sort();
+
+    list
+}
+
+#[test]
+fn entities_grouped_by_component_set() {
+    let (ecs, list) = create_mixed(StorageMode::Indexed, 12);
+    let index = ecs.signature_index().unwrap();
+
+    // {p}, {p, v}, {p, h}, {p, v, h}, each with the built-in components
+    assert_eq!(4, index.iter().filter(|a| !a.is_empty()).count());
+
+    let position = ComponentManager::type_hash::<PositionComponent>();
+    let velocity = ComponentManager::type_hash::<VelocityComponent>();
+    let health = ComponentManager::type_hash::<HealthComponent>();
+
+    let both = index.find(list[0]).unwrap();
+    assert!(both.contains(position) && both.contains(velocity) && both.contains(health));
+    assert_eq!(2, both.len());
+    assert_eq!(12, index.matching(&[position]).map(|a| a.len()).sum::<usize>());
+    assert_eq!(6, index.matching(&[velocity]).map(|a| a.len()).sum::<usize>());
+
+    let row = ecs.get_component::<PositionComponent>().unwrap().component.find_index(&list[5]).unwrap();
+    assert_eq!(Some(row), index.row(list[5], position));
+}
+
+#[test]
+fn sparse_and_indexed_queries_agree() {
+    let (sparse, _) = create_mixed(StorageMode::Sparse, 30);
+    let (indexed, _) = create_mixed(StorageMode::Indexed, 30);
+
+    assert!(sparse.signature_index().is_none());
+    assert_eq!(snapshot(&sparse), snapshot(&indexed));
+
+    let count = |ecs: &ECS| ecs.query_filtered::<(&PositionComponent,), Without<HealthComponent>>()
+        .unwrap()
+        .count();
+    assert_eq!(20, count(&sparse));
+    assert_eq!(count(&sparse), count(&indexed));
+}
+
+#[test]
+fn rows_follow_swap_remove() {
+    let (mut sparse, list) = create_mixed(StorageMode::Sparse, 20);
+    let (mut indexed, _) = create_mixed(StorageMode::Indexed, 20);
+
+    for ecs in [&mut sparse, &mut indexed] {
+        ecs.detach_component::<PositionComponent>(list[0]).unwrap();
+        ecs.detach_component::<VelocityComponent>(list[4]).unwrap();
+        ecs.remove_entity(list[7]).unwrap();
+        ecs.attach_component::<HealthComponent>(list[8]).unwrap();
+    }
+
+    assert_eq!(snapshot(&sparse), snapshot(&indexed));
+
+    let mut query = indexed.query_mut::<(&mut PositionComponent, &VelocityComponent)>().unwrap();
+    query.for_each(|e, (mut p, v)| {
+        assert_eq!(e, p.entity);
+        assert_eq!(e, v.entity);
+        assert!(p.set_value(*v.value()));
+    });
+
+    let query = indexed.query::<(&PositionComponent, &VelocityComponent)>().unwrap();
+    assert_eq!(8, query.count());
+    for (_, (p, v)) in query.iter() {
+        assert_eq!(p.value(), v.value());
+    }
+}
+
+#[test]
+fn switching_modes_indexes_existing_rows() {
+    let (mut ecs, _) = create_mixed(StorageMode::Sparse, 10);
+    let expected = snapshot(&ecs);
+
+    ecs.set_storage_mode(StorageMode::Indexed);
+    assert_eq!(StorageMode::Indexed, ecs.storage_mode());
+    assert_eq!(10, ecs.signature_index().unwrap().iter().map(|a| a.len()).sum::<usize>());
+    assert_eq!(expected, snapshot(&ecs));
+
+    let json = serde_json::to_string(&ecs).unwrap();
+    let ecs: ECS = serde_json::from_str(&json).unwrap();
+    assert_eq!(StorageMode::Indexed, ecs.storage_mode());
+    assert_eq!(expected, snapshot(&ecs));
+
+    let (mut ecs, _) = create_mixed(StorageMode::Indexed, 10);
+    ecs.set_storage_mode(StorageMode::Sparse);
+    assert!(ecs.signature_index().is_none());
+    assert_eq!(expected, snapshot(&ecs));
+}
+
+#[test]
+fn joins_match_between_modes() {
+    let mut results = Vec::new();
+    for mode in [StorageMode::Sparse, StorageMode::Indexed] {
+        let (mut ecs, _) = create_mixed(mode, 1_000);
+        let mut query = ecs.query_mut::<(&mut PositionComponent, &VelocityComponent)>().unwrap();
+        let mut visited = 0;
+        query.for_each(|_, (mut p, v)| {
+            p.set_value(*p.value() + *v.value());
+            visited += 1;
+        });
+
+        assert_eq!(500, visited);
+        results.push(snapshot(&ecs));
+    }
+
+    assert_eq!(results[0], results[1]);
+    assert!(results[0].iter().all(|(_, p, v)| v.is_none_or(|v| *p == v + v / 10)));
+}
+
+// Run with `cargo test --release -- --ignored storage_modes`
+#[test]
+#[ignore = "benchmark"]
+fn bench_storage_modes() {
+    let mut timings = Vec::new();
+    for mode in [StorageMode::Sparse, StorageMode::Indexed] {
+        let (mut ecs, _) = create_mixed(mode, 100_000);
+
+        let start = Instant::now();
+        for _ in 0..10 {
+            let mut query = ecs.query_mut::<(&mut PositionComponent, &VelocityComponent)>().unwrap();
+            query.for_each(|_, (mut p, v)| {
+                p.set_value(*p.value() + *v.value());
+            });
+        }
+        timings.push(start.elapsed());
+    }
+
+    assert!(timings[1] <= timings[0], "indexed joins are slower than sparse ones: {:?}", timings);
+}
@@ -0,0 +1,6 @@
+// Per process file path in the temp directory, for tests that write files.
+// `name` includes the extension, the format is always detected from the contents.
+pub fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("iguana_eye_{}_{name}", std::process::id()));
+    String::from(path.to_str().unwrap())
+}
@@ -0,0 +1,193 @@
+use cgmath::{Point3, Vector4, Matrix4, InnerSpace, MetricSpace};
+use winit::event::{VirtualKeyCode, ModifiersState, KeyboardInput, ElementState};
+
+use crate::graphics::camera::{Camera, Projection};
+use crate::graphics::camera_controller::{self, CameraController, ControlMode};
+use crate::system::input::Input;
+
+fn press(input: &mut Input, code: VirtualKeyCode) {
+    input.handle_modifiers(&ModifiersState::empty());
+    input.handle_kb_input(&KeyboardInput {
+        state: ElementState::Pressed,
+        virtual_keycode: Some(code),
+        scancode: 0,
+        modifiers: ModifiersState::empty()
+    });
+}
+
+fn bound_input() -> Input {
+    let mut input = Input::new();
+    CameraController::bind(&mut input);
+
+    return input;
+}
+
+fn perspective_camera() -> Camera {
+    let mut camera = Camera::with_projection(Projection::perspective(60.0), 800.0, 600.0);
+    camera.eye = Point3::new(0.0, 0.0, 10.0);
+    camera.target = Point3::new(0.0, 0.0, 0.0);
+
+    return camera;
+}
+
+fn clip(camera: &Camera, point: Point3<f32>) -> Vector4<f32> {
+    return camera.view_projection() * point.to_homogeneous();
+}
+
+#[test]
+fn perspective_shrinks_with_distance() {
+    let camera = perspective_camera();
+
+    let near = clip(&camera, Point3::new(1.0, 0.0, 5.0));
+    let far = clip(&camera, Point3::new(1.0, 0.0, -5.0));
+
+    assert!(near.x / near.w > far.x / far.w);
+    assert!(far.z / far.w > near.z / near.w);
+    assert!((0.0..=1.0).contains(&(near.z / near.w)));
+}
+
+// OpenGL to wgpu conversion before it was fixed, the z row was transposed into w
+const OLD_OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+);
+
+#[test]
+fn clip_depth_spans_near_to_far() {
+    let camera = perspective_camera();
+    let near = Point3::new(0.0, 0.0, 9.9).to_homogeneous();
+    let far = Point3::new(0.0, 0.0, -990.0).to_homogeneous();
+    let depth = |v: Vector4<f32>| v.z / v.w;
+
+    // wgpu clips depth to 0..1, the near plane maps to 0 and the far plane to 1
+    let vp = camera.view_projection();
+    assert!(depth(vp * near).abs() < 1e-3);
+    assert!((depth(vp * far) - 1.0).abs() < 1e-3);
+
+    // The old matrix put the near plane at -1, clipping it, and the far plane at 1/3
+    let old = OLD_OPENGL_TO_WGPU_MATRIX * camera.projection_matrix() * camera.view_matrix();
+    assert!((depth(old * near) + 1.0).abs() < 1e-3);
+    assert!((depth(old * far) - 1.0 / 3.0).abs() < 1e-3);
+}
+
+#[test]
+fn orthographic_ignores_distance() {
+    let mut camera = perspective_camera();
+    camera.set_projection(Projection::orthographic());
+
+    let near = clip(&camera, Point3::new(100.0, 0.0, 5.0));
+    let far = clip(&camera, Point3::new(100.0, 0.0, -5.0));
+
+    assert!(!camera.projection.is_perspective());
+    assert!((near.x / near.w - far.x / far.w).abs() < 1e-5);
+}
+
+#[test]
+fn resize_updates_aspect() {
+    let mut camera = perspective_camera();
+    let before = camera.projection_matrix();
+
+    camera.handle_resize(600.0, 600.0);
+
+    assert_eq!(camera.aspect(), 1.0);
+    assert_ne!(before, camera.projection_matrix());
+}
+
+#[test]
+fn camera_state_round_trips() {
+    let mut camera = perspective_camera();
+    camera.eye = Point3::new(1.0, 2.0, 3.0);
+
+    let json = serde_json::to_string(&camera).unwrap();
+    let loaded: Camera = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(loaded.projection, camera.projection);
+    assert_eq!(loaded.eye, camera.eye);
+    assert_eq!(loaded.target, camera.target);
+    assert_eq!(loaded.size(), (800.0, 600.0));
+    assert!(loaded.camera_buffer.is_none());
+
+    let controller = CameraController::new(ControlMode::Orbit);
+    let json = serde_json::to_string(&controller).unwrap();
+    assert_eq!(serde_json::from_str::<CameraController>(&json).unwrap(), controller);
+}
+
+#[test]
+fn fly_moves_eye_and_target() {
+    let mut camera = perspective_camera();
+    let mut input = bound_input();
+    press(&mut input, VirtualKeyCode::W);
+
+    CameraController::new(ControlMode::Fly).update(&mut camera, &input, 0.1);
+
+    assert_eq!(camera.eye, Point3::new(0.0, 0.0, 9.0));
+    assert_eq!(camera.target, Point3::new(0.0, 0.0, -1.0));
+}
+
+#[test]
+fn orbit_keeps_distance() {
+    let mut camera = perspective_camera();
+    let mut input = bound_input();
+    press(&mut input, VirtualKeyCode::D);
+    press(&mut input, VirtualKeyCode::E);
+
+    CameraController::new(ControlMode::Orbit).update(&mut camera, &input, 0.5);
+
+    assert!(camera.eye.y > 0.0);
+    assert!(camera.eye.x.abs() > 0.1);
+    assert!((camera.eye.distance(camera.target) - 10.0).abs() < 1e-4);
+    assert_eq!(camera.target, Point3::new(0.0, 0.0, 0.0));
+}
+
+#[test]
+fn orbit_stops_short_of_the_pole() {
+    let mut camera = perspective_camera();
+    let mut input = bound_input();
+    press(&mut input, VirtualKeyCode::E);
+
+    let controller = CameraController::new(ControlMode::Orbit);
+    for _ in 0..10 {
+        controller.update(&mut camera, &input, 1.0);
+    }
+
+    let offset = (camera.eye - camera.target).normalize();
+    assert!(offset.y < 1.0);
+    assert!(offset.dot(camera.up) > 0.99);
+    assert!(camera.view_projection().x.x.is_finite());
+}
+
+#[test]
+fn pan_zoom_scales_orthographic() {
+    let mut camera = Camera::with_projection(Projection::orthographic(), 800.0, 600.0);
+    let mut input = bound_input();
+    press(&mut input, VirtualKeyCode::W);
+    press(&mut input, VirtualKeyCode::D);
+
+    CameraController::new(ControlMode::PanZoom).update(&mut camera, &input, 0.5);
+
+    match camera.projection {
+        Projection::Orthographic { scale, .. } => assert_eq!(scale, 0.5),
+        _ => panic!("projection changed mode")
+    }
+    assert_eq!(camera.eye.z, -1.0);
+    assert_eq!(camera.eye.x - camera.target.x, 0.0);
+    assert!(camera.target.x.abs() > 0.0);
+}
+
+#[test]
+fn bind_keeps_existing_hotkeys() {
+    let mut input = Input::new();
+    input.keyboard.add_hotkey(
+        String::from(camera_controller::FORWARD),
+        crate::system::input::Key::new(VirtualKeyCode::Up, ModifiersState::empty())
+    );
+    CameraController::bind(&mut input);
+
+    press(&mut input, VirtualKeyCode::W);
+    assert!(!input.key_state(String::from(camera_controller::FORWARD)));
+
+    press(&mut input, VirtualKeyCode::Up);
+    assert!(input.key_state(String::from(camera_controller::FORWARD)));
+}
@@ -0,0 +1,45 @@
+use crate::graphics::depth::{DepthState, StencilState, Compare, StencilOp, DEPTH_FORMAT};
+use crate::graphics::renderable::Vertex;
+
+#[test]
+fn descriptor_follows_state() {
+    let desc = DepthState::opaque().descriptor();
+    assert_eq!(DEPTH_FORMAT, desc.format);
+    assert!(desc.depth_write_enabled);
+    assert_eq!(wgpu::CompareFunction::Less, desc.depth_compare);
+    assert!(!desc.stencil.is_enabled());
+
+    let desc = DepthState { test: false, ..DepthState::read_only() }.descriptor();
+    assert!(!desc.depth_write_enabled);
+    assert_eq!(wgpu::CompareFunction::Always, desc.depth_compare);
+
+    // Renderables without a state still fit the depth attachment of their pass
+    let desc = DepthState::disabled().descriptor();
+    assert_eq!(DEPTH_FORMAT, desc.format);
+    assert!(!desc.depth_write_enabled);
+    assert_eq!(wgpu::CompareFunction::Always, desc.depth_compare);
+    assert!(!desc.stencil.is_enabled());
+}
+
+#[test]
+fn stencil_applies_to_both_faces() {
+    let desc = DepthState::opaque().with_stencil(StencilState::write()).descriptor();
+    assert!(desc.stencil.is_enabled());
+    assert_eq!(desc.stencil.front, desc.stencil.back);
+    assert_eq!(wgpu::StencilOperation::Replace, desc.stencil.front.pass_op);
+    assert_eq!(0xff, desc.stencil.write_mask);
+
+    let mask = StencilState::test(Compare::Equal);
+    assert_eq!(StencilOp::Keep, mask.pass);
+    assert_eq!(0, mask.write_mask);
+}
+
+#[test]
+fn renderables_without_depth_load_without_it() {
+    let r: Vertex = serde_json::from_str(r#"{"hash":1,"shader_hash":2,"buffer_list":[]}"#).unwrap();
+    assert_eq!(None, r.depth);
+
+    let json = serde_json::to_string(&DepthState::opaque()).unwrap();
+    let r: Vertex = serde_json::from_str(&format!(r#"{{"hash":1,"shader_hash":2,"buffer_list":[],"depth":{json}}}"#)).unwrap();
+    assert_eq!(Some(DepthState::opaque()), r.depth);
+}
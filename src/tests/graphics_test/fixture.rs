@@ -0,0 +1,15 @@
+use crate::app::Viewport;
+use crate::error::Error;
+
+// A headless viewport, None when the machine has no graphics adapter at all.
+// Tests using it return early in that case instead of failing.
+pub fn headless(width: u32, height: u32) -> Option<Viewport> {
+    match pollster::block_on(Viewport::headless(width, height)) {
+        Ok(val) => Some(val),
+        Err(Error::NoAdapter) => {
+            eprintln!("skipped, no graphics adapter is available");
+            None
+        },
+        Err(e) => panic!("cannot create a headless viewport: {e}")
+    }
+}
@@ -0,0 +1,69 @@
+use image::{RgbaImage, Rgba};
+
+use crate::app::Viewport;
+
+const GOLDEN: &str = "./src/tests/fixtures/golden";
+const OUTPUT: &str = "./target/golden";
+
+pub struct Comparison {
+    pub mismatched: usize,
+    pub diff: RgbaImage, // mismatches in red over the dimmed reference
+}
+
+// Pixels with any channel further than `tolerance` from the reference are mismatches
+pub fn compare(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> Comparison {
+    let mut diff = RgbaImage::from_pixel(actual.width(), actual.height(), Rgba([255, 0, 0, 255]));
+    if expected.dimensions() != actual.dimensions() {
+        return Comparison { mismatched: (actual.width() * actual.height()) as usize, diff }
+    }
+
+    let mut mismatched = 0;
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        let reference = expected.get_pixel(x, y);
+        let differs = pixel.0.iter()
+            .zip(reference.0.iter())
+            .any(|(a, e)| a.abs_diff(*e) > tolerance);
+
+        if differs {
+            mismatched += 1;
+        } else {
+            let [r, g, b, _] = reference.0;
+            let gray = ((r as u32 + g as u32 + b as u32) / 12) as u8;
+            diff.put_pixel(x, y, Rgba([gray, gray, gray, 255]));
+        }
+    }
+
+    Comparison { mismatched, diff }
+}
+
+// Compares the viewport's last frame with `fixtures/golden/{name}.png`.
+// With IGUANA_EYE_BLESS=1 the reference is (re)written instead. On a mismatch
+// the frame and the diff are written to `target/golden` and the test fails.
+pub fn assert_golden(viewport: &Viewport, name: &str, tolerance: u8) {
+    let (width, height) = (viewport.config.width, viewport.config.height);
+    let actual = RgbaImage::from_raw(width, height, viewport.read_pixels().unwrap()).unwrap();
+    let reference = format!("{GOLDEN}/{name}.png");
+
+    if std::env::var("IGUANA_EYE_BLESS").is_ok_and(|v| v == "1") {
+        actual.save(&reference).unwrap();
+        return
+    }
+
+    let expected = match image::open(&reference) {
+        Ok(val) => val.to_rgba8(),
+        Err(e) => panic!("cannot open {reference}: {e}, run with IGUANA_EYE_BLESS=1 to create it")
+    };
+
+    let result = compare(&expected, &actual, tolerance);
+    if result.mismatched == 0 {
+        return
+    }
+
+    std::fs::create_dir_all(OUTPUT).unwrap();
+    let actual_path = format!("{OUTPUT}/{name}_actual.png");
+    let diff_path = format!("{OUTPUT}/{name}_diff.png");
+    actual.save(&actual_path).unwrap();
+    result.diff.save(&diff_path).unwrap();
+
+    panic!("{name}: {} pixels differ from {reference} by more than {tolerance}, see {diff_path}", result.mismatched);
+}
@@ -0,0 +1,217 @@
+use cgmath::{Matrix4, Vector3};
+use image::{RgbaImage, Rgba};
+
+use crate::app::{Viewport, Frame};
+use crate::graphics::shader::Shader;
+use crate::graphics::buffer::{VertexBuffer, InstanceBuffer};
+use crate::graphics::renderable::{Vertex, Index, InstanceIndex};
+use crate::graphics::render_graph::{RenderGraph, AttachmentPool, PassDesc, PassContext};
+use crate::graphics::depth::{DepthState, StencilState, Compare, DEPTH_CLEAR};
+use super::fixture::headless;
+use super::golden::{assert_golden, compare};
+
+const SIZE: u32 = 64;
+const TOLERANCE: u8 = 2;
+
+fn shader(viewport: &Viewport, name: &str) -> Shader {
+    Shader::new(&format!("./src/tests/fixtures/shaders/{name}"), &viewport.device).unwrap()
+}
+
+fn quad() -> (Vec<VertexBuffer>, Vec<u16>) {
+    let vertices = vec![
+        VertexBuffer { position: [-0.5, -0.5, 0.0] },
+        VertexBuffer { position: [0.5, -0.5, 0.0] },
+        VertexBuffer { position: [0.5, 0.5, 0.0] },
+        VertexBuffer { position: [-0.5, 0.5, 0.0] },
+    ];
+
+    (vertices, vec![0, 1, 2, 0, 2, 3])
+}
+
+fn instances(list: &[([f32; 4], Vector3<f32>, f32)]) -> Vec<InstanceBuffer> {
+    list.iter()
+        .map(|(color, offset, scale)| InstanceBuffer {
+            color: *color,
+            model: (Matrix4::from_translation(*offset) * Matrix4::from_scale(*scale)).into(),
+        })
+        .collect()
+}
+
+fn quads(viewport: &Viewport, shader: &Shader, list: &[([f32; 4], Vector3<f32>, f32)]) -> InstanceIndex {
+    let (vertices, indices) = quad();
+    InstanceIndex::new(
+        1, &viewport.device, &viewport.config, shader, vertices, indices, instances(list), &mut Vec::new(), &Vec::new()
+    ).unwrap()
+}
+
+fn draw_quads<'p>(rp: &mut wgpu::RenderPass<'p>, r: &'p InstanceIndex) {
+    let index = &r.r_index;
+    rp.set_pipeline(index.r_vertex.pipeline.as_ref().unwrap());
+    rp.set_vertex_buffer(0, index.r_vertex.vertex_buffer.as_ref().unwrap().slice(..));
+    rp.set_vertex_buffer(1, r.r_instance.inst_buffer.as_ref().unwrap().slice(..));
+    rp.set_index_buffer(index.index_buffer.as_ref().unwrap().slice(..), wgpu::IndexFormat::Uint16);
+    rp.draw_indexed(0..index.index_count, 0, 0..r.r_instance.inst_list.len() as u32);
+}
+
+// Clears the frame to black and the depth to far, and runs `draw` in a single pass
+fn render<'a, F>(viewport: &'a Viewport, frame: &'a Frame, draw: F)
+where F: for<'p> FnOnce(&mut wgpu::RenderPass<'p>, &PassContext<'p, 'a>) + 'a {
+    let mut graph = RenderGraph::new();
+    let target = graph.import_view("frame", frame.view());
+    let depth = graph.import_view("depth", frame.depth_view());
+    let desc = PassDesc::new("golden")
+        .color(target, wgpu::LoadOp::Clear(wgpu::Color::BLACK))
+        .depth(depth, wgpu::LoadOp::Clear(DEPTH_CLEAR), Some(wgpu::LoadOp::Clear(0)));
+    graph.add_render_pass(desc, draw);
+    graph.execute(&viewport.device, &viewport.queue, &mut AttachmentPool::new()).unwrap();
+}
+
+#[test]
+fn vertex_triangle() {
+    let viewport = match headless(SIZE, SIZE) {
+        Some(val) => val,
+        None => return
+    };
+
+    let shader = shader(&viewport, "flat.wgsl");
+    let vertices = vec![
+        VertexBuffer { position: [-0.5, -0.5, 0.0] },
+        VertexBuffer { position: [0.5, -0.5, 0.0] },
+        VertexBuffer { position: [0.0, 0.5, 0.0] },
+    ];
+    let r = Vertex::new(1, &viewport.device, &viewport.config, &shader, vertices, &mut Vec::new(), &Vec::new()).unwrap();
+
+    let frame = Frame::begin(&viewport).unwrap();
+    render(&viewport, &frame, |rp, _| {
+        rp.set_pipeline(r.pipeline.as_ref().unwrap());
+        rp.set_vertex_buffer(0, r.vertex_buffer.as_ref().unwrap().slice(..));
+        rp.draw(0..3, 0..1);
+    });
+    frame.end();
+
+    assert_golden(&viewport, "vertex_triangle", TOLERANCE);
+}
+
+#[test]
+fn index_quad() {
+    let viewport = match headless(SIZE, SIZE) {
+        Some(val) => val,
+        None => return
+    };
+
+    let shader = shader(&viewport, "flat.wgsl");
+    let (vertices, indices) = quad();
+    let r = Index::new(1, &viewport.device, &viewport.config, &shader, vertices, indices, &mut Vec::new(), &Vec::new()).unwrap();
+
+    let frame = Frame::begin(&viewport).unwrap();
+    render(&viewport, &frame, |rp, _| {
+        rp.set_pipeline(r.r_vertex.pipeline.as_ref().unwrap());
+        rp.set_vertex_buffer(0, r.r_vertex.vertex_buffer.as_ref().unwrap().slice(..));
+        rp.set_index_buffer(r.index_buffer.as_ref().unwrap().slice(..), wgpu::IndexFormat::Uint16);
+        rp.draw_indexed(0..r.index_count, 0, 0..1);
+    });
+    frame.end();
+
+    assert_golden(&viewport, "index_quad", TOLERANCE);
+}
+
+#[test]
+fn instance_index_quads() {
+    let viewport = match headless(SIZE, SIZE) {
+        Some(val) => val,
+        None => return
+    };
+
+    let shader = shader(&viewport, "instanced.wgsl");
+    let r = quads(&viewport, &shader, &[
+        ([1.0, 0.0, 0.0, 1.0], Vector3::new(-0.5, -0.5, 0.0), 0.75),
+        ([0.0, 1.0, 0.0, 1.0], Vector3::new(0.5, -0.5, 0.0), 0.75),
+        ([0.0, 0.0, 1.0, 1.0], Vector3::new(0.0, 0.5, 0.0), 0.75),
+    ]);
+
+    let frame = Frame::begin(&viewport).unwrap();
+    render(&viewport, &frame, |rp, _| draw_quads(rp, &r));
+    frame.end();
+
+    assert_golden(&viewport, "instance_index_quads", TOLERANCE);
+}
+
+#[test]
+fn depth_hides_far_quads() {
+    let viewport = match headless(SIZE, SIZE) {
+        Some(val) => val,
+        None => return
+    };
+
+    // The far quad is drawn last and still ends up behind
+    let shader = shader(&viewport, "instanced.wgsl");
+    let mut r = quads(&viewport, &shader, &[
+        ([1.0, 0.0, 0.0, 1.0], Vector3::new(-0.2, 0.0, 0.25), 1.0),
+        ([0.0, 1.0, 0.0, 1.0], Vector3::new(0.2, 0.0, 0.75), 1.0),
+    ]);
+    r.set_depth(&viewport.device, &viewport.config, &shader, Some(DepthState::opaque()), &mut Vec::new(), &Vec::new()).unwrap();
+
+    let frame = Frame::begin(&viewport).unwrap();
+    render(&viewport, &frame, |rp, _| draw_quads(rp, &r));
+    frame.end();
+
+    assert_golden(&viewport, "depth_hides_far_quads", TOLERANCE);
+}
+
+#[test]
+fn stencil_outlines_quad() {
+    let viewport = match headless(SIZE, SIZE) {
+        Some(val) => val,
+        None => return
+    };
+
+    // The outline is nearer than the marked quad, only the stencil keeps it off
+    let shader = shader(&viewport, "instanced.wgsl");
+    let mut marked = quads(&viewport, &shader, &[([1.0, 0.0, 0.0, 1.0], Vector3::new(0.0, 0.0, 0.5), 1.0)]);
+    let mut outline = quads(&viewport, &shader, &[([0.0, 1.0, 0.0, 1.0], Vector3::new(0.0, 0.0, 0.0), 1.5)]);
+
+    let state = DepthState::opaque().with_stencil(StencilState::write());
+    marked.set_depth(&viewport.device, &viewport.config, &shader, Some(state), &mut Vec::new(), &Vec::new()).unwrap();
+    let state = DepthState::opaque().with_stencil(StencilState::test(Compare::NotEqual));
+    outline.set_depth(&viewport.device, &viewport.config, &shader, Some(state), &mut Vec::new(), &Vec::new()).unwrap();
+
+    let frame = Frame::begin(&viewport).unwrap();
+    render(&viewport, &frame, |rp, _| {
+        rp.set_stencil_reference(1);
+        draw_quads(rp, &marked);
+        draw_quads(rp, &outline);
+    });
+    frame.end();
+
+    assert_golden(&viewport, "stencil_outlines_quad", TOLERANCE);
+}
+
+#[test]
+fn comparison_marks_mismatches() {
+    let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(1, 2, Rgba([103, 100, 100, 255]));
+    actual.put_pixel(3, 3, Rgba([102, 98, 100, 255]));
+
+    let result = compare(&expected, &actual, 2);
+    assert_eq!(1, result.mismatched);
+    assert_eq!(&Rgba([255, 0, 0, 255]), result.diff.get_pixel(1, 2));
+    assert_eq!(&Rgba([25, 25, 25, 255]), result.diff.get_pixel(3, 3));
+
+    let smaller = RgbaImage::new(2, 2);
+    assert_eq!(16, compare(&smaller, &actual, 255).mismatched);
+}
+
+#[test]
+fn exact_comparison_dims_matches() {
+    let expected = RgbaImage::from_pixel(2, 1, Rgba([120, 60, 0, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(1, 0, Rgba([120, 60, 0, 254]));
+
+    // Alpha counts like any other channel
+    let result = compare(&expected, &actual, 0);
+    assert_eq!(1, result.mismatched);
+    assert_eq!(&Rgba([15, 15, 15, 255]), result.diff.get_pixel(0, 0));
+    assert_eq!(&Rgba([255, 0, 0, 255]), result.diff.get_pixel(1, 0));
+    assert_eq!(0, compare(&expected, &actual, 1).mismatched);
+}
@@ -0,0 +1,57 @@
+use crate::app::Frame;
+use crate::graphics::render_graph::{RenderGraph, AttachmentPool, PassDesc};
+use crate::graphics::depth::DEPTH_CLEAR;
+use crate::tests::fixture::temp_path;
+use super::fixture::headless;
+
+fn render_clear(viewport: &crate::app::Viewport, color: wgpu::Color) {
+    let frame = Frame::begin(viewport).unwrap();
+    let mut graph = RenderGraph::new();
+    let target = graph.import_view("frame", frame.view());
+    let depth = graph.import_view("depth", frame.depth_view());
+    let desc = PassDesc::new("clear")
+        .color(target, wgpu::LoadOp::Clear(color))
+        .depth(depth, wgpu::LoadOp::Clear(DEPTH_CLEAR), Some(wgpu::LoadOp::Clear(0)));
+    graph.add_render_pass(desc, |_, _| {});
+    graph.execute(&viewport.device, &viewport.queue, &mut AttachmentPool::new()).unwrap();
+    frame.end();
+}
+
+#[test]
+fn clear_reads_back() {
+    let mut viewport = match headless(70, 20) {
+        Some(val) => val,
+        None => return
+    };
+    assert!(viewport.is_headless());
+
+    // sRGB target, 0 and 1 are stored as is
+    render_clear(&viewport, wgpu::Color { r: 1.0, g: 0.0, b: 1.0, a: 1.0 });
+    let pixels = viewport.read_pixels().unwrap();
+    assert_eq!(70 * 20 * 4, pixels.len());
+    assert!(pixels.chunks(4).all(|p| p == [255, 0, 255, 255]));
+
+    // Rows of a resized target are still unpadded, and the depth texture follows its size
+    viewport.resize(winit::dpi::PhysicalSize::new(3, 2));
+    render_clear(&viewport, wgpu::Color::BLACK);
+    let pixels = viewport.read_pixels().unwrap();
+    assert_eq!([0, 0, 0, 255].repeat(6), pixels);
+}
+
+#[test]
+fn frames_save_to_png() {
+    let viewport = match headless(8, 4) {
+        Some(val) => val,
+        None => return
+    };
+
+    render_clear(&viewport, wgpu::Color::WHITE);
+    let path = temp_path("frames_save_to_png.png");
+    viewport.save_png(&path).unwrap();
+
+    let image = image::open(&path).unwrap().to_rgba8();
+    assert_eq!((8, 4), image.dimensions());
+    assert_eq!(&[255, 255, 255, 255], &image.get_pixel(7, 3).0);
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,7 @@
+mod fixture;
+mod golden;
+mod render_graph_test;
+mod headless_test;
+mod golden_test;
+mod camera_test;
+mod depth_test;
\ No newline at end of file
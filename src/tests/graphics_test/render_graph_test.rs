@@ -0,0 +1,123 @@
+use crate::error::Error;
+use crate::graphics::render_graph::{RenderGraph, PassDesc, TextureDesc, Attachment};
+
+fn color_desc(width: u32) -> TextureDesc {
+    TextureDesc {
+        width,
+        height: 64,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+    }
+}
+
+fn names(graph: &RenderGraph, order: &[usize]) -> Vec<String> {
+    order.iter().map(|i| String::from(graph.pass_name(*i).unwrap())).collect()
+}
+
+#[test]
+fn readers_follow_writers() {
+    let mut graph = RenderGraph::new();
+    let scene = graph.create_texture("scene", color_desc(64));
+    let shadow = graph.create_texture("shadow", color_desc(32));
+    let output = graph.create_buffer("output", crate::graphics::render_graph::BufferDesc {
+        size: 256,
+        usage: wgpu::BufferUsages::COPY_DST,
+    });
+
+    // Added out of order on purpose
+    graph.add_pass(PassDesc::new("readback").read(scene).write(output), |_, _| {});
+    graph.add_render_pass(PassDesc::new("post").read(scene).read(shadow), |_, _| {});
+    graph.add_render_pass(PassDesc::new("scene").read(shadow).color(scene, wgpu::LoadOp::Load), |_, _| {});
+    graph.add_render_pass(PassDesc::new("shadow").color(shadow, wgpu::LoadOp::Clear(wgpu::Color::BLACK)), |_, _| {});
+
+    let plan = graph.compile().unwrap();
+    assert_eq!(vec!["shadow", "scene", "readback", "post"], names(&graph, &plan.order));
+}
+
+#[test]
+fn writers_keep_their_order() {
+    let mut graph = RenderGraph::new();
+    let frame = graph.create_texture("frame", color_desc(64));
+    graph.add_render_pass(PassDesc::new("game").color(frame, wgpu::LoadOp::Clear(wgpu::Color::BLACK)), |_, _| {});
+    graph.add_render_pass(PassDesc::new("ui").color(frame, wgpu::LoadOp::Load), |_, _| {});
+
+    let plan = graph.compile().unwrap();
+    assert_eq!(vec!["game", "ui"], names(&graph, &plan.order));
+}
+
+#[test]
+fn reads_see_the_version_written_before_them() {
+    let mut graph = RenderGraph::new();
+    let scene = graph.create_texture("scene", color_desc(64));
+    let blurred = graph.create_texture("blurred", color_desc(64));
+    let frame = graph.create_texture("frame", color_desc(64));
+
+    // The blur samples the scene before the overlay draws over it
+    graph.add_render_pass(PassDesc::new("scene").color(scene, wgpu::LoadOp::Clear(wgpu::Color::BLACK)), |_, _| {});
+    graph.add_render_pass(PassDesc::new("blur").read(scene).color(blurred, wgpu::LoadOp::Load), |_, _| {});
+    graph.add_render_pass(PassDesc::new("overlay").color(scene, wgpu::LoadOp::Load), |_, _| {});
+    graph.add_render_pass(PassDesc::new("composite").read(scene).read(blurred).color(frame, wgpu::LoadOp::Load), |_, _| {});
+
+    let plan = graph.compile().unwrap();
+    assert_eq!(vec!["scene", "blur", "overlay", "composite"], names(&graph, &plan.order));
+}
+
+#[test]
+fn depth_attachments_are_writes() {
+    let mut graph = RenderGraph::new();
+    let frame = graph.create_texture("frame", color_desc(64));
+    let depth = graph.create_texture("depth", TextureDesc {
+        format: crate::graphics::depth::DEPTH_FORMAT,
+        ..color_desc(64)
+    });
+    let fogged = graph.create_texture("fogged", color_desc(64));
+    graph.add_render_pass(PassDesc::new("fog").read(frame).read(depth).color(fogged, wgpu::LoadOp::Load), |_, _| {});
+    graph.add_render_pass(
+        PassDesc::new("opaque")
+            .color(frame, wgpu::LoadOp::Clear(wgpu::Color::BLACK))
+            .depth(depth, wgpu::LoadOp::Clear(1.0), None),
+        |_, _| {}
+    );
+
+    let plan = graph.compile().unwrap();
+    assert_eq!(vec!["opaque", "fog"], names(&graph, &plan.order));
+}
+
+#[test]
+fn transients_share_slots_when_lifetimes_dont_overlap() {
+    let mut graph = RenderGraph::new();
+    let a = graph.create_texture("a", color_desc(64));
+    let b = graph.create_texture("b", color_desc(64));
+    let c = graph.create_texture("c", color_desc(64));
+    let small = graph.create_texture("small", color_desc(16));
+    graph.create_texture("unused", color_desc(64));
+
+    // a lives over the first two passes, b over the next two, c over the last
+    graph.add_render_pass(PassDesc::new("1").color(a, wgpu::LoadOp::Load), |_, _| {});
+    graph.add_render_pass(PassDesc::new("2").read(a).color(b, wgpu::LoadOp::Load), |_, _| {});
+    graph.add_render_pass(PassDesc::new("3").read(b).color(small, wgpu::LoadOp::Load), |_, _| {});
+    graph.add_render_pass(PassDesc::new("4").read(small).color(c, wgpu::LoadOp::Load), |_, _| {});
+
+    let plan = graph.compile().unwrap();
+    assert_eq!(4, plan.assigned.len());
+    assert_ne!(plan.assigned[&a], plan.assigned[&b]);
+    assert!(plan.assigned[&c] == plan.assigned[&a] || plan.assigned[&c] == plan.assigned[&b]);
+    assert_eq!(3, plan.slots.len());
+    assert_eq!(Attachment::Texture(color_desc(16)), plan.slots[plan.assigned[&small]]);
+}
+
+#[test]
+fn invalid_graphs_are_rejected() {
+    let mut graph = RenderGraph::new();
+    let a = graph.create_texture("a", color_desc(64));
+    let b = graph.create_texture("b", color_desc(64));
+    graph.add_render_pass(PassDesc::new("first").read(b).color(a, wgpu::LoadOp::Load), |_, _| {});
+    graph.add_render_pass(PassDesc::new("second").read(a).color(b, wgpu::LoadOp::Load), |_, _| {});
+    assert!(matches!(graph.compile(), Err(Error::PassCycle(_))));
+
+    let mut graph = RenderGraph::new();
+    let a = graph.create_texture("a", color_desc(64));
+    graph.add_render_pass(PassDesc::new("lonely").read(a), |_, _| {});
+    let e = graph.compile().err().unwrap();
+    assert!(matches!(&e, Error::ResourceNotWritten { pass, resource } if pass == "lonely" && resource == "a"));
+}
@@ -16,4 +16,11 @@ fn all_unique() {
 
     assert_ne!(h1, h2);
     assert_ne!(h2, h3);
+}
+
+#[test]
+fn fnv1a_known_values() {
+    assert_eq!(0xcbf29ce484222325, hash::fnv1a(b""));
+    assert_eq!(0xaf63dc4c8601ec8c, hash::fnv1a(b"a"));
+    assert_eq!(0x85944171f73967e8, hash::fnv1a(b"foobar"));
 }
\ No newline at end of file
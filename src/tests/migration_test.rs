@@ -0,0 +1,120 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::app::Config;
+use crate::graphics::camera::{Camera, Projection};
+use crate::error::Error;
+use crate::system::ecs::ECS;
+use crate::util::serialize::{self, Format};
+use crate::util::migration::{Migrations, Versioned};
+use super::fixture::temp_path;
+
+const FIXTURES: &str = "./src/tests/fixtures";
+
+#[derive(Serialize, Deserialize)]
+struct Player {
+    name: String,
+    health: i64,
+    max_health: i64,
+}
+
+impl Versioned for Player {
+    fn migrations() -> Migrations {
+        Migrations::new("Player")
+            .add(rename_hp)
+            .add(add_max_health)
+    }
+}
+
+fn rename_hp(value: &mut Value) -> Result<(), Error> {
+    let fields = value.as_object_mut().unwrap();
+    let hp = fields.remove("hp").unwrap_or(Value::from(0));
+    fields.insert(String::from("health"), hp);
+
+    Ok(())
+}
+
+fn add_max_health(value: &mut Value) -> Result<(), Error> {
+    let health = value["health"].clone();
+    value.as_object_mut().unwrap().insert(String::from("max_health"), health);
+
+    Ok(())
+}
+
+fn fixture(name: &str) -> String {
+    format!("{FIXTURES}/{name}")
+}
+
+#[test]
+fn every_version_upgrades_to_current() {
+    assert_eq!(2, Player::migrations().version());
+
+    for name in ["player_v0.json", "player_v1.json", "player_v2.json"] {
+        let player: Player = serialize::read_versioned(&fixture(name)).unwrap();
+        assert_eq!("goblin", player.name);
+        assert_eq!(30, player.health);
+        assert_eq!(30, player.max_health);
+    }
+}
+
+#[test]
+fn config_gains_missing_fields() {
+    let config: Config = serialize::read_versioned(&fixture("config_v0.json")).unwrap();
+    assert_eq!((1280, 720, false), (config.width, config.height, config.fullscreen));
+
+    let config: Config = serialize::read_versioned(&fixture("config_v1.json")).unwrap();
+    assert_eq!((1920, 1080, true), (config.width, config.height, config.fullscreen));
+
+    // Without the migration the old file doesn't deserialize, and says why
+    let e = serialize::read::<Config>(&fixture("config_v0.json")).err().unwrap();
+    assert!(matches!(&e, Error::Deserialize { path, line: 4, .. } if path.ends_with("config_v0.json")));
+    assert!(e.to_string().contains("fullscreen"));
+}
+
+#[test]
+fn camera_gains_projection() {
+    let camera: Camera = serialize::read_versioned(&fixture("camera_v0.json")).unwrap();
+    assert_eq!(Projection::Orthographic { scale: 1.0, znear: -500.0, zfar: 500.0 }, camera.projection);
+    assert_eq!((800.0, 600.0), camera.size());
+
+    // The current layout round trips without migrating
+    let json = serde_json::to_vec(&serde_json::json!({ "version": 1, "data": &camera })).unwrap();
+    let loaded: Camera = serialize::from_versioned_bytes(&json).unwrap();
+    assert_eq!(camera.projection, loaded.projection);
+    assert_eq!(camera.eye, loaded.eye);
+}
+
+#[test]
+fn newer_versions_are_rejected() {
+    let bytes = br#"{ "version": 3, "data": { "name": "goblin", "health": 30, "max_health": 30 } }"#;
+    let e = serialize::from_versioned_bytes::<Player>(bytes).err().unwrap();
+    assert!(matches!(e, Error::UnsupportedVersion { version: 3, current: 2, .. }));
+}
+
+#[test]
+fn saved_roots_carry_their_version() {
+    let json_path = temp_path("saved_roots_carry_their_version.json");
+    let binary_path = temp_path("saved_roots_carry_their_version.bin");
+    let player = Player { name: String::from("orc"), health: 5, max_health: 10 };
+    serialize::write_versioned(&json_path, &player, Format::Json).unwrap();
+    serialize::write_versioned(&binary_path, &player, Format::Binary).unwrap();
+
+    let value: Value = serialize::read(&json_path).unwrap();
+    assert_eq!(2, value["version"]);
+    assert_eq!("orc", value["data"]["name"]);
+
+    let read: Player = serialize::read_versioned(&binary_path).unwrap();
+    assert_eq!((5, 10), (read.health, read.max_health));
+
+    // Scenes written before they had a version still load
+    let mut ecs = ECS::new().unwrap();
+    let e = ecs.create_entity().unwrap();
+    serialize::write(&json_path, &ecs).unwrap();
+
+    let mut loaded = ECS::new().unwrap();
+    loaded.load_scene(&json_path).unwrap();
+    assert!(loaded.does_entity_exist(e));
+
+    std::fs::remove_file(&json_path).unwrap();
+    std::fs::remove_file(&binary_path).unwrap();
+}
@@ -1,4 +1,7 @@
+mod fixture;
 mod input_test;
 mod graphics_test;
 mod hash_test;
-mod ecs_test;
\ No newline at end of file
+mod ecs_test;
+mod serialize_test;
+mod migration_test;
\ No newline at end of file
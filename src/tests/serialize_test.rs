@@ -0,0 +1,74 @@
+use serde::{Serialize, Deserialize};
+
+use crate::util::serialize::{self, Format, MAGIC, BINARY_VERSION};
+use crate::graphics::buffer::InstanceBuffer;
+use crate::error::Error;
+use super::fixture::temp_path;
+
+#[derive(Serialize, Deserialize)]
+struct Batch {
+    name: String,
+    #[serde(with = "crate::util::serialize::pod_list")]
+    inst_list: Vec<InstanceBuffer>,
+}
+
+fn create_batch(count: usize) -> Batch {
+    let inst_list = (0..count)
+        .map(|i| InstanceBuffer {
+            color: [i as f32, 0.5, 0.25, 1.0],
+            model: [[i as f32; 4]; 4],
+        })
+        .collect();
+
+    Batch { name: String::from("batch"), inst_list }
+}
+
+#[test]
+fn binary_round_trip() {
+    let batch = create_batch(100);
+
+    let bytes = serialize::to_binary(&batch).unwrap();
+    assert_eq!(Format::Binary, serialize::detect(&bytes));
+    assert_eq!(MAGIC, bytes[0..4]);
+    assert_eq!(BINARY_VERSION.to_le_bytes(), bytes[4..6]);
+
+    let read: Batch = serialize::from_bytes(&bytes).unwrap();
+    assert_eq!("batch", read.name);
+    assert_eq!(100, read.inst_list.len());
+    assert_eq!(42.0, read.inst_list[42].color[0]);
+    assert_eq!([7.0; 4], read.inst_list[7].model[3]);
+
+    // Instances are a single block of raw bytes instead of a list of numbers
+    let raw = 100 * std::mem::size_of::<InstanceBuffer>();
+    assert!(bytes.len() < raw + 64);
+    assert!(bytes.len() < serde_json::to_vec(&batch).unwrap().len());
+}
+
+#[test]
+fn read_detects_format() {
+    let batch = create_batch(3);
+    let json_path = temp_path("read_detects_format.json");
+    let binary_path = temp_path("read_detects_format.bin");
+
+    serialize::write_as(&json_path, &batch, Format::Json).unwrap();
+    serialize::write_as(&binary_path, &batch, Format::Binary).unwrap();
+
+    let from_json: Batch = serialize::read(&json_path).unwrap();
+    let from_binary: Batch = serialize::read(&binary_path).unwrap();
+    assert_eq!(3, from_json.inst_list.len());
+    assert_eq!(from_json.inst_list[2].color, from_binary.inst_list[2].color);
+
+    std::fs::remove_file(&json_path).unwrap();
+    std::fs::remove_file(&binary_path).unwrap();
+}
+
+#[test]
+fn bad_headers_are_rejected() {
+    let mut bytes = serialize::to_binary(&create_batch(1)).unwrap();
+
+    assert!(serialize::from_bytes::<Batch>(&bytes[0..5]).is_err());
+
+    bytes[4..6].copy_from_slice(&(BINARY_VERSION + 1).to_le_bytes());
+    let result = serialize::from_bytes::<Batch>(&bytes);
+    assert!(matches!(result, Err(Error::UnsupportedVersion { version: 2, current: 1, .. })));
+}
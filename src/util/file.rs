@@ -1,18 +1,16 @@
 use std::fs;
-use std::io;
 use std::path;
 
-pub fn absolute_path(path: &str) -> Result<String, io::Error> {
+use crate::error::Error;
+
+pub fn absolute_path(path: &str) -> Result<String, Error> {
     let abs_path = path::Path::new(path);
     match abs_path.canonicalize() {
         Ok(to_convert) => match to_convert.to_str() {
             Some(str) => Ok(String::from(str)),
-            None => {
-                return Err(io::Error::new(io::ErrorKind::InvalidData,
-                         "ERROR::file::absolute_path()::cannot convert to string"))
-            }
+            None => return Err(Error::InvalidPath(String::from(path)))
         },
-        Err(e) => return Err(e)
+        Err(e) => return Err(Error::Io(e))
     }
 }
 
@@ -25,7 +23,7 @@ pub fn exist(path: &str) -> bool {
     return path::Path::new(&abs_path).exists();
 }
 
-pub fn extract_dir(path: &str) -> Result<String, io::Error> {
+pub fn extract_dir(path: &str) -> Result<String, Error> {
     let abs_path = match absolute_path(path) {
         Ok(val) => val,
         Err(e) => return Err(e)
@@ -33,8 +31,7 @@ pub fn extract_dir(path: &str) -> Result<String, io::Error> {
 
     let sys_path = path::Path::new(&abs_path);
     if !sys_path.is_file() {
-        return Err(io::Error::new(io::ErrorKind::Other,
-            "ERROR::file::extract_dir()::path is not a file"))
+        return Err(Error::InvalidPath(String::from(path)))
     }
 
     let mut buf = sys_path.to_path_buf();
@@ -43,15 +40,14 @@ pub fn extract_dir(path: &str) -> Result<String, io::Error> {
     let extracted_path = match buf.to_str() {
         Some(val) => String::from(val),
         None => {
-            return Err(io::Error::new(io::ErrorKind::Other,
-                "ERROR::file::extract_dir()::cannot convert buf path to string"))
+            return Err(Error::InvalidPath(String::from(path)))
         }
     };
 
     Ok(extracted_path)
 }
 
-pub fn extract_file_name(path: &str) -> Result<String, io::Error> {
+pub fn extract_file_name(path: &str) -> Result<String, Error> {
     let abs_path = match absolute_path(path) {
         Ok(val) => val,
         Err(e) => return Err(e)
@@ -59,8 +55,7 @@ pub fn extract_file_name(path: &str) -> Result<String, io::Error> {
 
     let sys_path = path::Path::new(&abs_path);
     if !sys_path.is_file() {
-        return Err(io::Error::new(io::ErrorKind::Other,
-            "ERROR::file::extract_file_name()::path is not a file"))
+        return Err(Error::InvalidPath(String::from(path)))
     }
 
     let extracted_name = match sys_path.file_name() {
@@ -68,21 +63,19 @@ pub fn extract_file_name(path: &str) -> Result<String, io::Error> {
             match val.to_str() {
                 Some(val) => String::from(val),
                 None => {
-                    return Err(io::Error::new(io::ErrorKind::Other,
-                        "ERROR::file::extract_file_name()::cannot convert OsStr to string"))
+                    return Err(Error::InvalidPath(String::from(path)))
                 }
             }
         },
         None => {
-            return Err(io::Error::new(io::ErrorKind::Other,
-                "ERROR::file::extract_file_name()::cannot convert OsStr to string"))
+            return Err(Error::InvalidPath(String::from(path)))
         }
     };
 
     Ok(extracted_name)
 }
 
-pub fn extract_extension(path: &str) -> Result<String, io::Error> {
+pub fn extract_extension(path: &str) -> Result<String, Error> {
     let abs_path = match absolute_path(path) {
         Ok(val) => val,
         Err(e) => return Err(e)
@@ -90,8 +83,7 @@ pub fn extract_extension(path: &str) -> Result<String, io::Error> {
 
     let sys_path = path::Path::new(&abs_path);
     if !sys_path.is_file() {
-        return Err(io::Error::new(io::ErrorKind::Other,
-            "ERROR::file::extract_extension()::path is not a file"))
+        return Err(Error::InvalidPath(String::from(path)))
     }
 
     let extracted_ext = match sys_path.extension() {
@@ -99,24 +91,22 @@ pub fn extract_extension(path: &str) -> Result<String, io::Error> {
             match val.to_str() {
                 Some(val) => String::from(val),
                 None => {
-                    return Err(io::Error::new(io::ErrorKind::Other,
-                        "ERROR::file::extract_extension()::cannot convert OsStr to string"))
+                    return Err(Error::InvalidPath(String::from(path)))
                 }
             }
         },
         None => {
-            return Err(io::Error::new(io::ErrorKind::Other,
-                "ERROR::file::extract_extension()::cannot convert OsStr to string"))
+            return Err(Error::InvalidPath(String::from(path)))
         }
     };
 
     Ok(extracted_ext)
 }
 
-pub fn remove_all_file(path: &str) -> Result<(), io::Error> {
+pub fn remove_all_file(path: &str) -> Result<(), Error> {
     let dir = match fs::read_dir(path) {
         Ok(val) => val,
-        Err(e) => return Err(e)
+        Err(e) => return Err(Error::Io(e))
     };
 
     for entry in dir {
@@ -5,4 +5,18 @@ pub fn get<T: Hash>(t: &T) -> u64 {
     let mut s = DefaultHasher::new();
     t.hash(&mut s);
     s.finish()
-}
\ No newline at end of file
+}
+
+// 64-bit FNV-1a. Unlike `get` the result is fixed across builds and platforms,
+// so it can be written to files, see http://www.isthe.com/chongo/tech/comp/fnv/
+pub const fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+        i += 1;
+    }
+
+    hash
+}
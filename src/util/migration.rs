@@ -0,0 +1,89 @@
+use serde::{ser, de};
+use serde_json::Value;
+
+use crate::error::Error;
+
+// Upgrades a tree saved at one version to the next
+pub type Migration = fn(&mut Value) -> Result<(), Error>;
+
+// The ordered upgrade steps of a serialized root.
+// Step n upgrades version n to n + 1, files without a version are version 0.
+pub struct Migrations {
+    kind: &'static str,
+    steps: Vec<Migration>,
+}
+
+impl Migrations {
+    pub fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn add(mut self, step: Migration) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    // The version objects are currently written with
+    pub fn version(&self) -> u32 {
+        self.steps.len() as u32
+    }
+
+    // Runs every step from `from` up to the current version
+    pub fn upgrade(&self, value: &mut Value, from: u32) -> Result<(), Error> {
+        if from > self.version() {
+            return Err(Error::UnsupportedVersion {
+                kind: String::from(self.kind),
+                version: from,
+                current: self.version(),
+            })
+        }
+
+        for (version, step) in self.steps.iter().enumerate().skip(from as usize) {
+            if let Err(e) = step(value) {
+                return Err(Error::Migration {
+                    kind: String::from(self.kind),
+                    from: version as u32,
+                    source: Box::new(e),
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Roots saved with a version, see `serialize::write_versioned`
+pub trait Versioned: ser::Serialize + de::DeserializeOwned {
+    fn migrations() -> Migrations;
+}
+
+// Migration of roots that only gained the version, e.g. `{ ... }` to `{ "version": 1, "data": { ... } }`
+pub fn unversioned(_: &mut Value) -> Result<(), Error> {
+    Ok(())
+}
+
+// Adds the fields of `defaults` missing from `value`, keeping the ones it has
+pub fn fill_defaults(value: &mut Value, defaults: impl ser::Serialize) -> Result<(), Error> {
+    let defaults = match serde_json::to_value(defaults) {
+        Ok(Value::Object(val)) => val,
+        _ => {
+            return Err(Error::InvalidData(String::from("migration defaults aren't an object")))
+        }
+    };
+
+    let fields = match value.as_object_mut() {
+        Some(val) => val,
+        None => {
+            return Err(Error::InvalidData(String::from("migrated value isn't an object")))
+        }
+    };
+
+    for (key, val) in defaults {
+        fields.entry(key).or_insert(val);
+    }
+
+    Ok(())
+}
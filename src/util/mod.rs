@@ -1,5 +1,7 @@
 pub mod file;
+pub mod glob;
 pub mod hash;
 pub mod math;
+pub mod migration;
 pub mod random;
 pub mod serialize;
\ No newline at end of file
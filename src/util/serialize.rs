@@ -1,52 +1,255 @@
-use std::io;
-use serde::{ser, de};
+use std::hash::Hash;
+use std::collections::HashMap;
+use serde::{ser, de, Serialize, Deserialize};
+use serde_json::Value;
 use crate::util::file;
+use crate::util::migration::Versioned;
+use crate::error::Error;
 
-pub fn write<T: ser::Serialize>(path: &str, object: &T) -> Result<String, io::Error> {
+// Binary files start with `MAGIC` and the little endian `BINARY_VERSION` they
+// were written with, followed by the object as MessagePack
+pub const MAGIC: [u8; 4] = *b"IGEB";
+pub const BINARY_VERSION: u16 = 1;
+const HEADER_LEN: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Binary,
+}
+
+pub fn write_as<T: ser::Serialize>(path: &str, object: &T, format: Format) -> Result<(), Error> {
+    match format {
+        Format::Json => write(path, object).map(|_| ()),
+        Format::Binary => write_binary(path, object),
+    }
+}
+
+pub fn write<T: ser::Serialize>(path: &str, object: &T) -> Result<String, Error> {
     let str = match serde_json::to_string_pretty(&object) {
         Ok(val) => val,
-        Err(e) => {
-            eprintln!("ERROR::serialize::write()::{e}");
-            return Err(io::Error::new(io::ErrorKind::NotFound,
-                    "ERROR::serialize::write()::cannot get pretty json"))
-        }
+        Err(e) => return Err(Error::Serialize(e.to_string()))
     };
 
     match std::fs::write(path, &str) {
         Ok(()) => Ok(str),
+        Err(e) => return Err(Error::Io(e))
+    }
+}
+
+pub fn write_binary<T: ser::Serialize>(path: &str, object: &T) -> Result<(), Error> {
+    let bytes = to_binary(object)?;
+
+    match std::fs::write(path, bytes) {
+        Ok(()) => Ok(()),
+        Err(e) => return Err(Error::Io(e))
+    }
+}
+
+pub fn to_binary<T: ser::Serialize>(object: &T) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::from(MAGIC);
+    bytes.extend(BINARY_VERSION.to_le_bytes());
+
+    match rmp_serde::encode::write_named(&mut bytes, object) {
+        Ok(()) => Ok(bytes),
+        Err(e) => return Err(Error::Serialize(e.to_string()))
+    }
+}
+
+pub fn detect(bytes: &[u8]) -> Format {
+    match bytes.starts_with(&MAGIC) {
+        true => Format::Binary,
+        false => Format::Json
+    }
+}
+
+// Accepts both JSON and binary, see `detect`
+pub fn from_bytes<T: de::DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    if detect(bytes) == Format::Json {
+        match serde_json::from_slice(bytes) {
+            Ok(val) => return Ok(val),
+            Err(e) => return Err(Error::deserialize(&e))
+        }
+    }
+
+    if bytes.len() < HEADER_LEN {
+        return Err(Error::InvalidData(String::from("binary header is truncated")));
+    }
+
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version > BINARY_VERSION {
+        return Err(Error::UnsupportedVersion {
+            kind: String::from("binary format"),
+            version: version as u32,
+            current: BINARY_VERSION as u32,
+        });
+    }
+
+    match rmp_serde::from_slice(&bytes[HEADER_LEN..]) {
+        Ok(val) => Ok(val),
         Err(e) => {
-            eprintln!("ERROR::serialize::write()::{e}");
-            return Err(io::Error::new(io::ErrorKind::Other,
-                    "ERROR::serialize::write()::cannot write to file"))
+            return Err(Error::Deserialize { path: String::new(), line: 0, col: 0, msg: e.to_string() });
         }
     }
 }
 
-pub fn read<T: de::DeserializeOwned>(path: &str) -> Result<T, io::Error> {
-    let abs_path = match file::absolute_path(path) {
-        Ok(val) => val,
-        Err(e) => {
-            eprintln!("ERROR::serialize::read()::{e}");
-            return Err(io::Error::new(io::ErrorKind::NotFound,
-                "ERROR::serialize::read()::cannot get absolute path"));
-        },
-    };
+// Versioned roots are written as `{ "version": n, "data": object }`
+#[derive(Serialize)]
+struct EnvelopeRef<'a, T: ser::Serialize> {
+    version: u32,
+    data: &'a T,
+}
 
-    let str = match std::fs::read_to_string(abs_path) {
-        Ok(val) => val,
-        Err(e) => {
-            eprintln!("ERROR::serialize::read()::{e}");
-            return Err(io::Error::new(io::ErrorKind::Other,
-                "ERROR::serialize::read()::cannot read from file"));
-        },
+#[derive(Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    data: T,
+}
+
+pub fn write_versioned<T: Versioned>(path: &str, object: &T, format: Format) -> Result<(), Error> {
+    let envelope = EnvelopeRef {
+        version: T::migrations().version(),
+        data: object,
     };
 
-    match serde_json::from_str(&str) {
+    write_as(path, &envelope, format)
+}
+
+// Older versions are upgraded by the migrations of `T` before being deserialized.
+// Binary files are upgraded through `serde_json::Value` as well, so the ones
+// with maps keyed by numbers only load at the current version.
+pub fn from_versioned_bytes<T: Versioned>(bytes: &[u8]) -> Result<T, Error> {
+    let migrations = T::migrations();
+
+    if detect(bytes) == Format::Binary {
+        let header: Result<Envelope<de::IgnoredAny>, Error> = from_bytes(bytes);
+        if matches!(header, Ok(envelope) if envelope.version == migrations.version()) {
+            let envelope: Envelope<T> = from_bytes(bytes)?;
+            return Ok(envelope.data)
+        }
+    }
+
+    let (version, mut data) = open_envelope(from_bytes(bytes)?);
+    migrations.upgrade(&mut data, version)?;
+
+    match serde_json::from_value(data) {
         Ok(val) => Ok(val),
-        Err(e) => {
-            eprintln!("ERROR::serialize::read()::{e}");
-            return Err(io::Error::new(io::ErrorKind::NotFound,
-                "ERROR::serialize::read()::failed to deserialize"));
-        },
+        Err(e) => return Err(Error::deserialize(&e))
+    }
+}
+
+pub fn read_versioned<T: Versioned>(path: &str) -> Result<T, Error> {
+    from_versioned_bytes(&read_bytes(path)?).map_err(|e| e.in_file(path))
+}
+
+// Splits a root into its version and object, roots without an envelope are version 0
+fn open_envelope(value: Value) -> (u32, Value) {
+    match value {
+        Value::Object(mut fields) if fields.len() == 2 && fields.contains_key("data") => {
+            match fields.get("version").and_then(|v| v.as_u64()) {
+                Some(version) => (version as u32, fields.remove("data").unwrap()),
+                None => (0, Value::Object(fields))
+            }
+        }
+        _ => (0, value)
+    }
+}
+
+pub fn read<T: de::DeserializeOwned>(path: &str) -> Result<T, Error> {
+    from_bytes(&read_bytes(path)?).map_err(|e| e.in_file(path))
+}
+
+fn read_bytes(path: &str) -> Result<Vec<u8>, Error> {
+    let abs_path = file::absolute_path(path)?;
+
+    match std::fs::read(abs_path) {
+        Ok(val) => Ok(val),
+        Err(e) => return Err(Error::Io(e))
+    }
+}
+
+// JSON maps only accept string keys, so maps keyed by structs
+// (e.g. Entity) are written as a list of (key, value) pairs instead.
+// Usage: #[serde(with = "crate::util::serialize::map_as_list")]
+pub mod map_as_list {
+    use super::*;
+    use serde::{Serializer, Deserializer, Deserialize};
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: ser::Serialize,
+        V: ser::Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: de::Deserialize<'de> + Eq + Hash,
+        V: de::Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let list = Vec::<(K, V)>::deserialize(deserializer)?;
+        Ok(list.into_iter().collect())
+    }
+}
+// Lists of plain data (e.g. `InstanceBuffer`) written as a list in JSON
+// and as one block of raw bytes in binary formats.
+// Usage: #[serde(with = "crate::util::serialize::pod_list")]
+pub mod pod_list {
+    use super::*;
+    use std::marker::PhantomData;
+    use serde::{Serializer, Deserializer, Deserialize};
+    use bytemuck::Pod;
+
+    pub fn serialize<T, S>(list: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Pod + ser::Serialize,
+        S: Serializer,
+    {
+        match serializer.is_human_readable() {
+            true => serializer.collect_seq(list.iter()),
+            false => serializer.serialize_bytes(bytemuck::cast_slice(list))
+        }
     }
-}
\ No newline at end of file
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: Pod + de::Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            return Vec::<T>::deserialize(deserializer)
+        }
+
+        deserializer.deserialize_any(PodVisitor(PhantomData))
+    }
+
+    struct PodVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Pod + de::Deserialize<'de>> de::Visitor<'de> for PodVisitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("bytes or a list")
+        }
+
+        fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+            if bytes.len() % std::mem::size_of::<T>() != 0 {
+                return Err(E::invalid_length(bytes.len(), &self))
+            }
+
+            Ok(bytemuck::pod_collect_to_vec(bytes))
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut list = Vec::new();
+            while let Some(val) = seq.next_element()? {
+                list.push(val);
+            }
+
+            Ok(list)
+        }
+    }
+}